@@ -0,0 +1,59 @@
+//! Support for the `template_encrypted()` resource: decrypting an
+//! age-encrypted file at plan/apply time so whole secret files can live in
+//! the dotfiles repo without ever committing plaintext.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// Decrypts `source` using the identities listed in `identity_file` (the
+/// same format `age-keygen` writes, one `AGE-SECRET-KEY-...` per line).
+pub fn decrypt(source: &Path, identity_file: &Path) -> Result<Vec<u8>> {
+    let identities = age::IdentityFile::from_file(identity_file.to_string_lossy().into_owned())
+        .with_context(|| {
+            format!(
+                "failed to read age identity file `{}`",
+                identity_file.display()
+            )
+        })?
+        .into_identities()
+        .with_context(|| {
+            format!(
+                "failed to parse age identity file `{}`",
+                identity_file.display()
+            )
+        })?;
+
+    let ciphertext =
+        std::fs::read(source).with_context(|| format!("failed to read `{}`", source.display()))?;
+
+    let decryptor = age::Decryptor::new_buffered(&ciphertext[..])
+        .with_context(|| format!("`{}` is not a valid age file", source.display()))?;
+
+    let mut reader = decryptor
+        .decrypt(
+            identities
+                .iter()
+                .map(|identity| identity.as_ref() as &dyn age::Identity),
+        )
+        .with_context(|| {
+            format!(
+                "failed to decrypt `{}`; check that `{}` holds the matching identity",
+                source.display(),
+                identity_file.display()
+            )
+        })?;
+
+    let mut plaintext = Vec::new();
+    reader
+        .read_to_end(&mut plaintext)
+        .with_context(|| format!("failed to decrypt `{}`", source.display()))?;
+    Ok(plaintext)
+}
+
+/// Where `template_encrypted()` looks for identities when the manifest
+/// doesn't set `identity`, mirroring where `age-keygen` writes by default.
+pub fn default_identity_file() -> PathBuf {
+    crate::xdg::config_dir().join("age").join("keys.txt")
+}
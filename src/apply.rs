@@ -0,0 +1,993 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::agefile;
+use crate::cargo_pkg;
+use crate::cmd;
+use crate::elevate;
+use crate::fileblock;
+use crate::gitrepo;
+use crate::hooks::{self, HookCmd};
+use crate::ownership;
+use crate::pipx;
+use crate::plan::{Action, Conflict, PackageSnapshots, Plan, PlannedOperation};
+use crate::render;
+use crate::resource::{
+    AgeFileResource, CargoPackageResource, DirResource, FileBlockResource, GitRepoResource,
+    LinkFallback, PipxPackageResource, Resource, TemplateValue,
+};
+use crate::secrets::{Cache, RedactionRules};
+use crate::sudo::{self, Invoker};
+
+pub struct ApplyOptions {
+    /// Clear the immutable attribute around writes to immutable
+    /// destinations, then restore it afterwards. Requires the necessary
+    /// privileges to run `chattr`.
+    pub allow_immutable_write: bool,
+    /// Stop at the first failed or conflicting operation, reporting every
+    /// operation after it as skipped. When `false` (`--keep-going`),
+    /// independent operations keep running even after an earlier one fails.
+    pub fail_fast: bool,
+    /// Forward `cmd()` output live to our own stdout/stderr. Disabled for
+    /// `--format json-lines`, where raw command output would corrupt the
+    /// event stream.
+    pub forward_command_output: bool,
+    /// Move a `link()`/`template()` destination here instead of deleting it
+    /// outright when replacing it with managed content. `None` keeps the
+    /// old delete-on-replace behavior. Mutually exclusive with `use_trash`.
+    pub backup_dir: Option<std::path::PathBuf>,
+    /// Send a replaced `link()`/`template()` destination to the OS trash/
+    /// recycle bin instead of deleting it outright. Mutually exclusive with
+    /// `backup_dir`.
+    pub use_trash: bool,
+    /// User-configured regex patterns for scrubbing secret-shaped `cmd()`
+    /// output that keron never resolved itself, on top of the exact secret
+    /// values it did resolve.
+    pub redaction: RedactionRules,
+    /// Which elevation launcher `elevate=true` resources (and ownership
+    /// reclaiming) are allowed to use.
+    pub elevation: elevate::ElevationStrategy,
+    /// Report any operation that would need to elevate as
+    /// [`ApplyOutcome::SkippedElevation`] instead of attempting it. Meant
+    /// for CI and other non-interactive runs, where a launcher prompt would
+    /// otherwise just hang (or, now that non-interactive launcher calls
+    /// pass `-n`, fail with a possibly-confusing "a password is required").
+    pub assume_no_elevation: bool,
+}
+
+/// What happened, if anything, to a destination's previous contents before
+/// it got replaced.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Preserved {
+    None,
+    BackedUp(std::path::PathBuf),
+    Trashed,
+}
+
+/// A single applied mutation, recorded into the apply journal so `keron
+/// undo` can attempt to reverse it later.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AppliedOperation {
+    pub description: String,
+    pub resource: Resource,
+    pub preserved: Preserved,
+}
+
+/// Tally of what happened across a `Plan`'s operations, used to report
+/// "applied" vs "failed" vs "skipped due to earlier failure" separately.
+#[derive(Debug, Default, Clone)]
+pub struct ApplySummary {
+    pub applied: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Every mutation that actually happened, in application order, for
+    /// `keron undo` to journal.
+    pub applied_operations: Vec<AppliedOperation>,
+}
+
+/// How a single operation resolved, reported through [`ApplyEvent::Finished`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    Applied,
+    Noop,
+    Failed,
+    /// Skipped because an earlier operation failed and `--keep-going` wasn't
+    /// given.
+    Skipped,
+    /// Skipped because a resource named in this operation's `after` list
+    /// failed or was itself skipped, regardless of `--keep-going` — running
+    /// it anyway would apply a dependent out of order.
+    SkippedDependency,
+    /// Skipped because it needed to elevate and `--assume-no-elevation` was
+    /// given.
+    SkippedElevation,
+}
+
+/// A lifecycle event emitted as `apply()` works through a plan, so a caller
+/// can stream progress (e.g. `--format json-lines`) instead of waiting for a
+/// report built after everything has finished.
+pub enum ApplyEvent<'a> {
+    Started {
+        description: &'a str,
+    },
+    Finished {
+        description: &'a str,
+        outcome: ApplyOutcome,
+        error: Option<&'a anyhow::Error>,
+        /// What happened to the destination's previous contents, if
+        /// `--backup-dir`/`--use-trash` was given and there was something to
+        /// preserve.
+        preserved: &'a Preserved,
+    },
+}
+
+pub fn apply(
+    plan: &Plan,
+    options: &ApplyOptions,
+    on_event: &mut dyn FnMut(ApplyEvent),
+) -> Result<ApplySummary> {
+    // When keron itself is run under `sudo`, non-elevated writes shouldn't
+    // silently end up owned by root.
+    let invoker = sudo::detect();
+    let mut summary = ApplySummary::default();
+    let mut stop_after_failure = false;
+    // Names (from `name = "..."`) of operations that failed or were skipped,
+    // so a dependent naming one of them in `after` gets skipped too, even
+    // under `--keep-going`. Operations already run in dependency order (see
+    // `order_by_dependencies`), so every one of an operation's dependencies
+    // has already been resolved by the time we reach it here.
+    let mut unmet: std::collections::HashSet<String> = std::collections::HashSet::new();
+    // Manifests (by path) that either failed outright or were themselves
+    // skipped because a manifest they `depends_on(...)` was in this set, so
+    // the block propagates transitively through a chain of dependent
+    // manifests.
+    let mut blocked_manifests: std::collections::HashSet<std::path::PathBuf> =
+        std::collections::HashSet::new();
+    // Elevated `dir()` chmods, queued instead of run immediately so the
+    // whole plan's worth of them can go through one launcher prompt; see
+    // the flush after the loop below.
+    let mut deferred_elevated: Vec<(String, elevate::BatchedCommand)> = Vec::new();
+
+    for chunk in plan
+        .operations
+        .chunk_by(|a, b| a.manifest_path == b.manifest_path)
+    {
+        let manifest_path = &chunk[0].manifest_path;
+        let manifest_hooks = plan.manifest_hooks.get(manifest_path);
+
+        let blocking_dependency = manifest_hooks
+            .map(|hooks| hooks.depends_on.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .find(|dependency| blocked_manifests.contains(*dependency));
+
+        if let Some(dependency) = blocking_dependency {
+            for operation in chunk {
+                summary.skipped += 1;
+                on_event(ApplyEvent::Finished {
+                    description: &operation.description,
+                    outcome: ApplyOutcome::SkippedDependency,
+                    error: Some(&anyhow::anyhow!(
+                        "`depends_on(\"{}\")` failed or was skipped",
+                        dependency.display()
+                    )),
+                    preserved: &Preserved::None,
+                });
+            }
+            blocked_manifests.insert(manifest_path.clone());
+            continue;
+        }
+
+        if !stop_after_failure {
+            if let Some(pre_cmds) = manifest_hooks
+                .map(|hooks| &hooks.pre_cmds)
+                .filter(|cmds| !cmds.is_empty())
+            {
+                run_manifest_hooks(pre_cmds, "pre_cmd", manifest_path, options, on_event)?;
+            }
+        }
+
+        let mut manifest_changed = false;
+        let mut manifest_failed = false;
+
+        for operation in chunk {
+            let description = &operation.description;
+            let blocked_by = operation
+                .after
+                .iter()
+                .find(|dependency| unmet.contains(*dependency));
+
+            if !stop_after_failure {
+                if let Some(dependency) = blocked_by {
+                    summary.skipped += 1;
+                    if let Some(name) = &operation.name {
+                        unmet.insert(name.clone());
+                    }
+                    on_event(ApplyEvent::Finished {
+                        description,
+                        outcome: ApplyOutcome::SkippedDependency,
+                        error: Some(&anyhow::anyhow!(
+                            "`after` dependency `{dependency}` failed or was skipped"
+                        )),
+                        preserved: &Preserved::None,
+                    });
+                    continue;
+                }
+            }
+
+            if stop_after_failure {
+                summary.skipped += 1;
+                on_event(ApplyEvent::Finished {
+                    description,
+                    outcome: ApplyOutcome::Skipped,
+                    error: None,
+                    preserved: &Preserved::None,
+                });
+                continue;
+            }
+
+            if options.assume_no_elevation && needs_elevation(operation, options) {
+                summary.skipped += 1;
+                on_event(ApplyEvent::Finished {
+                    description,
+                    outcome: ApplyOutcome::SkippedElevation,
+                    error: Some(&anyhow::anyhow!(
+                        "needs elevation, skipped due to `--assume-no-elevation`"
+                    )),
+                    preserved: &Preserved::None,
+                });
+                continue;
+            }
+
+            on_event(ApplyEvent::Started { description });
+
+            match apply_operation(
+                operation,
+                options,
+                invoker.as_ref(),
+                &plan.package_snapshot,
+                &plan.secret_cache,
+                &mut deferred_elevated,
+            ) {
+                Ok((true, preserved)) => {
+                    summary.applied += 1;
+                    manifest_changed = true;
+                    on_event(ApplyEvent::Finished {
+                        description,
+                        outcome: ApplyOutcome::Applied,
+                        error: None,
+                        preserved: &preserved,
+                    });
+                    summary.applied_operations.push(AppliedOperation {
+                        description: description.clone(),
+                        resource: operation.resource.clone(),
+                        preserved,
+                    });
+                }
+                Ok((false, _)) => on_event(ApplyEvent::Finished {
+                    description,
+                    outcome: ApplyOutcome::Noop,
+                    error: None,
+                    preserved: &Preserved::None,
+                }),
+                Err(err) => {
+                    summary.failed += 1;
+                    manifest_failed = true;
+                    if let Some(name) = &operation.name {
+                        unmet.insert(name.clone());
+                    }
+                    on_event(ApplyEvent::Finished {
+                        description,
+                        outcome: ApplyOutcome::Failed,
+                        error: Some(&err),
+                        preserved: &Preserved::None,
+                    });
+                    if options.fail_fast {
+                        stop_after_failure = true;
+                    }
+                }
+            }
+        }
+
+        if let Some(hooks) = manifest_hooks {
+            let run_by_default = manifest_changed && !manifest_failed;
+            let post_cmds: Vec<HookCmd> = hooks
+                .post_cmds
+                .iter()
+                .filter(|hook| hook.always || run_by_default)
+                .cloned()
+                .collect();
+            if !post_cmds.is_empty() {
+                run_manifest_hooks(&post_cmds, "post_cmd", manifest_path, options, on_event)?;
+            }
+        }
+
+        if manifest_failed {
+            blocked_manifests.insert(manifest_path.clone());
+        }
+    }
+
+    if !deferred_elevated.is_empty() {
+        flush_deferred_elevated(&deferred_elevated, options.elevation, &mut summary)?;
+    }
+
+    if summary.failed > 0 {
+        bail!(
+            "apply failed: {} applied, {} failed, {} skipped",
+            summary.applied,
+            summary.failed,
+            summary.skipped
+        );
+    }
+    Ok(summary)
+}
+
+/// Runs every queued elevated `dir()` chmod as one batch (one launcher
+/// prompt for the whole run instead of one per directory), folding any
+/// per-command failure into `summary.failed` so it still fails the overall
+/// apply the same way a regular operation failure would.
+fn flush_deferred_elevated(
+    deferred: &[(String, elevate::BatchedCommand)],
+    elevation: elevate::ElevationStrategy,
+    summary: &mut ApplySummary,
+) -> Result<()> {
+    let commands: Vec<elevate::BatchedCommand> = deferred
+        .iter()
+        .map(|(_, command)| command.clone())
+        .collect();
+
+    match elevate::run_privileged_batch(&commands, elevation) {
+        Ok(results) => {
+            for ((description, _), result) in deferred.iter().zip(results) {
+                if let Err(err) = result {
+                    summary.failed += 1;
+                    eprintln!("failed to apply `{description}`: {err}");
+                }
+            }
+            Ok(())
+        }
+        Err(err) => {
+            summary.failed += deferred.len();
+            eprintln!(
+                "failed to batch {} elevated dir chmod(s): {err}",
+                deferred.len()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Runs a manifest's `pre_cmd()`/`post_cmd()` hooks as a single pseudo-step,
+/// reported through the same [`ApplyEvent`] stream as regular operations so
+/// progress output and `--format json-lines` see them too. Unlike a resource
+/// operation, a failing hook has no "skip and keep going" fallback: it's
+/// propagated as a hard error, aborting the apply even under `--keep-going`.
+fn run_manifest_hooks(
+    hooks: &[HookCmd],
+    kind: &str,
+    manifest_path: &Path,
+    options: &ApplyOptions,
+    on_event: &mut dyn FnMut(ApplyEvent),
+) -> Result<()> {
+    let description = format!("{kind}() in `{}`", manifest_path.display());
+    on_event(ApplyEvent::Started {
+        description: &description,
+    });
+    match hooks::run_all(hooks, options.forward_command_output, &options.redaction) {
+        Ok(()) => {
+            on_event(ApplyEvent::Finished {
+                description: &description,
+                outcome: ApplyOutcome::Applied,
+                error: None,
+                preserved: &Preserved::None,
+            });
+            Ok(())
+        }
+        Err(err) => {
+            on_event(ApplyEvent::Finished {
+                description: &description,
+                outcome: ApplyOutcome::Failed,
+                error: Some(&err),
+                preserved: &Preserved::None,
+            });
+            Err(err)
+        }
+    }
+}
+
+/// Whether applying `operation` would need to invoke an elevation launcher
+/// (`sudo`/`doas`), so `--assume-no-elevation` can skip it upfront instead
+/// of finding out partway through applying it.
+fn needs_elevation(operation: &PlannedOperation, options: &ApplyOptions) -> bool {
+    if options.allow_immutable_write
+        && matches!(operation.action, Action::Conflict(Conflict::Immutable))
+    {
+        // `set_immutable`'s `chattr` toggle always elevates, regardless of
+        // the resource's own `elevate` flag.
+        return true;
+    }
+
+    match &operation.resource {
+        Resource::Dir(dir) => dir.elevate && dir.mode.is_some(),
+        Resource::Link(link) => link.elevate && (link.owner.is_some() || link.group.is_some()),
+        Resource::Template(template) => {
+            template.elevate && (template.owner.is_some() || template.group.is_some())
+        }
+        _ => false,
+    }
+}
+
+/// Applies a single operation, returning whether it changed anything
+/// (`false` for a `Noop`) and what happened to its destination's previous
+/// contents, if anything.
+fn apply_operation(
+    operation: &PlannedOperation,
+    options: &ApplyOptions,
+    invoker: Option<&Invoker>,
+    package_snapshot: &PackageSnapshots,
+    secret_cache: &Cache,
+    deferred_elevated: &mut Vec<(String, elevate::BatchedCommand)>,
+) -> Result<(bool, Preserved)> {
+    match &operation.action {
+        Action::Noop => Ok((false, Preserved::None)),
+        // Nothing to apply: the query that would tell us was skipped, and
+        // an install/checkout would need the same network access.
+        Action::Unknown(_) => Ok((false, Preserved::None)),
+        Action::Create | Action::Update => {
+            let preserved = apply_resource(
+                &operation.resource,
+                options,
+                package_snapshot,
+                secret_cache,
+                deferred_elevated,
+            )?;
+            reclaim_ownership(&operation.resource, invoker)?;
+            apply_explicit_ownership(&operation.resource, options.elevation)?;
+            Ok((true, preserved))
+        }
+        Action::Adopt => {
+            let Resource::Link(link) = &operation.resource else {
+                bail!("`Action::Adopt` planned for a non-link resource");
+            };
+            let preserved = apply_adopt(link, options)?;
+            reclaim_ownership(&operation.resource, invoker)?;
+            apply_explicit_ownership(&operation.resource, options.elevation)?;
+            Ok((true, preserved))
+        }
+        Action::Remove => {
+            let preserved = remove_resource(&operation.resource, options)?;
+            Ok((true, preserved))
+        }
+        Action::Conflict(Conflict::Immutable) if options.allow_immutable_write => {
+            let preserved = apply_through_immutable(
+                &operation.resource,
+                options,
+                package_snapshot,
+                secret_cache,
+                deferred_elevated,
+            )?;
+            Ok((true, preserved))
+        }
+        Action::Conflict(conflict) => bail!("{}", conflict.describe()),
+    }
+}
+
+/// Applies a `link()`/`template()`'s explicit `owner`/`group` opts, if any
+/// were set, after `reclaim_ownership` has had its say.
+fn apply_explicit_ownership(
+    resource: &Resource,
+    elevation: elevate::ElevationStrategy,
+) -> Result<()> {
+    match resource {
+        Resource::Link(link) => ownership::chown(
+            &link.destination,
+            link.owner.as_deref(),
+            link.group.as_deref(),
+            link.elevate,
+            elevation,
+        ),
+        Resource::Template(template) => ownership::chown(
+            &template.destination,
+            template.owner.as_deref(),
+            template.group.as_deref(),
+            template.elevate,
+            elevation,
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Chowns whatever a non-elevated resource wrote back to the invoking user.
+/// Resources (or directories) that requested `elevate = true` are left
+/// owned by root, since that was requested on purpose.
+fn reclaim_ownership(resource: &Resource, invoker: Option<&Invoker>) -> Result<()> {
+    let Some(invoker) = invoker else {
+        return Ok(());
+    };
+
+    match resource {
+        // An explicit `owner`/`group` opt takes over from the invoking-uid
+        // reclaim; `apply_explicit_ownership` sets it instead, below.
+        Resource::Link(link) if link.owner.is_none() && link.group.is_none() => {
+            sudo::chown_to_invoker(&link.destination, invoker, false)
+        }
+        Resource::Link(_) => Ok(()),
+        Resource::Template(template) if template.owner.is_none() && template.group.is_none() => {
+            sudo::chown_to_invoker(&template.destination, invoker, false)
+        }
+        Resource::Template(_) => Ok(()),
+        Resource::FileBlock(file_block) => {
+            sudo::chown_to_invoker(&file_block.destination, invoker, false)
+        }
+        Resource::GitRepo(git_repo) => sudo::chown_to_invoker(&git_repo.destination, invoker, true),
+        Resource::Dir(dir) if !dir.elevate => sudo::chown_to_invoker(&dir.path, invoker, false),
+        Resource::Dir(_) => Ok(()),
+        Resource::Cmd(cmd) => match &cmd.creates {
+            Some(creates) => sudo::chown_to_invoker(creates, invoker, false),
+            None => Ok(()),
+        },
+        // Lives in the provider's own package store, not somewhere keron
+        // itself wrote to.
+        Resource::PipxPackage(_) => Ok(()),
+        Resource::CargoPackage(_) => Ok(()),
+        Resource::AgeFile(age_file) => {
+            sudo::chown_to_invoker(&age_file.destination, invoker, false)
+        }
+    }
+}
+
+/// Removes a `link()`/`template()` destination planned as [`Action::Remove`]
+/// (`state = "absent"`), preserving its previous contents the same way a
+/// `Create`/`Update` would (`--backup-dir`/`--use-trash`) rather than just
+/// deleting it outright.
+fn remove_resource(resource: &Resource, options: &ApplyOptions) -> Result<Preserved> {
+    let destination = match resource {
+        Resource::Link(link) => &link.destination,
+        Resource::Template(template) => &template.destination,
+        _ => bail!("`Action::Remove` planned for a resource that doesn't support removal"),
+    };
+
+    let preserved = preserve_existing(destination, options)?;
+    if matches!(preserved, Preserved::None) {
+        std::fs::remove_file(destination)
+            .with_context(|| format!("failed to remove `{}`", destination.display()))?;
+    }
+    Ok(preserved)
+}
+
+fn apply_resource(
+    resource: &Resource,
+    options: &ApplyOptions,
+    package_snapshot: &PackageSnapshots,
+    secret_cache: &Cache,
+    deferred_elevated: &mut Vec<(String, elevate::BatchedCommand)>,
+) -> Result<Preserved> {
+    match resource {
+        Resource::Link(link) => create_link(
+            &link.source,
+            &link.destination,
+            link.windows_fallback,
+            options,
+        ),
+        Resource::Template(template) => write_template(template, secret_cache, options),
+        Resource::GitRepo(git_repo) => sync_git_repo(git_repo).map(|()| Preserved::None),
+        Resource::FileBlock(file_block) => write_file_block(file_block).map(|()| Preserved::None),
+        Resource::Cmd(resource) => {
+            // Apply always resolves secrets for real, unlike plan-time diffing.
+            let env = render::resolve_map(&resource.env, true, secret_cache)?;
+            let redact: Vec<String> = resource
+                .env
+                .iter()
+                .filter(|(_, value)| match value {
+                    TemplateValue::Secret(_) | TemplateValue::SensitiveStr(_) => true,
+                    TemplateValue::EnvVar(env_var) => env_var.sensitive,
+                    TemplateValue::Str(_) | TemplateValue::List(_) | TemplateValue::Table(_) => {
+                        false
+                    }
+                })
+                .filter_map(|(name, _)| env.get(name).cloned())
+                .collect();
+            cmd::run(
+                &resource.command,
+                &cmd::RunOptions {
+                    env: &env,
+                    cwd: resource.cwd.as_deref(),
+                    redact: &redact,
+                    redact_patterns: &options.redaction,
+                    retries: resource.retries,
+                    timeout: resource.timeout,
+                    forward_output: options.forward_command_output,
+                },
+            )
+            .map(|()| Preserved::None)
+        }
+        Resource::Dir(dir) => apply_dir(dir, deferred_elevated).map(|()| Preserved::None),
+        Resource::PipxPackage(package) => {
+            install_pipx_package(package, &package_snapshot.pipx).map(|()| Preserved::None)
+        }
+        Resource::CargoPackage(package) => install_cargo_package(package).map(|()| Preserved::None),
+        Resource::AgeFile(age_file) => write_age_file(age_file).map(|()| Preserved::None),
+    }
+}
+
+/// Seeds `link.source` from `link.destination`'s current content if
+/// `source` doesn't exist yet (planning already confirmed the content
+/// matches when both exist), then hands off to the normal link creation so
+/// the existing preserve/backup/trash handling for the previous destination
+/// applies uniformly.
+fn apply_adopt(link: &crate::resource::LinkResource, options: &ApplyOptions) -> Result<Preserved> {
+    if !link.source.exists() {
+        if let Some(parent) = link.source.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create `{}`", parent.display()))?;
+        }
+        std::fs::copy(&link.destination, &link.source).with_context(|| {
+            format!(
+                "failed to adopt `{}` into `{}`",
+                link.destination.display(),
+                link.source.display()
+            )
+        })?;
+    }
+    create_link(
+        &link.source,
+        &link.destination,
+        link.windows_fallback,
+        options,
+    )
+}
+
+/// Installs `package`, skipping the actual install if the plan-time
+/// snapshot already shows it satisfied — the plan may have picked
+/// `Action::Update` for a constrained pin it couldn't verify (see
+/// `plan::plan_pipx_package`), and re-running an install pipx/uv already
+/// considers current is just wasted work.
+fn install_pipx_package(
+    package: &PipxPackageResource,
+    package_snapshot: &pipx::Snapshot,
+) -> Result<()> {
+    let installed = package_snapshot.installed(package.provider)?;
+    if let Some(installed_version) = installed.get(&package.name) {
+        if matches!(
+            pipx::parse_version_pin(package.version.as_deref()),
+            pipx::VersionPin::Constrained(_)
+        ) {
+            eprintln!(
+                "pipx_package `{}`: already present at `{installed_version}`, skipping install for unverifiable constraint",
+                package.name
+            );
+            return Ok(());
+        }
+    }
+
+    pipx::install(
+        package.provider,
+        &package.name,
+        package.version.as_deref(),
+        package.retries,
+        package.timeout,
+    )
+}
+
+/// Installs `package` via `cargo install`. Unlike `install_pipx_package`,
+/// there's no plan-time-satisfied case to short-circuit: `plan_cargo_package`
+/// only ever picks `Action::Update`/`Action::Create` when the installed
+/// version actually differs (or is absent), since cargo reports exact
+/// versions, not constraints.
+fn install_cargo_package(package: &CargoPackageResource) -> Result<()> {
+    cargo_pkg::install(package)
+}
+
+fn write_age_file(age_file: &AgeFileResource) -> Result<()> {
+    let plaintext = agefile::decrypt(&age_file.source, &age_file.identity)?;
+
+    if let Some(parent) = age_file.destination.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+    std::fs::write(&age_file.destination, plaintext)
+        .with_context(|| format!("failed to write `{}`", age_file.destination.display()))
+}
+
+fn apply_dir(
+    resource: &DirResource,
+    deferred_elevated: &mut Vec<(String, elevate::BatchedCommand)>,
+) -> Result<()> {
+    if !resource.path.exists() {
+        if resource.mkdirs {
+            std::fs::create_dir_all(&resource.path)
+        } else {
+            std::fs::create_dir(&resource.path)
+        }
+        .with_context(|| format!("failed to create `{}`", resource.path.display()))?;
+    }
+
+    if let Some(mode) = resource.mode {
+        set_dir_mode(&resource.path, mode, resource.elevate, deferred_elevated)?;
+    }
+    Ok(())
+}
+
+/// Sets `path`'s permissions to `mode`. When `elevate` is set, the actual
+/// `chmod` isn't run here — it's queued into `deferred_elevated` so the
+/// whole apply run's elevated dir modes can go through one launcher prompt
+/// instead of one per directory; `apply` flushes the queue once it's done
+/// with every operation.
+fn set_dir_mode(
+    path: &Path,
+    mode: u32,
+    elevate: bool,
+    deferred_elevated: &mut Vec<(String, elevate::BatchedCommand)>,
+) -> Result<()> {
+    let mode_octal = format!("{mode:o}");
+    if !elevate {
+        return std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("failed to set permissions on `{}`", path.display()));
+    }
+
+    deferred_elevated.push((
+        format!("chmod {mode_octal} `{}`", path.display()),
+        elevate::BatchedCommand {
+            program: "chmod".to_string(),
+            args: vec![mode_octal, path.to_string_lossy().into_owned()],
+        },
+    ));
+    Ok(())
+}
+
+fn write_file_block(file_block: &FileBlockResource) -> Result<()> {
+    let existing = std::fs::read_to_string(&file_block.destination).unwrap_or_default();
+    let block = fileblock::render_block(&file_block.content, &file_block.marker);
+    let merged = fileblock::merge_block(&existing, &block, &file_block.marker);
+
+    if let Some(parent) = file_block.destination.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+    std::fs::write(&file_block.destination, merged)
+        .with_context(|| format!("failed to write `{}`", file_block.destination.display()))
+}
+
+fn sync_git_repo(git_repo: &GitRepoResource) -> Result<()> {
+    let reference = git_repo.reference.as_deref();
+    if gitrepo::current_commit(&git_repo.destination).is_some() {
+        gitrepo::fetch_and_checkout(&git_repo.destination, reference, git_repo.depth)
+    } else {
+        gitrepo::clone(
+            &git_repo.url,
+            &git_repo.destination,
+            reference,
+            git_repo.depth,
+        )
+    }
+}
+
+fn write_template(
+    template: &crate::resource::TemplateResource,
+    secret_cache: &Cache,
+    options: &ApplyOptions,
+) -> Result<Preserved> {
+    let source = std::fs::read_to_string(&template.source).with_context(|| {
+        format!(
+            "failed to read template source `{}`",
+            template.source.display()
+        )
+    })?;
+    // Apply always resolves secrets for real, unlike plan-time diffing.
+    let vars = render::with_existing_content(&template.vars, &template.destination);
+    let vars = render::with_facts(&vars, &crate::facts::Facts::gather());
+    let rendered = render::render(&source, &vars, true, secret_cache)?;
+    let rendered = match template.newline {
+        Some(newline) => render::normalize_newlines(&rendered, newline),
+        None => rendered,
+    };
+
+    let preserved = preserve_existing(&template.destination, options)?;
+
+    if let Some(parent) = template.destination.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+    std::fs::write(&template.destination, rendered)
+        .with_context(|| format!("failed to write `{}`", template.destination.display()))?;
+    Ok(preserved)
+}
+
+/// Preserves `destination`'s previous contents instead of letting them be
+/// silently overwritten/removed, so replacing a file keron doesn't manage
+/// never destroys data the user might still want. Sends it to the OS trash
+/// when `use_trash` is set, otherwise moves it into `backup_dir` when given.
+/// Returns [`Preserved::None`] (and leaves `destination` for the caller to
+/// remove as usual) when neither is set, or there's nothing at `destination`
+/// to preserve.
+fn preserve_existing(destination: &Path, options: &ApplyOptions) -> Result<Preserved> {
+    if !destination.is_symlink() && !destination.exists() {
+        return Ok(Preserved::None);
+    }
+
+    if options.use_trash {
+        trash::delete(destination)
+            .with_context(|| format!("failed to send `{}` to trash", destination.display()))?;
+        return Ok(Preserved::Trashed);
+    }
+
+    let Some(backup_dir) = &options.backup_dir else {
+        return Ok(Preserved::None);
+    };
+
+    std::fs::create_dir_all(backup_dir)
+        .with_context(|| format!("failed to create `{}`", backup_dir.display()))?;
+
+    let name = destination
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let backup_path = backup_dir.join(format!("{name}.{timestamp}"));
+
+    std::fs::rename(destination, &backup_path).with_context(|| {
+        format!(
+            "failed to back up `{}` to `{}`",
+            destination.display(),
+            backup_path.display()
+        )
+    })?;
+    Ok(Preserved::BackedUp(backup_path))
+}
+
+#[cfg(unix)]
+fn create_link(
+    source: &Path,
+    destination: &Path,
+    _windows_fallback: Option<LinkFallback>,
+    options: &ApplyOptions,
+) -> Result<Preserved> {
+    let preserved = preserve_existing(destination, options)?;
+    if matches!(preserved, Preserved::None) && (destination.is_symlink() || destination.exists()) {
+        std::fs::remove_file(destination)
+            .with_context(|| format!("failed to remove existing `{}`", destination.display()))?;
+    }
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+    std::os::unix::fs::symlink(source, destination).with_context(|| {
+        format!(
+            "failed to symlink `{}` -> `{}`",
+            destination.display(),
+            source.display()
+        )
+    })?;
+    Ok(preserved)
+}
+
+/// Without Developer Mode, creating a symlink on Windows fails with
+/// `ERROR_PRIVILEGE_NOT_HELD` and only a cryptic hint pointing at it, so a
+/// manifest can opt into a degraded but always-available stand-in instead of
+/// failing outright.
+#[cfg(windows)]
+fn create_link(
+    source: &Path,
+    destination: &Path,
+    windows_fallback: Option<LinkFallback>,
+    options: &ApplyOptions,
+) -> Result<Preserved> {
+    let preserved = preserve_existing(destination, options)?;
+    if matches!(preserved, Preserved::None) && destination.exists() {
+        std::fs::remove_file(destination)
+            .or_else(|_| std::fs::remove_dir(destination))
+            .with_context(|| format!("failed to remove existing `{}`", destination.display()))?;
+    }
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+
+    let symlink_result = if source.is_dir() {
+        std::os::windows::fs::symlink_dir(source, destination)
+    } else {
+        std::os::windows::fs::symlink_file(source, destination)
+    };
+    let Err(symlink_err) = symlink_result else {
+        return Ok(preserved);
+    };
+
+    match windows_fallback {
+        Some(LinkFallback::Hardlink) if !source.is_dir() => std::fs::hard_link(source, destination)
+            .with_context(|| {
+                format!(
+                    "failed to hardlink `{}` -> `{}`",
+                    destination.display(),
+                    source.display()
+                )
+            }),
+        Some(LinkFallback::Copy) if !source.is_dir() => std::fs::copy(source, destination)
+            .map(|_| ())
+            .with_context(|| {
+                format!(
+                    "failed to copy `{}` -> `{}`",
+                    source.display(),
+                    destination.display()
+                )
+            }),
+        // Directory junctions don't need the symlink privilege plain
+        // symlinks do, but creating one needs a raw reparse-point syscall
+        // that std doesn't expose, so a directory copy is the closest
+        // approximation available without an extra dependency.
+        Some(LinkFallback::Junction) | Some(LinkFallback::Copy) if source.is_dir() => {
+            copy_dir_recursive(source, destination)
+        }
+        Some(fallback) => bail!(
+            "`windows_fallback = \"{}\"` doesn't apply to `{}`",
+            fallback.label(),
+            source.display()
+        ),
+        None => Err(symlink_err).with_context(|| {
+            format!(
+                "failed to symlink `{}` -> `{}` (enable Developer Mode, or set `windows_fallback`)",
+                destination.display(),
+                source.display()
+            )
+        }),
+    }
+    .map(|()| preserved)
+}
+
+#[cfg(windows)]
+fn copy_dir_recursive(source: &Path, destination: &Path) -> Result<()> {
+    std::fs::create_dir_all(destination)
+        .with_context(|| format!("failed to create `{}`", destination.display()))?;
+    for entry in std::fs::read_dir(source)
+        .with_context(|| format!("failed to read `{}`", source.display()))?
+    {
+        let entry = entry?;
+        let dest_entry = destination.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_recursive(&entry.path(), &dest_entry)?;
+        } else {
+            std::fs::copy(entry.path(), &dest_entry)
+                .with_context(|| format!("failed to copy `{}`", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn apply_through_immutable(
+    resource: &Resource,
+    options: &ApplyOptions,
+    package_snapshot: &PackageSnapshots,
+    secret_cache: &Cache,
+    deferred_elevated: &mut Vec<(String, elevate::BatchedCommand)>,
+) -> Result<Preserved> {
+    let destination = resource.destination().to_path_buf();
+
+    set_immutable(&destination, false, options.elevation)?;
+    let result = apply_resource(
+        resource,
+        options,
+        package_snapshot,
+        secret_cache,
+        deferred_elevated,
+    );
+    // Restore the attribute regardless of whether the write succeeded, so a
+    // failed apply doesn't leave the destination silently mutable.
+    set_immutable(&destination, true, options.elevation)?;
+    result
+}
+
+fn set_immutable(
+    path: &Path,
+    immutable: bool,
+    elevation: elevate::ElevationStrategy,
+) -> Result<()> {
+    let flag = if immutable { "+i" } else { "-i" };
+    let path = path.to_string_lossy();
+    elevate::run_privileged("chattr", &[flag, &path], elevation)
+        .with_context(|| format!("failed to run `chattr {flag} {path}`"))?;
+    Ok(())
+}
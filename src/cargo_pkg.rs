@@ -0,0 +1,105 @@
+//! Support for the `cargo_package()` resource: installing Rust binaries via
+//! `cargo install`, and checking what's already installed by parsing
+//! `cargo install --list`.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::resource::CargoPackageResource;
+use crate::subprocess::{self, Limits};
+
+/// Caches `cargo install --list` for the lifetime of one `plan`/`apply` run,
+/// so any number of `cargo_package()` resources trigger at most one listing
+/// call between them, instead of one per resource.
+#[derive(Default)]
+pub struct Snapshot {
+    installed: RefCell<Option<BTreeMap<String, String>>>,
+}
+
+impl Snapshot {
+    /// Returns the installed-package listing, querying it only on the first
+    /// call.
+    pub fn installed(&self) -> Result<BTreeMap<String, String>> {
+        if let Some(installed) = self.installed.borrow().as_ref() {
+            return Ok(installed.clone());
+        }
+
+        let installed = list_installed()?;
+        *self.installed.borrow_mut() = Some(installed.clone());
+        Ok(installed)
+    }
+}
+
+/// `cargo install --list` prints one un-indented header line per installed
+/// package (`name vX.Y.Z:`, or `name vX.Y.Z (https://...#rev):` for a
+/// git-sourced install), followed by indented lines naming the binaries it
+/// installed.
+fn list_installed() -> Result<BTreeMap<String, String>> {
+    let mut command = Command::new("cargo");
+    command.args(["install", "--list"]);
+    let output = subprocess::run_captured(&mut command, &Limits::default())
+        .context("failed to run `cargo install --list`")?;
+    if !output.status.success() {
+        bail!(
+            "`cargo install --list` failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.starts_with(char::is_whitespace) && !line.trim().is_empty())
+        .filter_map(|line| {
+            let header = line.trim_end_matches(':');
+            let mut parts = header.split_whitespace();
+            let name = parts.next()?;
+            let version = parts.next()?.trim_start_matches('v');
+            Some((name.to_string(), version.to_string()))
+        })
+        .collect())
+}
+
+/// Installs `resource` via `cargo install`.
+pub fn install(resource: &CargoPackageResource) -> Result<()> {
+    let mut args: Vec<String> = vec!["install".to_string()];
+    if let Some(git) = &resource.git {
+        args.push("--git".to_string());
+        args.push(git.clone());
+    }
+    if let Some(version) = &resource.version {
+        args.push("--version".to_string());
+        args.push(version.clone());
+    }
+    if resource.locked {
+        args.push("--locked".to_string());
+    }
+    if !resource.features.is_empty() {
+        args.push("--features".to_string());
+        args.push(resource.features.join(","));
+    }
+    args.push(resource.name.clone());
+
+    let limits = Limits {
+        timeout: resource.timeout,
+        ..Limits::default()
+    };
+    subprocess::retry_with_backoff(resource.retries, |_attempt| {
+        let mut command = Command::new("cargo");
+        command.args(&args);
+        let output = subprocess::run_captured(&mut command, &limits)
+            .with_context(|| format!("failed to run `cargo {}`", args.join(" ")))?;
+        if !output.status.success() {
+            bail!(
+                "`cargo {}` failed with {}: {}",
+                args.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    })
+}
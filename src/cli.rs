@@ -0,0 +1,470 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+
+/// Keron: an opinionated dotfile manager.
+#[derive(Debug, Parser)]
+#[command(name = "keron", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Log verbosity (`error`, `warn`, `info`, `debug`, `trace`, or an
+    /// `EnvFilter` directive string like `keron=debug`). Overrides
+    /// `RUST_LOG` if both are set; defaults to `warn` if neither is.
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+
+    /// Also write logs to this file, in addition to stderr.
+    #[arg(long, global = true)]
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Show what would change without touching the filesystem.
+    Plan(PlanArgs),
+    /// Compare two `keron plan -o` files and print which operations
+    /// appeared, disappeared, or changed action.
+    PlanDiff(PlanDiffArgs),
+    /// Apply a manifest source (local directory or git URL).
+    Apply(ApplyArgs),
+    /// Show which package managers are detected on this host.
+    Providers(ProvidersArgs),
+    /// Exit `0` if nothing has drifted, `2` if it has, printing nothing
+    /// either way. For polling from a status bar or a systemd timer, where a
+    /// full `keron plan` would be wasted work.
+    CheckDrift(CheckDriftArgs),
+    /// Reverse the most recent `keron apply` in this directory, where
+    /// possible.
+    Undo(UndoArgs),
+    /// Move existing files into a manifest repo, write `link()` entries for
+    /// them, and symlink them back in place.
+    Import(ImportArgs),
+    /// Print a shell completion script to stdout.
+    Completions(CompletionsArgs),
+    /// Print built-in reference documentation.
+    Docs(DocsArgs),
+    /// List locally recorded `keron apply` runs, or re-render one in detail.
+    History(HistoryArgs),
+    /// Browse manifests and their drift status, and apply one at a time,
+    /// instead of scrolling a single flat `keron plan`/`keron apply` run.
+    Tui(TuiArgs),
+    /// Export each manifest's resource `after`/`notify` edges as a graph, to
+    /// see why execution order is what it is or spot accidental coupling.
+    Graph(GraphArgs),
+    /// Convert a legacy recipe file into an equivalent Lua manifest.
+    Migrate(MigrateArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct MigrateArgs {
+    /// Legacy format to convert from. `hcl` is the only one understood so
+    /// far.
+    pub format: MigrateFormat,
+
+    /// Legacy recipe file to convert.
+    pub file: PathBuf,
+
+    /// Write the converted Lua here instead of printing it to stdout.
+    /// Appends if the file already exists.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+/// A legacy format `keron migrate` knows how to convert from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MigrateFormat {
+    Hcl,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct TuiArgs {
+    #[command(flatten)]
+    pub source_args: SourceArgs,
+
+    /// Skip package provider queries and remote git lookups, so link/
+    /// template changes can still be browsed without a network connection.
+    #[arg(long)]
+    pub offline: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct GraphArgs {
+    #[command(flatten)]
+    pub source_args: SourceArgs,
+
+    /// Output format. `dot` is the only one understood so far; pipe it
+    /// through `dot -Tsvg` (Graphviz) to render it.
+    #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+    pub format: GraphFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    Dot,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    pub command: Option<HistoryCommand>,
+
+    /// Print paths as-is instead of shortening `$HOME` to `~`.
+    #[arg(long)]
+    pub absolute_paths: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HistoryCommand {
+    /// Re-render the stored per-operation report for a single run.
+    Show {
+        /// The run id, as printed by a plain `keron history`.
+        id: u64,
+    },
+}
+
+/// A documentation topic `keron docs` can print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DocsTopic {
+    /// The Lua manifest API: every resource constructor and helper function
+    /// `manifest::lua` registers, generated from the same list `keron`
+    /// itself is built against.
+    Lua,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DocsArgs {
+    pub topic: DocsTopic,
+
+    /// Always print directly instead of paging, even if the output would
+    /// overflow the terminal.
+    #[arg(long)]
+    pub no_pager: bool,
+
+    /// Command to pipe output through instead of `$PAGER`/`less`.
+    #[arg(long)]
+    pub pager: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate completions for.
+    pub shell: Shell,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct SourceArgs {
+    /// Local path or git URL (e.g. `https://github.com/user/dotfiles`). A
+    /// local path may point directly at a single `.lua` manifest file
+    /// instead of a tree, to target just that manifest (see
+    /// [`crate::manifest::discover`]). If omitted, falls back to the
+    /// `source` key in `keron`'s own config file, then a manifest tree
+    /// detected in the current directory or `~/.dotfiles`.
+    pub source: Option<String>,
+
+    /// Force a fresh clone instead of reusing the on-disk source cache.
+    #[arg(long)]
+    pub refresh: bool,
+}
+
+impl SourceArgs {
+    /// Returns the given `source`, or resolves a default one (see
+    /// [`crate::source::default_source`]) when none was given.
+    pub fn resolve(&self) -> anyhow::Result<String> {
+        match &self.source {
+            Some(source) => Ok(source.clone()),
+            None => crate::source::default_source(),
+        }
+    }
+}
+
+/// How `keron plan` orders and sections its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// One line per operation, in manifest discovery order.
+    Flat,
+    /// A section header per manifest, its operations nested underneath, and
+    /// a per-manifest change count.
+    Manifest,
+}
+
+/// How `keron plan` renders its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PlanFormat {
+    /// The usual `+`/`~`/`!` marker lines.
+    Text,
+    /// A compact GitHub-flavored markdown summary (a tally table plus a list
+    /// of changed resources), for posting a plan as a CI PR comment.
+    Markdown,
+    /// JUnit XML, one `<testcase>` per operation: a conflict becomes a
+    /// `<failure>`, an `--offline`-skipped unknown becomes a `<skipped>`, so
+    /// a CI system that already understands JUnit can gate on and display
+    /// plan problems without a keron-specific integration.
+    Junit,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct PlanArgs {
+    #[command(flatten)]
+    pub source_args: SourceArgs,
+
+    /// Resolve `secret_ref()` values for real instead of diffing a
+    /// redaction placeholder against the secret backend.
+    #[arg(long)]
+    pub resolve_secrets: bool,
+
+    /// Skip package provider queries and remote git lookups, so link/
+    /// template changes can still be planned without a network connection.
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Print paths as-is instead of shortening `$HOME` to `~`.
+    #[arg(long)]
+    pub absolute_paths: bool,
+
+    /// Append a dim `(manifest.lua)` suffix to each operation line, naming
+    /// the manifest that declared it. Useful with `--group-by flat` once a
+    /// source has enough manifests that it's not obvious at a glance.
+    #[arg(long)]
+    pub show_manifest: bool,
+
+    /// For a `cmd()` operation, also print its resolved `cwd` and `env`
+    /// beneath the marker line (redacted the same as everywhere else,
+    /// unless `--resolve-secrets` is also given), so a reviewer can see what
+    /// would actually run before approving it instead of just the bare
+    /// command string.
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Exit `2` if the plan has any pending change (including a conflict or
+    /// an offline-skipped operation) instead of always exiting `0` on
+    /// success. Off by default, so a plain `keron plan` in a cron job never
+    /// gets treated as a failure just because there's drift to review.
+    #[arg(long)]
+    pub detailed_exitcode: bool,
+
+    /// How to order and section the operation list. `manifest` prints a
+    /// header per manifest with its operations nested underneath, useful
+    /// once a source has enough manifests that a flat list interleaves them.
+    #[arg(long, value_enum, default_value_t = GroupBy::Flat)]
+    pub group_by: GroupBy,
+
+    /// How to render the plan. `markdown` prints a compact GitHub-flavored
+    /// summary and `junit` prints JUnit XML, instead of the usual marker
+    /// lines; `--group-by` and `--show-manifest` are ignored under either.
+    #[arg(long, value_enum, default_value_t = PlanFormat::Text)]
+    pub format: PlanFormat,
+
+    /// Also write the plan to this file, in a versioned JSON format that
+    /// `keron apply --plan-file` can execute later.
+    #[arg(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+
+    /// Restrict the plan to a single resource, e.g. `name:zshrc`. `name:` is
+    /// the only selector understood so far, matching the `name = "..."`
+    /// given to that resource's constructor.
+    #[arg(long)]
+    pub only: Option<String>,
+
+    /// Restrict discovery to the manifest with this file name (e.g.
+    /// `workstation.lua`), rather than evaluating every manifest under the
+    /// source. Unlike `--only`, this skips evaluating the other manifests
+    /// entirely, so it's the one to reach for when a large tree has a slow
+    /// manifest elsewhere that a narrower plan shouldn't pay for.
+    #[arg(long)]
+    pub only_manifest: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct PlanDiffArgs {
+    /// A plan file from an earlier `keron plan -o`.
+    pub old: PathBuf,
+
+    /// A plan file from a later `keron plan -o`, to compare against `old`.
+    pub new: PathBuf,
+
+    /// Print paths as-is instead of shortening `$HOME` to `~`.
+    #[arg(long)]
+    pub absolute_paths: bool,
+}
+
+/// How `keron apply` reports progress as it works through a plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable lines on stdout/stderr.
+    Text,
+    /// One JSON object per line on stdout, one per operation start/finish,
+    /// for wrappers and editors that want to render live progress.
+    JsonLines,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ApplyArgs {
+    #[command(flatten)]
+    pub source_args: SourceArgs,
+
+    /// Refuse to apply if the resolved commit differs from `keron.lock`.
+    #[arg(long)]
+    pub locked: bool,
+
+    /// Clear the immutable attribute (`chattr -i`) around writes to
+    /// immutable destinations, then restore it afterwards.
+    #[arg(long)]
+    pub allow_immutable_write: bool,
+
+    /// After applying, re-plan and fail if any operation still reports a
+    /// change, catching non-idempotent commands/templates in CI.
+    #[arg(long)]
+    pub verify_idempotent: bool,
+
+    /// Keep applying independent operations after one fails, instead of
+    /// stopping at the first failure.
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Skip package provider queries and remote git lookups, so link/
+    /// template changes can still be applied without a network connection.
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Output format for progress as operations are applied.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Print paths as-is instead of shortening `$HOME` to `~`.
+    #[arg(long)]
+    pub absolute_paths: bool,
+
+    /// Apply exactly the operations recorded in this file (from `keron plan
+    /// -o`) instead of re-planning from the manifests. Requires `--execute`.
+    #[arg(long)]
+    pub plan_file: Option<PathBuf>,
+
+    /// Actually run the plan given via `--plan-file`, rather than just
+    /// printing what it would do. Split from `--plan-file` so a reviewed
+    /// plan can't be applied by a copy-pasted command missing this flag.
+    /// Not needed together with `--interactive`, whose checkbox picker is
+    /// itself the confirmation step.
+    #[arg(long)]
+    pub execute: bool,
+
+    /// Present a checkbox list of the planned changes and only apply the
+    /// ones left checked, recording the rest as skipped in the apply
+    /// report.
+    #[arg(long)]
+    pub interactive: bool,
+
+    /// Restrict the apply to a single resource, e.g. `name:zshrc`. `name:` is
+    /// the only selector understood so far, matching the `name = "..."`
+    /// given to that resource's constructor.
+    #[arg(long)]
+    pub only: Option<String>,
+
+    /// Restrict discovery to the manifest with this file name (e.g.
+    /// `workstation.lua`), rather than evaluating every manifest under the
+    /// source. Unlike `--only`, this skips evaluating the other manifests
+    /// entirely, so it's the one to reach for when a large tree has a slow
+    /// manifest elsewhere that a narrower apply shouldn't pay for.
+    #[arg(long)]
+    pub only_manifest: Option<String>,
+
+    /// Move a `link()`/`template()` destination's previous contents here
+    /// instead of deleting them outright when replacing them with managed
+    /// content. Mutually exclusive with `--use-trash`.
+    #[arg(long)]
+    pub backup_dir: Option<PathBuf>,
+
+    /// Send a replaced `link()`/`template()` destination to the OS trash/
+    /// recycle bin instead of deleting it outright. Mutually exclusive with
+    /// `--backup-dir`.
+    #[arg(long = "use-trash")]
+    pub use_trash: bool,
+
+    /// Show a desktop notification (via `notify-send`) summarizing what
+    /// this apply did once it finishes.
+    #[arg(long)]
+    pub notify_desktop: bool,
+
+    /// POST a JSON apply report to this URL once the apply finishes.
+    #[arg(long)]
+    pub notify_webhook: Option<String>,
+
+    /// Which elevation launcher `elevate = true` resources may use.
+    /// `sudo`/`doas` pick one explicitly; `none` fails any such resource
+    /// instead of prompting. Defaults to trying `sudo`, then `doas`.
+    #[arg(long, value_enum, default_value_t = crate::elevate::ElevationStrategy::Auto)]
+    pub elevation: crate::elevate::ElevationStrategy,
+
+    /// Report any operation that would need to elevate as skipped instead
+    /// of attempting it. For CI and other non-interactive runs, where a
+    /// launcher prompt has no TTY to answer it.
+    #[arg(long)]
+    pub assume_no_elevation: bool,
+
+    /// Run `apt-get update` (elevated) once before applying, if `apt` is
+    /// detected on this host. `apt` packages are only ever installed through
+    /// a manifest's own `cmd()`, so keron has no way to tell whether one of
+    /// them is about to `apt-get install` something — this refreshes
+    /// unconditionally instead, for anyone whose manifest does.
+    #[arg(long)]
+    pub refresh_packages: bool,
+
+    /// Fail instead of applying if evaluating the manifests produced any
+    /// warnings (currently: unknown options passed to a resource
+    /// constructor, see `manifest::lua::extract_meta`), so a repo can wire
+    /// this into CI to enforce manifest hygiene rather than merely printing
+    /// warnings a reviewer might scroll past.
+    #[arg(long)]
+    pub strict: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ProvidersArgs {
+    #[command(flatten)]
+    pub source_args: SourceArgs,
+
+    /// Print the full detection snapshot as a JSON array instead of a table.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct UndoArgs {
+    /// Print paths as-is instead of shortening `$HOME` to `~`.
+    #[arg(long)]
+    pub absolute_paths: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ImportArgs {
+    /// Existing manifest repo to import into (must already exist; `keron
+    /// import` never creates one).
+    pub repo: PathBuf,
+
+    /// Existing files or directories under `$HOME` to adopt, e.g.
+    /// `~/.zshrc ~/.gitconfig`.
+    #[arg(required = true)]
+    pub paths: Vec<String>,
+
+    /// Directory (relative to `repo`) to move adopted files into.
+    #[arg(long, default_value = "files")]
+    pub files_dir: PathBuf,
+
+    /// Manifest file (relative to `repo`) to append the generated `link()`
+    /// entries to, creating it if it doesn't exist yet.
+    #[arg(long, default_value = "imported.lua")]
+    pub manifest: PathBuf,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CheckDriftArgs {
+    #[command(flatten)]
+    pub source_args: SourceArgs,
+
+    /// Skip package provider queries and remote git lookups, so drift can
+    /// still be checked without a network connection.
+    #[arg(long)]
+    pub offline: bool,
+}
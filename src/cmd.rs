@@ -0,0 +1,238 @@
+//! Shared plumbing for the `cmd()` resource: running an arbitrary shell
+//! command, plus the `creates` / `creates_hash` idempotence shortcuts.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::secrets::{self, RedactionRules};
+use crate::subprocess::{self, Limits, DEFAULT_TIMEOUT};
+
+/// How many trailing lines of stdout/stderr to keep for a failure report.
+/// A stuck build spewing megabytes of output isn't made more diagnosable by
+/// keeping all of it; the tail is almost always what explains the failure.
+const OUTPUT_TAIL_LINES: usize = 20;
+
+/// Delay before the first retry; doubled after each further failed attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Options for [`run`], beyond the command string itself.
+pub struct RunOptions<'a> {
+    pub env: &'a BTreeMap<String, String>,
+    pub cwd: Option<&'a Path>,
+    pub redact: &'a [String],
+    /// Regex-based redaction on top of `redact`'s exact values, for
+    /// secret-shaped output `redact` doesn't already know about.
+    pub redact_patterns: &'a RedactionRules,
+    pub retries: u32,
+    pub timeout: Duration,
+    /// Forward captured stdout/stderr to our own, live. Turned off for
+    /// structured output formats (`--format json-lines`), where raw command
+    /// output interleaved on stdout would corrupt the event stream.
+    pub forward_output: bool,
+}
+
+/// Runs `command` through the shell, capturing its output, retrying up to
+/// `options.retries` times with exponential backoff on failure — useful for
+/// package installs on a flaky network.
+///
+/// If `options.forward_output` is set, captured output is also forwarded
+/// live to our own stdout/stderr; either way, a tail-limited, secret-redacted
+/// excerpt is folded into the returned error on failure so it shows up in
+/// reports even when the live output has scrolled out of view.
+pub fn run(command: &str, options: &RunOptions) -> Result<()> {
+    let mut delay = RETRY_BASE_DELAY;
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+        match run_once(command, options) {
+            Ok(()) => {
+                if attempt > 1 {
+                    eprintln!(
+                        "`{command}` succeeded on attempt {attempt}/{}",
+                        options.retries + 1
+                    );
+                }
+                return Ok(());
+            }
+            Err(err) if attempt <= options.retries => {
+                eprintln!(
+                    "`{command}` failed on attempt {attempt}/{}, retrying in {delay:?}: {err}",
+                    options.retries + 1
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(err) => return Err(err.context(format!("gave up after {attempt} attempt(s)"))),
+        }
+    }
+}
+
+fn run_once(command: &str, options: &RunOptions) -> Result<()> {
+    let mut process = Command::new("sh");
+    process.arg("-c").arg(command);
+    apply_env_and_cwd(&mut process, options.env, options.cwd);
+
+    let limits = Limits {
+        timeout: options.timeout,
+        ..Limits::default()
+    };
+    let output = subprocess::run_captured(&mut process, &limits)
+        .with_context(|| format!("failed to run `{command}`"))?;
+
+    if options.forward_output {
+        std::io::Write::write_all(&mut std::io::stdout(), &output.stdout).ok();
+        std::io::Write::write_all(&mut std::io::stderr(), &output.stderr).ok();
+    }
+
+    if !output.status.success() {
+        let stdout_tail = tail_lines(&String::from_utf8_lossy(&output.stdout), OUTPUT_TAIL_LINES);
+        let stderr_tail = tail_lines(&String::from_utf8_lossy(&output.stderr), OUTPUT_TAIL_LINES);
+        bail!(
+            "`{command}` failed with {}\n--- stdout (tail) ---\n{}\n--- stderr (tail) ---\n{}",
+            output.status,
+            secrets::redact_patterns(
+                &secrets::redact(&stdout_tail, options.redact),
+                options.redact_patterns
+            ),
+            secrets::redact_patterns(
+                &secrets::redact(&stderr_tail, options.redact),
+                options.redact_patterns
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// Keeps only the last `max_lines` lines of `text`, for embedding a bounded
+/// excerpt of command output in an error message.
+fn tail_lines(text: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.len().saturating_sub(max_lines);
+    lines[start..].join("\n")
+}
+
+/// Runs `command` as an `unless`/`only_if` guard, discarding its output and
+/// reporting only whether it exited successfully.
+pub fn guard_succeeds(
+    command: &str,
+    env: &BTreeMap<String, String>,
+    cwd: Option<&Path>,
+) -> Result<bool> {
+    let mut process = Command::new("sh");
+    process
+        .arg("-c")
+        .arg(command)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    apply_env_and_cwd(&mut process, env, cwd);
+
+    let status = subprocess::run_with_timeout(&mut process, DEFAULT_TIMEOUT)
+        .with_context(|| format!("failed to run guard `{command}`"))?;
+    Ok(status.success())
+}
+
+fn apply_env_and_cwd(process: &mut Command, env: &BTreeMap<String, String>, cwd: Option<&Path>) {
+    process.envs(env);
+    if let Some(cwd) = cwd {
+        process.current_dir(cwd);
+    }
+}
+
+/// Hex-encoded SHA-256 digest of `path`'s contents, for `creates_hash`.
+pub fn file_hash(path: &Path) -> Result<String> {
+    let contents =
+        std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    let digest = Sha256::digest(&contents);
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tail_lines_keeps_only_the_last_n_lines() {
+        let text = "one\ntwo\nthree\nfour";
+        assert_eq!(tail_lines(text, 2), "three\nfour");
+    }
+
+    #[test]
+    fn tail_lines_returns_everything_when_shorter_than_the_limit() {
+        assert_eq!(tail_lines("one\ntwo", 10), "one\ntwo");
+    }
+
+    #[test]
+    fn file_hash_is_stable_for_the_same_content() {
+        let dir = std::env::temp_dir().join(format!("keron-cmd-test-hash-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("input");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let first = file_hash(&path).unwrap();
+        let second = file_hash(&path).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 64);
+    }
+
+    #[test]
+    fn file_hash_errors_on_a_missing_file() {
+        let path = Path::new("/nonexistent/keron-cmd-test-file");
+        assert!(file_hash(path).is_err());
+    }
+
+    #[test]
+    fn guard_succeeds_reports_the_exit_code() {
+        assert!(guard_succeeds("true", &BTreeMap::new(), None).unwrap());
+        assert!(!guard_succeeds("false", &BTreeMap::new(), None).unwrap());
+    }
+
+    #[test]
+    fn run_retries_until_a_creates_style_guard_would_pass() {
+        let dir = std::env::temp_dir().join(format!("keron-cmd-test-retry-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("attempts");
+
+        let options = RunOptions {
+            env: &BTreeMap::new(),
+            cwd: None,
+            redact: &[],
+            redact_patterns: &RedactionRules::default(),
+            retries: 3,
+            timeout: Duration::from_secs(5),
+            forward_output: false,
+        };
+        let command = format!(
+            "echo x >> {marker} && test $(wc -l < {marker}) -ge 2",
+            marker = marker.display()
+        );
+
+        let result = run(&command, &options);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn run_gives_up_after_exhausting_retries() {
+        let options = RunOptions {
+            env: &BTreeMap::new(),
+            cwd: None,
+            redact: &[],
+            redact_patterns: &RedactionRules::default(),
+            retries: 1,
+            timeout: Duration::from_secs(5),
+            forward_output: false,
+        };
+        let error = run("exit 1", &options).unwrap_err();
+        assert!(error.to_string().contains("gave up after 2 attempt(s)"));
+    }
+}
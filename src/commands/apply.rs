@@ -0,0 +1,492 @@
+use std::io::IsTerminal;
+
+use anyhow::{bail, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::apply::{self, ApplyEvent, ApplyOptions, ApplyOutcome, Preserved};
+use crate::cli::{ApplyArgs, OutputFormat};
+use crate::lock::Lockfile;
+use crate::plan::{Action, BuildPlanOptions, Plan, PlannedOperation};
+use crate::{
+    history, journal, manifest, notify, plan, planfile, providers, report, secrets, source,
+};
+
+/// Apply always needs real secret values, both to write correct content and
+/// to compare against what's already on disk.
+fn apply_plan_options(offline: bool) -> BuildPlanOptions {
+    BuildPlanOptions {
+        resolve_secrets: true,
+        offline,
+    }
+}
+
+pub fn run(args: &ApplyArgs) -> Result<()> {
+    if args.execute && args.plan_file.is_none() && !args.interactive {
+        bail!("--execute only makes sense together with --plan-file or --interactive");
+    }
+
+    if args.use_trash && args.backup_dir.is_some() {
+        bail!("--use-trash and --backup-dir are mutually exclusive");
+    }
+
+    let source_name = args.source_args.resolve()?;
+    let source = source::Source::parse(&source_name);
+    let resolved = source::resolve_with(
+        &source,
+        &source::ResolveOptions {
+            refresh: args.source_args.refresh,
+            offline: args.offline,
+        },
+    )?;
+
+    let cwd = std::env::current_dir()?;
+
+    match &resolved.commit {
+        Some(commit) => {
+            let existing = Lockfile::load(&cwd)?;
+            if args.locked {
+                let Some(existing) = existing else {
+                    bail!("--locked was given but no `{}` was found; run `keron apply` once without --locked first", crate::lock::LOCK_FILE_NAME);
+                };
+                existing.verify(commit)?;
+            } else {
+                Lockfile {
+                    source: source_name.clone(),
+                    commit: commit.clone(),
+                }
+                .save(&cwd)?;
+            }
+        }
+        None => {
+            if args.locked {
+                bail!("--locked only applies to remote sources");
+            }
+        }
+    }
+
+    if args.refresh_packages {
+        refresh_apt_if_present(args)?;
+    }
+
+    let manifests = manifest::discover_filtered(resolved.root(), args.only_manifest.as_deref())?;
+    let plan = match &args.plan_file {
+        Some(path) => planfile::read_and_verify(path, &manifests)?,
+        None => plan::build_plan_with(&manifests, &apply_plan_options(args.offline))?,
+    };
+    let plan = match &args.only {
+        Some(selector) => plan::filter_only(plan, selector)?,
+        None => plan,
+    };
+
+    if args.strict && !plan.warnings.is_empty() {
+        bail!("--strict was given and the manifests produced {} warning(s); fix them or drop --strict", plan.warnings.len());
+    }
+
+    let mut deselected = Vec::new();
+    let plan = if args.interactive {
+        let (plan, skipped) = interactive_pick(plan, args.absolute_paths)?;
+        deselected = skipped;
+        plan
+    } else {
+        plan
+    };
+
+    if args.plan_file.is_some() && !args.execute {
+        for operation in &plan.operations {
+            println!(
+                "{}",
+                report::shorten_paths(&operation.description, args.absolute_paths)
+            );
+        }
+        println!("\nre-run with --execute to apply this plan");
+        return Ok(());
+    }
+
+    let mut sink: Box<dyn FnMut(ApplyEvent)> = match args.format {
+        OutputFormat::Text if std::io::stderr().is_terminal() => Box::new(progress_bar_sink(
+            args.absolute_paths,
+            plan.operations.len(),
+        )),
+        OutputFormat::Text => Box::new(plain_text_sink(args.absolute_paths)),
+        OutputFormat::JsonLines => Box::new(json_lines_sink),
+    };
+
+    let recorded = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut sink = history_recording_sink(std::rc::Rc::clone(&recorded), sink.as_mut());
+
+    let mut summary = apply::apply(
+        &plan,
+        &ApplyOptions {
+            allow_immutable_write: args.allow_immutable_write,
+            fail_fast: !args.keep_going,
+            forward_command_output: args.format == OutputFormat::Text,
+            backup_dir: args.backup_dir.clone(),
+            use_trash: args.use_trash,
+            redaction: secrets::RedactionRules::load()?,
+            elevation: args.elevation,
+            assume_no_elevation: args.assume_no_elevation,
+        },
+        &mut sink,
+    )?;
+    drop(sink);
+
+    if args.format == OutputFormat::Text {
+        println!("applied {} operation(s)", summary.applied);
+    }
+
+    journal::record(summary.applied_operations.clone())?;
+
+    let noop = plan
+        .operations
+        .len()
+        .saturating_sub(summary.applied + summary.failed + summary.skipped);
+    let mut operations = std::rc::Rc::try_unwrap(recorded)
+        .expect("sink dropped by now")
+        .into_inner();
+
+    if !deselected.is_empty() {
+        for operation in &deselected {
+            let description = report::shorten_paths(&operation.description, args.absolute_paths);
+            if args.format == OutputFormat::Text {
+                println!("skipped `{description}`: deselected in --interactive");
+            }
+            operations.push((description, ApplyOutcome::Skipped, Preserved::None));
+        }
+        summary.skipped += deselected.len();
+    }
+
+    if args.notify_desktop {
+        notify::desktop(&format!(
+            "{} applied, {} failed, {} skipped, {} unchanged",
+            summary.applied, summary.failed, summary.skipped, noop
+        ));
+    }
+    if let Some(url) = &args.notify_webhook {
+        let report = serde_json::json!({
+            "target": &source_name,
+            "applied": summary.applied,
+            "failed": summary.failed,
+            "skipped": summary.skipped,
+            "noop": noop,
+            "operations": operations.iter().map(|(description, outcome, _)| serde_json::json!({
+                "description": description,
+                "outcome": outcome_name(*outcome),
+            })).collect::<Vec<_>>(),
+        });
+        notify::webhook(url, &report)?;
+    }
+
+    history::record(
+        &source_name,
+        &cwd,
+        summary.applied,
+        summary.failed,
+        summary.skipped,
+        noop,
+        operations,
+    )?;
+
+    if args.verify_idempotent {
+        verify_idempotent(resolved.root(), args.absolute_paths, args.offline)?;
+    }
+
+    Ok(())
+}
+
+/// Refreshes the apt package index once, up front, if `apt` is detected on
+/// this host. On fresh machines apt's index starts empty, so an install run
+/// through a `cmd()` in a manifest fails with "unable to locate package"
+/// until something runs `apt-get update` first; this exists so a manifest
+/// author doesn't have to remember to shell that out themselves.
+fn refresh_apt_if_present(args: &ApplyArgs) -> Result<()> {
+    if !providers::detect_all()
+        .iter()
+        .any(|status| status.name == "apt" && status.detected)
+    {
+        return Ok(());
+    }
+
+    if args.assume_no_elevation {
+        if args.format == OutputFormat::Text {
+            println!(
+                "skipped refreshing apt package index: needs elevation (--assume-no-elevation)"
+            );
+        }
+        return Ok(());
+    }
+
+    providers::refresh_apt(args.elevation)
+        .with_context(|| "failed to refresh the apt package index (`apt-get update`)")?;
+
+    match args.format {
+        OutputFormat::Text => println!("refreshed apt package index (apt-get update)"),
+        OutputFormat::JsonLines => {
+            println!(
+                "{}",
+                serde_json::json!({"event": "refreshed_packages", "provider": "apt"})
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Presents a checkbox list of `plan`'s non-no-op operations (all checked by
+/// default) and splits it into what stays selected and what doesn't, so the
+/// caller can apply the former and report the latter as skipped. No-op
+/// operations aren't offered a checkbox since there's nothing to decide
+/// about them.
+fn interactive_pick(plan: Plan, absolute_paths: bool) -> Result<(Plan, Vec<PlannedOperation>)> {
+    let Plan {
+        operations,
+        package_snapshot,
+        secret_cache,
+        manifest_hooks,
+        warnings,
+    } = plan;
+    let (candidates, unchanged): (Vec<_>, Vec<_>) = operations
+        .into_iter()
+        .partition(|operation| !matches!(operation.action, Action::Noop));
+
+    if candidates.is_empty() {
+        return Ok((
+            Plan {
+                operations: unchanged,
+                package_snapshot,
+                secret_cache,
+                manifest_hooks,
+                warnings,
+            },
+            Vec::new(),
+        ));
+    }
+
+    let labels: Vec<String> = candidates
+        .iter()
+        .map(|operation| report::shorten_paths(&operation.description, absolute_paths))
+        .collect();
+    let selected = dialoguer::MultiSelect::new()
+        .with_prompt("select operations to apply (space to toggle, enter to confirm)")
+        .items(&labels)
+        .defaults(&vec![true; labels.len()])
+        .interact()?;
+    let selected: std::collections::HashSet<usize> = selected.into_iter().collect();
+
+    let mut kept = Vec::new();
+    let mut deselected = Vec::new();
+    for (index, operation) in candidates.into_iter().enumerate() {
+        if selected.contains(&index) {
+            kept.push(operation);
+        } else {
+            deselected.push(operation);
+        }
+    }
+    kept.extend(unchanged);
+
+    Ok((
+        Plan {
+            operations: kept,
+            package_snapshot,
+            secret_cache,
+            manifest_hooks,
+            warnings,
+        },
+        deselected,
+    ))
+}
+
+/// Wraps `inner`, additionally recording every finished operation's
+/// description, outcome, and preserved-contents fate into `recorded`, for
+/// [`history::record`] to persist once the whole apply is done.
+fn history_recording_sink<'a>(
+    recorded: std::rc::Rc<std::cell::RefCell<Vec<(String, ApplyOutcome, Preserved)>>>,
+    inner: &'a mut dyn FnMut(ApplyEvent),
+) -> impl FnMut(ApplyEvent) + 'a {
+    move |event| {
+        if let ApplyEvent::Finished {
+            description,
+            outcome,
+            preserved,
+            ..
+        } = &event
+        {
+            recorded
+                .borrow_mut()
+                .push((description.to_string(), *outcome, (**preserved).clone()));
+        }
+        inner(event);
+    }
+}
+
+/// Non-TTY text-mode event sink (piped to a file, CI logs, ...): matches the
+/// previous behavior of only narrating failures and skips, since a healthy
+/// apply speaks for itself and a redrawing progress bar makes no sense here.
+fn plain_text_sink(absolute_paths: bool) -> impl FnMut(ApplyEvent) {
+    move |event| {
+        let ApplyEvent::Finished {
+            description,
+            outcome,
+            error,
+            preserved,
+        } = event
+        else {
+            return;
+        };
+        let description = report::shorten_paths(description, absolute_paths);
+        match outcome {
+            ApplyOutcome::Failed => match error {
+                Some(err) => eprintln!("failed `{description}`: {err:#}"),
+                None => eprintln!("failed `{description}`"),
+            },
+            ApplyOutcome::Skipped => {
+                eprintln!("skipped `{description}`: an earlier operation failed")
+            }
+            ApplyOutcome::SkippedDependency => match error {
+                Some(err) => eprintln!("skipped `{description}`: {err:#}"),
+                None => eprintln!("skipped `{description}`: a dependency failed or was skipped"),
+            },
+            ApplyOutcome::SkippedElevation => {
+                eprintln!("skipped `{description}`: needs elevation (--assume-no-elevation)")
+            }
+            ApplyOutcome::Applied => match preserved {
+                Preserved::BackedUp(path) => {
+                    eprintln!("backed up previous `{description}` to `{}`", path.display())
+                }
+                Preserved::Trashed => eprintln!("sent previous `{description}` to trash"),
+                Preserved::None => {}
+            },
+            ApplyOutcome::Noop => {}
+        }
+    }
+}
+
+/// TTY text-mode event sink: a single progress bar showing the current
+/// operation, completed/total counts, and elapsed time. Failures and skips
+/// are printed above the bar (via `println`, which redraws it below) so
+/// they don't get lost once the bar clears at the end.
+fn progress_bar_sink(absolute_paths: bool, total: usize) -> impl FnMut(ApplyEvent) {
+    let bar = ProgressBar::new(total as u64);
+    bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] [{bar:40}] {pos}/{len} {msg}")
+            .expect("progress bar template is valid")
+            .progress_chars("=> "),
+    );
+
+    move |event| match event {
+        ApplyEvent::Started { description } => {
+            bar.set_message(report::shorten_paths(description, absolute_paths));
+        }
+        ApplyEvent::Finished {
+            description,
+            outcome,
+            error,
+            preserved,
+        } => {
+            let description = report::shorten_paths(description, absolute_paths);
+            match outcome {
+                ApplyOutcome::Failed => match error {
+                    Some(err) => bar.println(format!("failed `{description}`: {err:#}")),
+                    None => bar.println(format!("failed `{description}`")),
+                },
+                ApplyOutcome::Skipped => bar.println(format!(
+                    "skipped `{description}`: an earlier operation failed"
+                )),
+                ApplyOutcome::SkippedDependency => match error {
+                    Some(err) => bar.println(format!("skipped `{description}`: {err:#}")),
+                    None => bar.println(format!(
+                        "skipped `{description}`: a dependency failed or was skipped"
+                    )),
+                },
+                ApplyOutcome::SkippedElevation => {
+                    bar.println(format!(
+                        "skipped `{description}`: needs elevation (--assume-no-elevation)"
+                    ));
+                }
+                ApplyOutcome::Applied => match preserved {
+                    Preserved::BackedUp(path) => {
+                        bar.println(format!(
+                            "backed up previous `{description}` to `{}`",
+                            path.display()
+                        ));
+                    }
+                    Preserved::Trashed => {
+                        bar.println(format!("sent previous `{description}` to trash"))
+                    }
+                    Preserved::None => {}
+                },
+                ApplyOutcome::Noop => {}
+            }
+            bar.inc(1);
+            if bar.position() >= total as u64 {
+                bar.finish_and_clear();
+            }
+        }
+    }
+}
+
+/// `--format json-lines` event sink: one JSON object per operation
+/// start/finish, streamed as it happens so a wrapper can render live
+/// progress instead of waiting for the whole apply to finish.
+fn json_lines_sink(event: ApplyEvent) {
+    let line = match event {
+        ApplyEvent::Started { description } => serde_json::json!({
+            "event": "started",
+            "description": description,
+        }),
+        ApplyEvent::Finished {
+            description,
+            outcome,
+            error,
+            preserved,
+        } => serde_json::json!({
+            "event": "finished",
+            "description": description,
+            "outcome": outcome_name(outcome),
+            "error": error.map(|err| err.to_string()),
+            "preserved": match preserved {
+                Preserved::None => serde_json::Value::Null,
+                Preserved::BackedUp(path) => serde_json::json!({"kind": "backed_up", "path": path.display().to_string()}),
+                Preserved::Trashed => serde_json::json!({"kind": "trashed"}),
+            },
+        }),
+    };
+    println!("{line}");
+}
+
+fn outcome_name(outcome: ApplyOutcome) -> &'static str {
+    match outcome {
+        ApplyOutcome::Applied => "applied",
+        ApplyOutcome::Noop => "noop",
+        ApplyOutcome::Failed => "failed",
+        ApplyOutcome::Skipped => "skipped",
+        ApplyOutcome::SkippedDependency => "skipped_dependency",
+        ApplyOutcome::SkippedElevation => "skipped_elevation",
+    }
+}
+
+/// Re-plans against the already-applied source and fails loudly if any
+/// operation still reports a change, which would mean it isn't idempotent.
+fn verify_idempotent(root: &std::path::Path, absolute_paths: bool, offline: bool) -> Result<()> {
+    let manifests = manifest::discover(root)?;
+    let plan = plan::build_plan_with(&manifests, &apply_plan_options(offline))?;
+
+    let still_changing: Vec<_> = plan
+        .operations
+        .iter()
+        .filter(|op| !matches!(op.action, plan::Action::Noop))
+        .collect();
+
+    if !still_changing.is_empty() {
+        eprintln!(
+            "--verify-idempotent: the following operations still report a change after apply:"
+        );
+        for operation in &still_changing {
+            eprintln!(
+                "  {}",
+                report::shorten_paths(&operation.description, absolute_paths)
+            );
+        }
+        std::process::exit(crate::exitcode::NOT_IDEMPOTENT);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anyhow::Result;
+
+use crate::cli::CheckDriftArgs;
+use crate::plan::BuildPlanOptions;
+use crate::{manifest, plan, source};
+
+/// `keron check-drift`: prints nothing, exits `0` (no drift) or
+/// [`crate::exitcode::PLAN_HAS_CHANGES`] (drift found), for callers that
+/// only care about the exit code.
+pub fn run(args: &CheckDriftArgs) -> Result<()> {
+    let source = source::Source::parse(&args.source_args.resolve()?);
+    let resolved = source::resolve_with(
+        &source,
+        &source::ResolveOptions {
+            refresh: args.source_args.refresh,
+            offline: args.offline,
+        },
+    )?;
+
+    let manifests = manifest::discover(resolved.root())?;
+    let drifted = plan::has_any_change(
+        &manifests,
+        &BuildPlanOptions {
+            resolve_secrets: false,
+            offline: args.offline,
+        },
+    )?;
+
+    if drifted {
+        std::process::exit(crate::exitcode::PLAN_HAS_CHANGES);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use anyhow::Result;
+use clap::CommandFactory;
+
+use crate::cli::{Cli, CompletionsArgs};
+
+/// `keron completions <shell>`: prints a completion script to stdout, so it
+/// can be installed with `keron completions zsh > ~/.zfunc/_keron` (or
+/// wherever the target shell expects it) or piped straight into the running
+/// shell's completion loader.
+pub fn run(args: &CompletionsArgs) -> Result<()> {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(args.shell, &mut command, name, &mut std::io::stdout());
+    Ok(())
+}
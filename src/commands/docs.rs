@@ -0,0 +1,39 @@
+use anyhow::Result;
+
+use crate::cli::{DocsArgs, DocsTopic};
+use crate::manifest::docs;
+use crate::pager::{self, PagerOptions};
+
+/// `keron docs <topic>`: prints reference documentation generated from the
+/// same tables the rest of keron is built against, so it can't drift out of
+/// sync with what a manifest can actually call. Piped through a pager when
+/// the reference is longer than the terminal, since it's meant to be read
+/// as a whole rather than scrolled back through.
+pub fn run(args: &DocsArgs) -> Result<()> {
+    let text = match args.topic {
+        DocsTopic::Lua => lua_docs(),
+    };
+    pager::page(
+        &text,
+        &PagerOptions {
+            disabled: args.no_pager,
+            command: args.pager.clone(),
+        },
+    );
+    Ok(())
+}
+
+fn lua_docs() -> String {
+    let mut text = String::from("Lua manifest API\n\n");
+    for function in docs::LUA_FUNCTIONS {
+        text.push_str(function.signature);
+        text.push('\n');
+        text.push_str("    ");
+        text.push_str(function.description);
+        text.push_str("\n\n");
+    }
+    text.push_str("Every resource constructor above also accepts:\n");
+    text.push_str("    ");
+    text.push_str(docs::RESERVED_OPTS);
+    text
+}
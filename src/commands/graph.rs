@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::cli::{GraphArgs, GraphFormat};
+use crate::{manifest, source};
+
+/// `keron graph`: there's no cross-manifest dependency graph in this
+/// manifest model (only within-manifest `after`/`notify` edges between
+/// resources), so this renders that instead — one cluster per manifest,
+/// solid edges for `after`, dashed edges for `notify`.
+pub fn run(args: &GraphArgs) -> Result<()> {
+    let source = source::Source::parse(&args.source_args.resolve()?);
+    let resolved = source::resolve_with(
+        &source,
+        &source::ResolveOptions {
+            refresh: args.source_args.refresh,
+            offline: false,
+        },
+    )?;
+
+    let manifests = manifest::discover(resolved.root())?;
+
+    match args.format {
+        GraphFormat::Dot => print_dot(&manifests),
+    }
+
+    Ok(())
+}
+
+fn print_dot(manifests: &[manifest::Manifest]) {
+    println!("digraph keron {{");
+    println!("  rankdir=LR;");
+
+    for (cluster, manifest) in manifests.iter().enumerate() {
+        println!("  subgraph cluster_{cluster} {{");
+        println!(
+            "    label={};",
+            dot_string(&manifest.path.display().to_string())
+        );
+
+        // Keyed by position rather than `PlannedOperation::id`: this graph is
+        // redrawn fresh every run, so it doesn't need that id's cross-run
+        // stability, only uniqueness among this manifest's resources — which
+        // position guarantees and destination-based hashing doesn't quite,
+        // for two `cmd()` resources that don't set `creates`.
+        let mut name_to_node: HashMap<&str, String> = HashMap::new();
+        for (index, named) in manifest.resources.iter().enumerate() {
+            let node = format!("m{cluster}_r{index}");
+            println!(
+                "    {} [label={}];",
+                dot_id(&node),
+                dot_string(&named.resource.describe())
+            );
+            if let Some(name) = &named.name {
+                name_to_node.insert(name, node);
+            }
+        }
+
+        println!("  }}");
+
+        for (index, named) in manifest.resources.iter().enumerate() {
+            let node = format!("m{cluster}_r{index}");
+            for dependency in &named.after {
+                if let Some(dependency_node) = name_to_node.get(dependency.as_str()) {
+                    println!("  {} -> {};", dot_id(dependency_node), dot_id(&node));
+                }
+            }
+            for target in &named.notify {
+                if let Some(target_node) = name_to_node.get(target.as_str()) {
+                    println!(
+                        "  {} -> {} [style=dashed, label=notify];",
+                        dot_id(&node),
+                        dot_id(target_node)
+                    );
+                }
+            }
+        }
+    }
+
+    println!("}}");
+}
+
+fn dot_id(id: &str) -> String {
+    format!("\"{id}\"")
+}
+
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,71 @@
+use anyhow::{bail, Result};
+
+use crate::cli::{HistoryArgs, HistoryCommand};
+use crate::{history, report};
+
+pub fn run(args: &HistoryArgs) -> Result<()> {
+    match &args.command {
+        None => list(args.absolute_paths),
+        Some(HistoryCommand::Show { id }) => show(*id, args.absolute_paths),
+    }
+}
+
+fn list(absolute_paths: bool) -> Result<()> {
+    let entries = history::list()?;
+    if entries.is_empty() {
+        println!("no recorded runs yet");
+        return Ok(());
+    }
+
+    for entry in &entries {
+        let target = report::shorten_paths(&entry.target, absolute_paths);
+        println!(
+            "{:>4}  {}  {target:<40}  exit={}  applied={} failed={} skipped={} noop={}",
+            entry.id,
+            entry.timestamp,
+            entry.exit_code,
+            entry.applied,
+            entry.failed,
+            entry.skipped,
+            entry.noop,
+        );
+    }
+
+    Ok(())
+}
+
+fn show(id: u64, absolute_paths: bool) -> Result<()> {
+    let Some(entry) = history::show(id)? else {
+        bail!("no recorded run with id `{id}`");
+    };
+
+    println!(
+        "run {} at {} ({})",
+        entry.id,
+        entry.timestamp,
+        report::shorten_paths(&entry.target, absolute_paths)
+    );
+    println!(
+        "cwd: {}",
+        report::shorten_paths(&entry.cwd.display().to_string(), absolute_paths)
+    );
+    println!();
+
+    for operation in &entry.operations {
+        let description = report::shorten_paths(&operation.description, absolute_paths);
+        let marker = match operation.outcome.as_str() {
+            "applied" => "+",
+            "noop" => "=",
+            "failed" => "!",
+            _ => "-",
+        };
+        println!("{marker} {description} ({})", operation.outcome);
+    }
+
+    println!(
+        "\napplied={} failed={} skipped={} noop={}",
+        entry.applied, entry.failed, entry.skipped, entry.noop
+    );
+
+    Ok(())
+}
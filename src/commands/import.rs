@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::cli::ImportArgs;
+use crate::{apply, elevate, manifest, plan, secrets, xdg};
+
+/// `keron import`: the classic stow/chezmoi "adopt" workflow. Moves each
+/// given path out of `$HOME` and into the repo's `files_dir`, appends a
+/// `link()` entry pointing back at its original location, and immediately
+/// applies just that manifest so the symlink lands in place of the file
+/// that was just moved away.
+pub fn run(args: &ImportArgs) -> Result<()> {
+    if !args.repo.is_dir() {
+        bail!(
+            "`{}` is not an existing directory; `keron import` only adopts files into an existing manifest repo",
+            args.repo.display()
+        );
+    }
+
+    let home = xdg::home_dir();
+    let files_dir = args.repo.join(&args.files_dir);
+    let manifest_path = args.repo.join(&args.manifest);
+
+    let mut entries = Vec::new();
+    for raw in &args.paths {
+        let source_on_disk = xdg::expand_tilde(raw);
+        if !source_on_disk.exists() {
+            bail!("`{}` does not exist", source_on_disk.display());
+        }
+        let relative = source_on_disk.strip_prefix(&home).with_context(|| {
+            format!(
+                "`{}` is not under the home directory (`{}`); `keron import` only knows how to adopt dotfiles from there",
+                source_on_disk.display(),
+                home.display()
+            )
+        })?;
+
+        let repo_path = files_dir.join(relative);
+        if let Some(parent) = repo_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create `{}`", parent.display()))?;
+        }
+        move_path(&source_on_disk, &repo_path)?;
+
+        // An absolute path, not one relative to the repo: the symlink
+        // written at `destination` resolves a relative target against
+        // *its own* directory, not the repo, so a relative source would
+        // only work by coincidence of where `destination` happens to sit.
+        let repo_absolute = repo_path
+            .canonicalize()
+            .with_context(|| format!("failed to resolve `{}`", repo_path.display()))?;
+
+        entries.push(Entry {
+            repo_absolute,
+            home_relative: relative.to_path_buf(),
+        });
+        println!(
+            "moved `{}` -> `{}`",
+            source_on_disk.display(),
+            repo_path.display()
+        );
+    }
+
+    append_manifest_entries(&manifest_path, &entries)?;
+
+    let manifests = manifest::discover(&manifest_path)?;
+    let built = plan::build_plan_with(
+        &manifests,
+        &plan::BuildPlanOptions {
+            resolve_secrets: false,
+            offline: true,
+        },
+    )?;
+    let summary = apply::apply(
+        &built,
+        &apply::ApplyOptions {
+            allow_immutable_write: false,
+            fail_fast: false,
+            forward_command_output: false,
+            backup_dir: None,
+            use_trash: false,
+            redaction: secrets::RedactionRules::default(),
+            elevation: elevate::ElevationStrategy::default(),
+            assume_no_elevation: false,
+        },
+        &mut |_| {},
+    )?;
+
+    println!(
+        "imported {} file(s) into `{}`, applied {} operation(s)",
+        entries.len(),
+        manifest_path.display(),
+        summary.applied
+    );
+    Ok(())
+}
+
+struct Entry {
+    /// The moved file's new, absolute location (used as the `link()`
+    /// source).
+    repo_absolute: PathBuf,
+    /// Where the file used to live, relative to `$HOME` (used to rebuild
+    /// its `link()` destination via `expand("~/...")`).
+    home_relative: PathBuf,
+}
+
+/// Appends one `link()` call per entry to `manifest_path`, creating it with
+/// a short header comment if it doesn't exist yet.
+fn append_manifest_entries(manifest_path: &Path, entries: &[Entry]) -> Result<()> {
+    let mut contents = if manifest_path.exists() {
+        std::fs::read_to_string(manifest_path)
+            .with_context(|| format!("failed to read `{}`", manifest_path.display()))?
+    } else {
+        "-- entries adopted via `keron import`\n".to_string()
+    };
+
+    for entry in entries {
+        contents.push_str(&format!(
+            "link(\"{}\", expand(\"~/{}\"))\n",
+            entry.repo_absolute.display(),
+            entry.home_relative.display()
+        ));
+    }
+
+    std::fs::write(manifest_path, contents)
+        .with_context(|| format!("failed to write `{}`", manifest_path.display()))
+}
+
+/// Renames `from` to `to`, falling back to a recursive copy-then-remove when
+/// they're on different filesystems (`std::fs::rename` can't cross mount
+/// points, and a dotfile repo living on a different volume than `$HOME` is
+/// common enough, e.g. a network home directory).
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    copy_recursive(from, to)?;
+    if from.is_dir() {
+        std::fs::remove_dir_all(from)
+    } else {
+        std::fs::remove_file(from)
+    }
+    .with_context(|| {
+        format!(
+            "failed to remove `{}` after copying it to `{}`",
+            from.display(),
+            to.display()
+        )
+    })
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)
+            .with_context(|| format!("failed to create `{}`", to.display()))?;
+        for entry in std::fs::read_dir(from)
+            .with_context(|| format!("failed to read `{}`", from.display()))?
+        {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        std::fs::copy(from, to)
+            .with_context(|| format!("failed to copy `{}` -> `{}`", from.display(), to.display()))
+            .map(|_| ())
+    }
+}
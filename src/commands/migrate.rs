@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::cli::{MigrateArgs, MigrateFormat};
+
+/// `keron migrate hcl`: best-effort conversion of the old HCL-based recipe
+/// format (`link "name" { source = "..." destination = "..." }` and
+/// `template "name" { ... }` blocks) into an equivalent Lua manifest.
+///
+/// That format predates anything left in this tree — there's no `src/model`
+/// HCL parser to lean on anymore — so this understands only the shape those
+/// old recipes actually needed: a flat list of `link`/`template` blocks with
+/// plain string `source`/`destination` attributes, no interpolation and no
+/// nested blocks. A recipe using anything richer than that fails to parse
+/// with the offending line number instead of silently dropping it.
+pub fn run(args: &MigrateArgs) -> Result<()> {
+    match args.format {
+        MigrateFormat::Hcl => run_hcl(&args.file, args.output.as_deref()),
+    }
+}
+
+fn run_hcl(file: &Path, output: Option<&Path>) -> Result<()> {
+    let contents = std::fs::read_to_string(file)
+        .with_context(|| format!("failed to read `{}`", file.display()))?;
+    let blocks = parse_hcl_blocks(&contents)?;
+
+    let mut lua = String::from("-- migrated from a legacy HCL recipe by `keron migrate hcl`\n");
+    for block in &blocks {
+        let source = block.attr("source").with_context(|| {
+            format!(
+                "line {}: `{}` block `{}` has no `source` attribute",
+                block.line, block.kind, block.name
+            )
+        })?;
+        let destination = block.attr("destination").with_context(|| {
+            format!(
+                "line {}: `{}` block `{}` has no `destination` attribute",
+                block.line, block.kind, block.name
+            )
+        })?;
+        lua.push_str(&format!(
+            "{}(\"{source}\", \"{destination}\", {{ name = \"{}\" }})\n",
+            block.kind, block.name
+        ));
+    }
+
+    match output {
+        Some(path) => {
+            let mut existing = if path.exists() {
+                std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read `{}`", path.display()))?
+            } else {
+                String::new()
+            };
+            existing.push_str(&lua);
+            std::fs::write(path, existing)
+                .with_context(|| format!("failed to write `{}`", path.display()))?;
+            println!(
+                "converted {} resource(s) into `{}`",
+                blocks.len(),
+                path.display()
+            );
+        }
+        None => print!("{lua}"),
+    }
+    Ok(())
+}
+
+struct HclBlock {
+    kind: String,
+    name: String,
+    line: usize,
+    attrs: Vec<(String, String)>,
+}
+
+impl HclBlock {
+    fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Parses the minimal `kind "name" { key = "value" ... }` grammar the old
+/// recipes used. `#` and `//` start a line comment; blank lines are skipped.
+fn parse_hcl_blocks(contents: &str) -> Result<Vec<HclBlock>> {
+    let mut blocks = Vec::new();
+    let mut lines = contents.lines().enumerate().peekable();
+
+    while let Some((line_no, raw_line)) = lines.next() {
+        let line = strip_comment(raw_line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (header, rest) = line.split_once('{').with_context(|| {
+            format!(
+                "line {}: expected a block header ending in `{{`, found `{line}`",
+                line_no + 1
+            )
+        })?;
+        if !rest.trim().is_empty() {
+            bail!(
+                "line {}: expected nothing after `{{` on a block header, found `{rest}`",
+                line_no + 1
+            );
+        }
+
+        let mut header_parts = header.trim().splitn(2, char::is_whitespace);
+        let kind = header_parts.next().unwrap_or_default().to_string();
+        if kind != "link" && kind != "template" {
+            bail!("line {}: unsupported block type `{kind}` (only `link` and `template` are understood)", line_no + 1);
+        }
+        let name = header_parts
+            .next()
+            .map(str::trim)
+            .and_then(|value| value.strip_prefix('"'))
+            .and_then(|value| value.strip_suffix('"'))
+            .with_context(|| {
+                format!(
+                    "line {}: expected a quoted block name after `{kind}`",
+                    line_no + 1
+                )
+            })?
+            .to_string();
+
+        let mut attrs = Vec::new();
+        loop {
+            let (attr_line_no, raw) = lines.next().with_context(|| {
+                format!(
+                    "line {}: unterminated `{kind}` block (missing closing `}}`)",
+                    line_no + 1
+                )
+            })?;
+            let attr_line = strip_comment(raw).trim();
+            if attr_line.is_empty() {
+                continue;
+            }
+            if attr_line == "}" {
+                break;
+            }
+            let (key, value) = attr_line.split_once('=').with_context(|| {
+                format!(
+                    "line {}: expected `key = \"value\"`, found `{attr_line}`",
+                    attr_line_no + 1
+                )
+            })?;
+            let value = value
+                .trim()
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .with_context(|| {
+                    format!(
+                        "line {}: expected a quoted string value, found `{}`",
+                        attr_line_no + 1,
+                        value.trim()
+                    )
+                })?;
+            attrs.push((key.trim().to_string(), value.to_string()));
+        }
+
+        blocks.push(HclBlock {
+            kind,
+            name,
+            line: line_no + 1,
+            attrs,
+        });
+    }
+
+    Ok(blocks)
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find('#').or_else(|| line.find("//")) {
+        Some(idx) => &line[..idx],
+        None => line,
+    }
+}
@@ -0,0 +1,13 @@
+pub mod apply;
+pub mod check_drift;
+pub mod completions;
+pub mod docs;
+pub mod graph;
+pub mod history;
+pub mod import;
+pub mod migrate;
+pub mod plan;
+pub mod plan_diff;
+pub mod providers;
+pub mod tui;
+pub mod undo;
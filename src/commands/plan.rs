@@ -0,0 +1,354 @@
+use anyhow::Result;
+
+use crate::cli::{GroupBy, PlanArgs, PlanFormat};
+use crate::plan::{Action, BuildPlanOptions, PlannedOperation, ResolvedInvocation};
+use crate::{manifest, plan, planfile, report, source};
+
+pub fn run(args: &PlanArgs) -> Result<()> {
+    let source = source::Source::parse(&args.source_args.resolve()?);
+    let resolved = source::resolve_with(
+        &source,
+        &source::ResolveOptions {
+            refresh: args.source_args.refresh,
+            offline: args.offline,
+        },
+    )?;
+
+    let manifests = manifest::discover_filtered(resolved.root(), args.only_manifest.as_deref())?;
+    let plan = plan::build_plan_with(
+        &manifests,
+        &BuildPlanOptions {
+            resolve_secrets: args.resolve_secrets,
+            offline: args.offline,
+        },
+    )?;
+    let plan = match &args.only {
+        Some(selector) => plan::filter_only(plan, selector)?,
+        None => plan,
+    };
+
+    let options = RenderOptions {
+        absolute_paths: args.absolute_paths,
+        show_manifest: args.show_manifest,
+        verbose: args.verbose,
+    };
+    match args.format {
+        PlanFormat::Text => {
+            match args.group_by {
+                GroupBy::Flat => {
+                    for operation in &plan.operations {
+                        print_operation(operation, &options, "");
+                    }
+                }
+                GroupBy::Manifest => print_grouped_by_manifest(&plan.operations, &options),
+            }
+
+            if plan.has_conflicts() {
+                print_conflicts(&plan.operations, &options);
+            }
+
+            if !plan.warnings.is_empty() {
+                print_warnings(&plan.warnings);
+            }
+        }
+        PlanFormat::Markdown => print_markdown(&plan.operations, args.absolute_paths),
+        PlanFormat::Junit => print_junit(&plan.operations, args.absolute_paths),
+    }
+
+    if let Some(output) = &args.output {
+        planfile::write(output, &manifests, &plan)?;
+        println!("\nplan written to {}", output.display());
+    }
+
+    if args.detailed_exitcode && plan.has_changes() {
+        std::process::exit(crate::exitcode::PLAN_HAS_CHANGES);
+    }
+
+    Ok(())
+}
+
+/// Formatting toggles for plan output, bundled together so `print_operation`
+/// doesn't grow a new boolean parameter every time a display option like
+/// `--show-manifest` is added.
+struct RenderOptions {
+    absolute_paths: bool,
+    show_manifest: bool,
+    verbose: bool,
+}
+
+fn print_operation(operation: &PlannedOperation, options: &RenderOptions, indent: &str) {
+    let marker = match &operation.action {
+        Action::Noop => "=",
+        Action::Create => "+",
+        Action::Update => "~",
+        Action::Adopt => "^",
+        Action::Remove => "-",
+        Action::Conflict(_) => "!",
+        Action::Unknown(_) => "?",
+    };
+    let description = report::shorten_paths(&operation.description, options.absolute_paths);
+    let manifest_suffix = if options.show_manifest {
+        format!(
+            " {}",
+            report::dim(&format!("({})", manifest_location(operation)))
+        )
+    } else {
+        String::new()
+    };
+    match &operation.name {
+        Some(name) => println!("{indent}{marker} {description} [{name}]{manifest_suffix}"),
+        None => println!("{indent}{marker} {description}{manifest_suffix}"),
+    }
+    if let Action::Conflict(conflict) = &operation.action {
+        println!("{indent}    conflict: {}", conflict.describe());
+    }
+    if let Action::Unknown(reason) = &operation.action {
+        println!("{indent}    unknown: {reason}");
+    }
+    if options.verbose {
+        if let Some(invocation) = &operation.resolved_invocation {
+            print_resolved_invocation(invocation, indent);
+        }
+    }
+}
+
+/// The `--verbose` detail beneath a `cmd()` operation: its resolved `cwd`
+/// and `env`, so a reviewer can see what would actually run rather than just
+/// the bare command string in `description`. `cmd()` has no `elevate` opt in
+/// this tree (unlike `link()`/`template()`/`dir()`), so there's no
+/// elevation status to show alongside these.
+fn print_resolved_invocation(invocation: &ResolvedInvocation, indent: &str) {
+    match &invocation.cwd {
+        Some(cwd) => println!("{indent}    cwd: {}", cwd.display()),
+        None => println!("{indent}    cwd: (inherited)"),
+    }
+    if invocation.env.is_empty() {
+        println!("{indent}    env: (none)");
+    } else {
+        for (name, value) in &invocation.env {
+            println!("{indent}    env: {name}={value}");
+        }
+    }
+}
+
+/// The manifest's file name alone (e.g. `workstation.lua`), for the
+/// `--show-manifest` suffix — short enough to read inline without repeating
+/// the full path already visible in `keron plan --group-by manifest`.
+fn manifest_label(path: &std::path::Path) -> std::borrow::Cow<'_, str> {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => path.to_string_lossy(),
+    }
+}
+
+/// `manifest_label`, with a `:<line>` suffix when the resource's source line
+/// could be resolved (see `manifest::lua::caller_line`).
+fn manifest_location(operation: &PlannedOperation) -> String {
+    let label = manifest_label(&operation.manifest_path);
+    match operation.line {
+        Some(line) => format!("{label}:{line}"),
+        None => label.into_owned(),
+    }
+}
+
+/// Prints operations sectioned by the manifest that declared them, in
+/// discovery order, with a per-manifest change subtotal. Operations are
+/// already grouped by manifest in `plan.operations` (`build_plan_with`
+/// appends each manifest's resources consecutively), so this only needs to
+/// notice when `manifest_path` changes rather than sort or hash anything.
+fn print_grouped_by_manifest(operations: &[PlannedOperation], options: &RenderOptions) {
+    // The manifest heading below already names the manifest, so
+    // `--show-manifest`'s per-line suffix would just repeat it.
+    let per_line_options = RenderOptions {
+        absolute_paths: options.absolute_paths,
+        show_manifest: false,
+        verbose: options.verbose,
+    };
+
+    let mut current_manifest = None;
+    let mut changes_in_manifest = 0;
+
+    for operation in operations {
+        if current_manifest != Some(&operation.manifest_path) {
+            if let Some(path) = current_manifest {
+                print_subtotal(path, changes_in_manifest, options.absolute_paths);
+            }
+            let heading = report::shorten_paths(
+                &operation.manifest_path.to_string_lossy(),
+                options.absolute_paths,
+            );
+            println!("\n{heading}");
+            current_manifest = Some(&operation.manifest_path);
+            changes_in_manifest = 0;
+        }
+
+        if !matches!(operation.action, Action::Noop) {
+            changes_in_manifest += 1;
+        }
+        print_operation(operation, &per_line_options, "  ");
+    }
+
+    if let Some(path) = current_manifest {
+        print_subtotal(path, changes_in_manifest, options.absolute_paths);
+    }
+}
+
+fn print_subtotal(path: &std::path::Path, changes: usize, absolute_paths: bool) {
+    let heading = report::shorten_paths(&path.to_string_lossy(), absolute_paths);
+    println!("  {changes} change(s) in {heading}");
+}
+
+/// Appends a "Conflicts" section listing every conflicting operation with
+/// the manifest that declared it and a concrete next step, instead of
+/// leaving the reader to scroll back up and match `!` markers to causes by
+/// hand.
+fn print_conflicts(operations: &[PlannedOperation], options: &RenderOptions) {
+    println!("\nConflicts:");
+    for operation in operations {
+        let Action::Conflict(conflict) = &operation.action else {
+            continue;
+        };
+        let description = report::shorten_paths(&operation.description, options.absolute_paths);
+        println!("  {description} ({})", manifest_location(operation));
+        println!("    problem: {}", conflict.describe());
+        println!("    next step: {}", conflict.remediation());
+    }
+    println!("\n`keron apply` will fail until these are resolved.");
+}
+
+/// Appends a "Warnings" section for unknown-option warnings collected while
+/// evaluating the manifests (see `manifest::lua::extract_meta`), so a typo'd
+/// option turns up in `keron plan` output too, not just as a stderr line a
+/// reviewer scrolled past.
+fn print_warnings(warnings: &[String]) {
+    println!("\nWarnings:");
+    for warning in warnings {
+        println!("  {warning}");
+    }
+}
+
+/// A compact GitHub-flavored markdown rendering: a tally table, then the
+/// non-no-op operations, meant to be posted as a CI PR comment rather than
+/// read in a terminal — so unlike the text renderer this never groups by
+/// manifest or pages, it just names the manifest inline on each line.
+fn print_markdown(operations: &[PlannedOperation], absolute_paths: bool) {
+    let mut create = 0;
+    let mut update = 0;
+    let mut adopt = 0;
+    let mut remove = 0;
+    let mut conflict = 0;
+    let mut unknown = 0;
+    let mut noop = 0;
+    for operation in operations {
+        match &operation.action {
+            Action::Create => create += 1,
+            Action::Update => update += 1,
+            Action::Adopt => adopt += 1,
+            Action::Remove => remove += 1,
+            Action::Conflict(_) => conflict += 1,
+            Action::Unknown(_) => unknown += 1,
+            Action::Noop => noop += 1,
+        }
+    }
+
+    println!("## keron plan\n");
+    println!("| Action | Count |");
+    println!("| --- | --- |");
+    for (label, count) in [
+        ("Create", create),
+        ("Update", update),
+        ("Adopt", adopt),
+        ("Remove", remove),
+        ("Conflict", conflict),
+        ("Unknown", unknown),
+        ("No change", noop),
+    ] {
+        if count > 0 {
+            println!("| {label} | {count} |");
+        }
+    }
+
+    let changed: Vec<_> = operations
+        .iter()
+        .filter(|op| !matches!(op.action, Action::Noop))
+        .collect();
+    if changed.is_empty() {
+        println!("\nNo changes.");
+        return;
+    }
+
+    println!("\n### Changed resources\n");
+    for operation in changed {
+        let marker = match &operation.action {
+            Action::Create => "+",
+            Action::Update => "~",
+            Action::Adopt => "^",
+            Action::Remove => "-",
+            Action::Conflict(_) => "!",
+            Action::Unknown(_) => "?",
+            Action::Noop => unreachable!("filtered out above"),
+        };
+        let description = report::shorten_paths(&operation.description, absolute_paths);
+        println!(
+            "- `{marker}` `{description}` ({})",
+            manifest_location(operation)
+        );
+        if let Action::Conflict(conflict) = &operation.action {
+            println!("  - {}: {}", conflict.describe(), conflict.remediation());
+        }
+        if let Action::Unknown(reason) = &operation.action {
+            println!("  - {reason}");
+        }
+    }
+}
+
+/// JUnit XML, one `<testcase>` per operation, for CI systems that already
+/// have a JUnit reporter (test-result trends, PR annotations, ...) and would
+/// otherwise need a bespoke keron integration to surface plan problems.
+fn print_junit(operations: &[PlannedOperation], absolute_paths: bool) {
+    let failures = operations
+        .iter()
+        .filter(|op| matches!(op.action, Action::Conflict(_)))
+        .count();
+    let skipped = operations
+        .iter()
+        .filter(|op| matches!(op.action, Action::Unknown(_)))
+        .count();
+
+    println!(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    println!(
+        r#"<testsuite name="keron plan" tests="{}" failures="{failures}" skipped="{skipped}">"#,
+        operations.len()
+    );
+    for operation in operations {
+        let description = report::shorten_paths(&operation.description, absolute_paths);
+        print!(
+            r#"  <testcase classname="{}" name="{}">"#,
+            xml_escape(&manifest_location(operation)),
+            xml_escape(&description)
+        );
+        match &operation.action {
+            Action::Conflict(conflict) => {
+                print!(
+                    r#"<failure message="{}">{}</failure>"#,
+                    xml_escape(conflict.describe()),
+                    xml_escape(conflict.remediation())
+                );
+            }
+            Action::Unknown(reason) => print!(r#"<skipped message="{}"/>"#, xml_escape(reason)),
+            Action::Noop | Action::Create | Action::Update | Action::Adopt | Action::Remove => {}
+        }
+        println!("</testcase>");
+    }
+    println!("</testsuite>");
+}
+
+/// Escapes the handful of characters that would otherwise break well-formed
+/// XML if a manifest's path or a resource's description happened to contain
+/// them.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use crate::cli::PlanDiffArgs;
+use crate::plan::{Action, PlannedOperation};
+use crate::{planfile, report};
+
+pub fn run(args: &PlanDiffArgs) -> Result<()> {
+    let old = planfile::read_operations(&args.old)?;
+    let new = planfile::read_operations(&args.new)?;
+
+    let old_by_id: BTreeMap<&str, &PlannedOperation> =
+        old.iter().map(|op| (op.id.as_str(), op)).collect();
+    let new_by_id: BTreeMap<&str, &PlannedOperation> =
+        new.iter().map(|op| (op.id.as_str(), op)).collect();
+
+    let mut changes = 0;
+
+    for operation in &old {
+        if !new_by_id.contains_key(operation.id.as_str()) {
+            changes += 1;
+            println!("- {}", describe(operation, args.absolute_paths));
+        }
+    }
+
+    for operation in &new {
+        match old_by_id.get(operation.id.as_str()) {
+            None => {
+                changes += 1;
+                println!("+ {}", describe(operation, args.absolute_paths));
+            }
+            Some(previous) if previous.action != operation.action => {
+                changes += 1;
+                println!(
+                    "~ {} ({} -> {})",
+                    describe(operation, args.absolute_paths),
+                    action_label(&previous.action),
+                    action_label(&operation.action)
+                );
+            }
+            Some(_) => {}
+        }
+    }
+
+    if changes == 0 {
+        println!("no differences");
+    }
+
+    Ok(())
+}
+
+fn describe(operation: &PlannedOperation, absolute_paths: bool) -> String {
+    report::shorten_paths(&operation.description, absolute_paths)
+}
+
+fn action_label(action: &Action) -> &'static str {
+    match action {
+        Action::Noop => "noop",
+        Action::Create => "create",
+        Action::Update => "update",
+        Action::Adopt => "adopt",
+        Action::Remove => "remove",
+        Action::Conflict(_) => "conflict",
+        Action::Unknown(_) => "unknown",
+    }
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+
+use crate::cli::ProvidersArgs;
+use crate::{manifest, providers, source};
+
+pub fn run(args: &ProvidersArgs) -> Result<()> {
+    let source = source::Source::parse(&args.source_args.resolve()?);
+    let resolved = source::resolve_with(
+        &source,
+        &source::ResolveOptions {
+            refresh: args.source_args.refresh,
+            offline: false,
+        },
+    )?;
+
+    let manifests = manifest::discover(resolved.root())?;
+
+    let mut statuses = providers::detect_all();
+    for manifest in &manifests {
+        for provider in &manifest.providers {
+            statuses.push(providers::detect_custom(provider));
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&statuses)?);
+        return Ok(());
+    }
+
+    for status in &statuses {
+        let marker = if status.detected { "+" } else { "-" };
+        match &status.binary {
+            Some(_) => {
+                let version = status.version.as_deref().unwrap_or("-");
+                let install_dir = status
+                    .default_install_dir
+                    .as_ref()
+                    .map(|dir| dir.display().to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                println!(
+                    "{marker} {:<8} version={version:<20} default_install_dir={install_dir}",
+                    status.name
+                );
+            }
+            None => {
+                let detect = status.detect.as_deref().unwrap_or("-");
+                println!("{marker} {:<8} (custom, detect=`{detect}`)", status.name);
+                if let Some(install) = &status.install {
+                    println!("    install={install}");
+                }
+                if let Some(list) = &status.list {
+                    println!("    list={list}");
+                }
+                if let Some(remove) = &status.remove {
+                    println!("    remove={remove}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
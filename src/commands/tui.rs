@@ -0,0 +1,223 @@
+use anyhow::Result;
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::apply::{self, ApplyEvent, ApplyOptions, ApplyOutcome};
+use crate::cli::TuiArgs;
+use crate::plan::{Action, BuildPlanOptions};
+use crate::{elevate, history, journal, manifest, plan, report, secrets, source};
+
+/// A menu-driven dashboard over `keron plan`'s tree of manifests and
+/// resources: pick a manifest to see its drift status, then optionally
+/// apply just that manifest, instead of scrolling a single flat `keron
+/// plan`/`keron apply` run. Re-plans every time the manifest menu is shown,
+/// so applying one manifest is reflected before picking the next.
+pub fn run(args: &TuiArgs) -> Result<()> {
+    loop {
+        let source_name = args.source_args.resolve()?;
+        let source = source::Source::parse(&source_name);
+        let resolved = source::resolve_with(
+            &source,
+            &source::ResolveOptions {
+                refresh: args.source_args.refresh,
+                offline: args.offline,
+            },
+        )?;
+        let manifests = manifest::discover(resolved.root())?;
+        if manifests.is_empty() {
+            println!("no manifests found");
+            return Ok(());
+        }
+
+        let built = plan::build_plan_with(
+            &manifests,
+            &BuildPlanOptions {
+                resolve_secrets: false,
+                offline: args.offline,
+            },
+        )?;
+
+        let mut items: Vec<String> = manifests
+            .iter()
+            .map(|manifest| {
+                let changes = built
+                    .operations
+                    .iter()
+                    .filter(|op| {
+                        op.manifest_path == manifest.path && !matches!(op.action, Action::Noop)
+                    })
+                    .count();
+                let label = report::shorten_paths(&manifest.path.to_string_lossy(), false);
+                if changes > 0 {
+                    format!("{label} ({changes} change(s))")
+                } else {
+                    format!("{label} (up to date)")
+                }
+            })
+            .collect();
+        items.push("quit".to_string());
+
+        let selection = dialoguer::Select::new()
+            .with_prompt("manifests")
+            .items(&items)
+            .default(0)
+            .interact()?;
+
+        if selection == manifests.len() {
+            return Ok(());
+        }
+
+        let manifest = &manifests[selection];
+        let operations: Vec<_> = built
+            .operations
+            .iter()
+            .filter(|op| op.manifest_path == manifest.path)
+            .collect();
+
+        println!(
+            "\n{}",
+            report::shorten_paths(&manifest.path.to_string_lossy(), false)
+        );
+        for operation in &operations {
+            print_operation_line(operation);
+        }
+
+        if !operations
+            .iter()
+            .any(|op| !matches!(op.action, Action::Noop))
+        {
+            println!();
+            continue;
+        }
+
+        let apply_now = dialoguer::Confirm::new()
+            .with_prompt("apply this manifest's changes?")
+            .default(false)
+            .interact()?;
+        if apply_now {
+            apply_manifest(resolved.root(), &manifest.path, args.offline)?;
+        }
+        println!();
+    }
+}
+
+fn print_operation_line(operation: &plan::PlannedOperation) {
+    let marker = match &operation.action {
+        Action::Noop => "=",
+        Action::Create => "+",
+        Action::Update => "~",
+        Action::Adopt => "^",
+        Action::Remove => "-",
+        Action::Conflict(_) => "!",
+        Action::Unknown(_) => "?",
+    };
+    println!(
+        "  {marker} {}",
+        report::shorten_paths(&operation.description, false)
+    );
+}
+
+/// Re-plans just `manifest_path` with real secret values, applies it with a
+/// live progress bar, and journals/records history the same as `keron
+/// apply` would, so `keron undo` and `keron history` see it too.
+fn apply_manifest(
+    root: &std::path::Path,
+    manifest_path: &std::path::Path,
+    offline: bool,
+) -> Result<()> {
+    let manifests: Vec<_> = manifest::discover(root)?
+        .into_iter()
+        .filter(|manifest| manifest.path == manifest_path)
+        .collect();
+    let Some(manifest) = manifests.into_iter().next() else {
+        println!("manifest no longer found; re-plan and try again");
+        return Ok(());
+    };
+
+    let plan = plan::build_plan_with(
+        std::slice::from_ref(&manifest),
+        &BuildPlanOptions {
+            resolve_secrets: true,
+            offline,
+        },
+    )?;
+
+    let bar = ProgressBar::new(plan.operations.len() as u64);
+    bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] [{bar:40}] {pos}/{len} {msg}")
+            .expect("progress bar template is valid")
+            .progress_chars("=> "),
+    );
+
+    let mut recorded = Vec::new();
+    let mut sink = |event: ApplyEvent| match event {
+        ApplyEvent::Started { description } => {
+            bar.set_message(report::shorten_paths(description, false))
+        }
+        ApplyEvent::Finished {
+            description,
+            outcome,
+            error,
+            preserved,
+        } => {
+            let description = report::shorten_paths(description, false);
+            match outcome {
+                ApplyOutcome::Failed => match error {
+                    Some(err) => bar.println(format!("failed `{description}`: {err:#}")),
+                    None => bar.println(format!("failed `{description}`")),
+                },
+                ApplyOutcome::Skipped => bar.println(format!(
+                    "skipped `{description}`: an earlier operation failed"
+                )),
+                ApplyOutcome::SkippedDependency => {
+                    bar.println(format!(
+                        "skipped `{description}`: a dependency failed or was skipped"
+                    ));
+                }
+                ApplyOutcome::SkippedElevation => {
+                    bar.println(format!(
+                        "skipped `{description}`: needs elevation (--assume-no-elevation)"
+                    ));
+                }
+                ApplyOutcome::Applied | ApplyOutcome::Noop => {}
+            }
+            recorded.push((description, outcome, preserved.clone()));
+            bar.inc(1);
+        }
+    };
+
+    let summary = apply::apply(
+        &plan,
+        &ApplyOptions {
+            allow_immutable_write: false,
+            fail_fast: true,
+            forward_command_output: true,
+            backup_dir: None,
+            use_trash: false,
+            redaction: secrets::RedactionRules::load()?,
+            elevation: elevate::ElevationStrategy::default(),
+            assume_no_elevation: false,
+        },
+        &mut sink,
+    )?;
+    bar.finish_and_clear();
+
+    println!("applied {} operation(s)", summary.applied);
+
+    let cwd = std::env::current_dir()?;
+    journal::record(summary.applied_operations.clone())?;
+    let noop = plan
+        .operations
+        .len()
+        .saturating_sub(summary.applied + summary.failed + summary.skipped);
+    history::record(
+        &manifest_path.display().to_string(),
+        &cwd,
+        summary.applied,
+        summary.failed,
+        summary.skipped,
+        noop,
+        recorded,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::apply::{AppliedOperation, Preserved};
+use crate::cli::UndoArgs;
+use crate::resource::Resource;
+use crate::{journal, report};
+
+/// `keron undo`: reverses every mutation recorded in the most recent
+/// `keron apply`'s journal, most recent first, reporting what it can't
+/// reverse instead of silently leaving it alone.
+pub fn run(args: &UndoArgs) -> Result<()> {
+    let Some(operations) = journal::load()? else {
+        bail!("no `{}` found; nothing to undo", journal::JOURNAL_FILE_NAME);
+    };
+
+    for operation in operations.iter().rev() {
+        let description = report::shorten_paths(&operation.description, args.absolute_paths);
+        match undo_operation(operation) {
+            Ok(Undone::Reversed) => println!("undone: {description}"),
+            Ok(Undone::Unsupported(reason)) => println!("cannot undo `{description}`: {reason}"),
+            Err(err) => eprintln!("failed to undo `{description}`: {err:#}"),
+        }
+    }
+
+    journal::clear()
+}
+
+enum Undone {
+    Reversed,
+    Unsupported(&'static str),
+}
+
+fn undo_operation(operation: &AppliedOperation) -> Result<Undone> {
+    match &operation.resource {
+        Resource::Link(link) => undo_replaced(&link.destination, &operation.preserved),
+        Resource::Template(template) => undo_replaced(&template.destination, &operation.preserved),
+        Resource::AgeFile(age_file) => undo_replaced(&age_file.destination, &operation.preserved),
+        Resource::GitRepo(_) => Ok(Undone::Unsupported("git_repo checkouts aren't reversible")),
+        Resource::FileBlock(_) => Ok(Undone::Unsupported(
+            "file_block merges its content into an existing file; the marked block would need to be removed by hand",
+        )),
+        Resource::Cmd(_) => Ok(Undone::Unsupported("cmd() has no recorded inverse")),
+        Resource::Dir(_) => Ok(Undone::Unsupported(
+            "dir() is left in place in case other resources depend on it",
+        )),
+        Resource::PipxPackage(_) => Ok(Undone::Unsupported(
+            "pipx_package installs are left in place, since uninstalling might discard config changes made since",
+        )),
+        Resource::CargoPackage(_) => Ok(Undone::Unsupported(
+            "cargo_package installs are left in place, since uninstalling might discard config changes made since",
+        )),
+    }
+}
+
+/// Removes what apply wrote to `destination`, then restores whatever was
+/// preserved from before, if anything.
+fn undo_replaced(destination: &Path, preserved: &Preserved) -> Result<Undone> {
+    if destination.is_symlink() || destination.exists() {
+        std::fs::remove_file(destination)
+            .or_else(|_| std::fs::remove_dir_all(destination))
+            .with_context(|| format!("failed to remove `{}`", destination.display()))?;
+    }
+
+    match preserved {
+        Preserved::None => Ok(Undone::Reversed),
+        Preserved::BackedUp(backup_path) => {
+            std::fs::rename(backup_path, destination).with_context(|| {
+                format!(
+                    "failed to restore `{}` from `{}`",
+                    destination.display(),
+                    backup_path.display()
+                )
+            })?;
+            Ok(Undone::Reversed)
+        }
+        Preserved::Trashed => Ok(Undone::Unsupported(
+            "its previous contents were sent to the OS trash; restore them from there manually if needed",
+        )),
+    }
+}
@@ -0,0 +1,504 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::process::{Command, ExitStatus};
+
+/// Why a candidate elevation launcher wasn't used.
+#[derive(Debug, Clone)]
+pub enum LauncherOutcome {
+    /// Not found in `$PATH`.
+    NotFound,
+    /// This is the launcher that was actually invoked.
+    Selected,
+}
+
+#[derive(Debug, Clone)]
+pub struct LauncherAttempt {
+    pub name: &'static str,
+    pub outcome: LauncherOutcome,
+}
+
+/// Structured failure report for a privileged command, so callers (and
+/// eventually machine-readable output) can tell *which* launcher ran,
+/// which ones were skipped and why, and separate the exit status from
+/// whatever the child printed to stderr.
+#[derive(Debug)]
+pub struct ElevationError {
+    pub attempts: Vec<LauncherAttempt>,
+    pub selected: &'static str,
+    /// `None` when the launcher itself couldn't be found or spawned.
+    pub exit_status: Option<ExitStatus>,
+    pub stderr: String,
+}
+
+impl fmt::Display for ElevationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.exit_status {
+            Some(status) => writeln!(f, "elevation via `{}` failed with {status}", self.selected)?,
+            None => writeln!(f, "elevation via `{}` failed to start", self.selected)?,
+        }
+        for attempt in &self.attempts {
+            match attempt.outcome {
+                LauncherOutcome::NotFound => writeln!(f, "  {}: not found in PATH", attempt.name)?,
+                LauncherOutcome::Selected => writeln!(f, "  {}: selected", attempt.name)?,
+            }
+        }
+        if !self.stderr.trim().is_empty() {
+            write!(f, "stderr:\n{}", self.stderr)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ElevationError {}
+
+const CANDIDATE_LAUNCHERS: &[&str] = &["sudo", "doas"];
+
+/// Which elevation launcher(s) `run_privileged` is allowed to try, set via
+/// `--elevation` and threaded down through [`crate::apply::ApplyOptions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ElevationStrategy {
+    /// Try `sudo`, then `doas` — whichever is found first in `$PATH`.
+    #[default]
+    Auto,
+    Sudo,
+    Doas,
+    /// Never elevate; every privileged operation fails immediately.
+    None,
+}
+
+impl ElevationStrategy {
+    fn candidates(self) -> &'static [&'static str] {
+        match self {
+            ElevationStrategy::Auto => CANDIDATE_LAUNCHERS,
+            ElevationStrategy::Sudo => &["sudo"],
+            ElevationStrategy::Doas => &["doas"],
+            ElevationStrategy::None => &[],
+        }
+    }
+}
+
+/// Runs `program` with `args` under the first available elevation launcher
+/// allowed by `strategy` (by default `sudo`, then `doas`), returning a
+/// structured [`ElevationError`] if none are available or if the elevated
+/// command fails.
+pub fn run_privileged(
+    program: &str,
+    args: &[&str],
+    strategy: ElevationStrategy,
+) -> Result<(), ElevationError> {
+    let (attempts, launcher, output) = spawn_privileged(program, args, strategy)?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    Err(ElevationError {
+        attempts,
+        selected: launcher,
+        exit_status: Some(output.status),
+        stderr: elevation_failure_stderr(String::from_utf8_lossy(&output.stderr).into_owned()),
+    })
+}
+
+/// One command to run as part of a [`run_privileged_batch`] call.
+#[derive(Clone)]
+pub struct BatchedCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+/// Runs several commands under a single elevation launcher invocation, in
+/// order, so a run with more than one `elevate = true` resource only
+/// prompts once instead of once per resource. Commands run even after an
+/// earlier one fails (there's no rollback), and each gets its own
+/// `Ok`/`Err` in the returned `Vec`, in the same order as `commands`.
+///
+/// There's no payload-file protocol to extend here (`run_privileged` has
+/// never had one) — this shells the whole batch out as one script instead,
+/// marking each command's exit status with an unguessable-enough delimiter
+/// so per-command results can be pulled back out of the combined child's
+/// stdout. The script is piped to the elevated `sh`'s stdin rather than
+/// passed as a `-c` argument or dropped in a temp file: an argument would
+/// sit in `ps`/`/proc/<pid>/cmdline` for any local user to read for as long
+/// as the elevated child runs, and a temp file would need its own
+/// world-readability and cleanup story to avoid the same exposure.
+pub fn run_privileged_batch(
+    commands: &[BatchedCommand],
+    strategy: ElevationStrategy,
+) -> Result<Vec<std::result::Result<(), String>>, ElevationError> {
+    let script = build_batch_script(commands);
+    let (attempts, launcher, output) = spawn_privileged_with_stdin("sh", &[], &script, strategy)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    let (results, reported) = parse_batch_stdout(&stdout, commands.len());
+    if reported != commands.len() {
+        return Err(ElevationError {
+            attempts,
+            selected: launcher,
+            exit_status: Some(output.status),
+            stderr: elevation_failure_stderr(format!(
+                "only {reported}/{} batched commands reported a result:\n{stderr}",
+                commands.len()
+            )),
+        });
+    }
+
+    Ok(results)
+}
+
+const BATCH_MARKER: &str = "__keron_batch_result__";
+
+/// Builds the `sh` script `run_privileged_batch` feeds to the elevated
+/// child's stdin: each command runs in order, followed by an `echo` of its
+/// exit status tagged with [`BATCH_MARKER`] and its index so the results can
+/// be pulled back out of the combined child's stdout by [`parse_batch_stdout`].
+fn build_batch_script(commands: &[BatchedCommand]) -> String {
+    let mut script = String::from("set +e\n");
+    for (index, command) in commands.iter().enumerate() {
+        script.push_str(&shell_quote(&command.program));
+        for arg in &command.args {
+            script.push(' ');
+            script.push_str(&shell_quote(arg));
+        }
+        script.push_str(&format!(" >&2\necho \"{BATCH_MARKER} {index} $?\"\n"));
+    }
+    script
+}
+
+/// Pulls each command's `Ok`/`Err` back out of `stdout`'s [`BATCH_MARKER`]
+/// lines, defaulting unreported indices to `Ok` (they either haven't run yet
+/// or the child died before printing their marker). Returns the results
+/// alongside how many markers were actually seen, so the caller can tell a
+/// clean batch from one that was cut short.
+fn parse_batch_stdout(
+    stdout: &str,
+    command_count: usize,
+) -> (Vec<std::result::Result<(), String>>, usize) {
+    let mut results: Vec<std::result::Result<(), String>> =
+        (0..command_count).map(|_| Ok(())).collect();
+    let mut reported = 0;
+    for line in stdout.lines() {
+        let Some(rest) = line.strip_prefix(&format!("{BATCH_MARKER} ")) else {
+            continue;
+        };
+        let Some((index, code)) = rest.split_once(' ') else {
+            continue;
+        };
+        let Ok(index) = index.parse::<usize>() else {
+            continue;
+        };
+        let Some(slot) = results.get_mut(index) else {
+            continue;
+        };
+        *slot = if code == "0" {
+            Ok(())
+        } else {
+            Err(format!("exited with status {code}"))
+        };
+        reported += 1;
+    }
+    (results, reported)
+}
+
+/// Single-quotes `value` for embedding in the batch script, escaping any
+/// single quotes it contains the usual POSIX-shell way.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+type PrivilegedSpawn = (Vec<LauncherAttempt>, &'static str, std::process::Output);
+
+fn select_launcher(
+    strategy: ElevationStrategy,
+) -> Result<(Vec<LauncherAttempt>, &'static str), ElevationError> {
+    let mut attempts = Vec::new();
+    let mut selected = None;
+
+    if strategy.candidates().is_empty() {
+        return Err(ElevationError {
+            attempts,
+            selected: "none",
+            exit_status: None,
+            stderr: "elevation disabled by `--elevation none`".to_string(),
+        });
+    }
+
+    for launcher in strategy.candidates() {
+        if which(launcher) {
+            attempts.push(LauncherAttempt {
+                name: launcher,
+                outcome: LauncherOutcome::Selected,
+            });
+            selected = Some(*launcher);
+            break;
+        }
+        attempts.push(LauncherAttempt {
+            name: launcher,
+            outcome: LauncherOutcome::NotFound,
+        });
+    }
+
+    match selected {
+        Some(launcher) => Ok((attempts, launcher)),
+        None => Err(ElevationError {
+            attempts,
+            selected: "none",
+            exit_status: None,
+            stderr: "no elevation launcher (sudo, doas) found in PATH".to_string(),
+        }),
+    }
+}
+
+fn spawn_privileged(
+    program: &str,
+    args: &[&str],
+    strategy: ElevationStrategy,
+) -> Result<PrivilegedSpawn, ElevationError> {
+    let (attempts, launcher) = select_launcher(strategy)?;
+
+    let mut command = Command::new(launcher);
+    if !stdin_is_interactive() {
+        command.arg("-n");
+    }
+
+    match command.arg(program).args(args).output() {
+        Ok(output) => Ok((attempts, launcher, output)),
+        Err(err) => Err(ElevationError {
+            attempts,
+            selected: launcher,
+            exit_status: None,
+            stderr: format!("failed to spawn `{launcher}`: {err}"),
+        }),
+    }
+}
+
+/// Like [`spawn_privileged`], but feeds `stdin_data` to the elevated
+/// child's stdin instead of passing it as a command-line argument, so it
+/// never lands somewhere another local user could read it (`ps`,
+/// `/proc/<pid>/cmdline`, a temp file) for the run's duration.
+fn spawn_privileged_with_stdin(
+    program: &str,
+    args: &[&str],
+    stdin_data: &str,
+    strategy: ElevationStrategy,
+) -> Result<PrivilegedSpawn, ElevationError> {
+    let (attempts, launcher) = select_launcher(strategy)?;
+
+    let mut command = Command::new(launcher);
+    if !stdin_is_interactive() {
+        command.arg("-n");
+    }
+
+    let mut child = match command
+        .arg(program)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            return Err(ElevationError {
+                attempts,
+                selected: launcher,
+                exit_status: None,
+                stderr: format!("failed to spawn `{launcher}`: {err}"),
+            })
+        }
+    };
+
+    {
+        use std::io::Write;
+        let mut stdin = child.stdin.take().expect("stdin was piped");
+        if let Err(err) = stdin.write_all(stdin_data.as_bytes()) {
+            return Err(ElevationError {
+                attempts,
+                selected: launcher,
+                exit_status: None,
+                stderr: format!("failed to write to `{launcher}`'s stdin: {err}"),
+            });
+        }
+    }
+
+    match child.wait_with_output() {
+        Ok(output) => Ok((attempts, launcher, output)),
+        Err(err) => Err(ElevationError {
+            attempts,
+            selected: launcher,
+            exit_status: None,
+            stderr: format!("failed to wait for `{launcher}`: {err}"),
+        }),
+    }
+}
+
+/// True when stdin is a TTY a human could actually see and answer an
+/// elevation prompt through. When it isn't (CI, a cron job, piped input),
+/// every launcher invocation gets `-n` so a missing password fails
+/// immediately instead of hanging forever on a prompt nobody can answer.
+fn stdin_is_interactive() -> bool {
+    std::io::stdin().is_terminal()
+}
+
+/// Prefixes a non-interactive elevation failure with a hint pointing at
+/// `--assume-no-elevation`, since the launcher's own `-n` error (e.g.
+/// sudo's "a password is required") doesn't know keron has an escape hatch.
+fn elevation_failure_stderr(raw: String) -> String {
+    if stdin_is_interactive() {
+        return raw;
+    }
+    format!(
+        "needs elevation but no TTY is attached to answer a prompt (running in CI, a hook, or with stdin \
+         redirected?); pass `--assume-no-elevation` to skip such operations instead of failing here\n{raw}"
+    )
+}
+
+fn which(program: &str) -> bool {
+    let Ok(path) = std::env::var("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| dir.join(program).is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_quote_escapes_embedded_single_quotes() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn build_batch_script_tags_each_command_with_its_index() {
+        let commands = vec![
+            BatchedCommand {
+                program: "chown".to_string(),
+                args: vec!["root:root".to_string(), "/etc/keron".to_string()],
+            },
+            BatchedCommand {
+                program: "chmod".to_string(),
+                args: vec!["0644".to_string()],
+            },
+        ];
+        let script = build_batch_script(&commands);
+        assert!(script.contains("'chown' 'root:root' '/etc/keron' >&2"));
+        assert!(script.contains(&format!("echo \"{BATCH_MARKER} 0 $?\"")));
+        assert!(script.contains(&format!("echo \"{BATCH_MARKER} 1 $?\"")));
+    }
+
+    #[test]
+    fn parse_batch_stdout_reads_results_in_any_order() {
+        let stdout = format!("noise\n{BATCH_MARKER} 1 1\n{BATCH_MARKER} 0 0\n");
+        let (results, reported) = parse_batch_stdout(&stdout, 2);
+        assert_eq!(reported, 2);
+        assert!(results[0].is_ok());
+        assert_eq!(results[1], Err("exited with status 1".to_string()));
+    }
+
+    #[test]
+    fn parse_batch_stdout_ignores_malformed_or_unknown_markers() {
+        let stdout = format!("{BATCH_MARKER} not-a-number 0\n{BATCH_MARKER} 5 0\nunrelated line\n");
+        let (results, reported) = parse_batch_stdout(&stdout, 1);
+        assert_eq!(reported, 0);
+        assert!(results[0].is_ok());
+    }
+
+    #[test]
+    fn strategy_candidates_match_the_chosen_launcher() {
+        assert_eq!(ElevationStrategy::Auto.candidates(), CANDIDATE_LAUNCHERS);
+        assert_eq!(ElevationStrategy::Sudo.candidates(), &["sudo"]);
+        assert_eq!(ElevationStrategy::Doas.candidates(), &["doas"]);
+        assert!(ElevationStrategy::None.candidates().is_empty());
+    }
+
+    #[test]
+    fn none_strategy_fails_without_trying_any_launcher() {
+        let error = select_launcher(ElevationStrategy::None).unwrap_err();
+        assert!(error.attempts.is_empty());
+        assert_eq!(error.selected, "none");
+        assert!(error.stderr.contains("--elevation none"));
+    }
+
+    #[test]
+    fn select_launcher_falls_through_to_the_first_candidate_found_on_path() {
+        // Both PATH mutation and the launcher lookup it drives have to happen
+        // in one test: std::env::set_var isn't safe to race against another
+        // test doing the same, and select_launcher always reads the process's
+        // real PATH rather than taking one as an argument.
+        let dir =
+            std::env::temp_dir().join(format!("keron-elevate-test-path-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("doas"), "").unwrap();
+        let original_path = std::env::var("PATH").unwrap_or_default();
+        std::env::set_var("PATH", &dir);
+
+        let result = select_launcher(ElevationStrategy::Auto);
+
+        std::env::set_var("PATH", original_path);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (attempts, launcher) = result.unwrap();
+        assert_eq!(launcher, "doas");
+        assert!(matches!(
+            attempts.first().unwrap().outcome,
+            LauncherOutcome::NotFound
+        ));
+        assert!(matches!(
+            attempts.last().unwrap().outcome,
+            LauncherOutcome::Selected
+        ));
+    }
+
+    #[test]
+    fn display_reports_selected_launcher_and_skipped_ones() {
+        let error = ElevationError {
+            attempts: vec![
+                LauncherAttempt {
+                    name: "sudo",
+                    outcome: LauncherOutcome::NotFound,
+                },
+                LauncherAttempt {
+                    name: "doas",
+                    outcome: LauncherOutcome::Selected,
+                },
+            ],
+            selected: "doas",
+            exit_status: None,
+            stderr: String::new(),
+        };
+        let rendered = error.to_string();
+        assert!(rendered.contains("elevation via `doas` failed to start"));
+        assert!(rendered.contains("sudo: not found in PATH"));
+        assert!(rendered.contains("doas: selected"));
+    }
+
+    #[test]
+    fn display_reports_the_child_exit_status_when_it_ran() {
+        let status = Command::new("/bin/false")
+            .status()
+            .expect("`/bin/false` exists");
+        let error = ElevationError {
+            attempts: Vec::new(),
+            selected: "sudo",
+            exit_status: Some(status),
+            stderr: String::new(),
+        };
+        assert!(error
+            .to_string()
+            .starts_with("elevation via `sudo` failed with"));
+    }
+
+    #[test]
+    fn display_omits_stderr_section_when_blank() {
+        let error = ElevationError {
+            attempts: Vec::new(),
+            selected: "sudo",
+            exit_status: None,
+            stderr: "  \n".to_string(),
+        };
+        assert!(!error.to_string().contains("stderr:"));
+    }
+}
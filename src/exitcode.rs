@@ -0,0 +1,12 @@
+//! Exit codes used for conditions more specific than a generic failure
+//! (which comes from returning `Err` from `main` and always exits `1`).
+
+/// `keron apply --verify-idempotent` found an operation that still reports
+/// a change after a successful apply.
+pub const NOT_IDEMPOTENT: i32 = 3;
+
+/// `keron plan --detailed-exitcode` found at least one operation that isn't
+/// a no-op. Off by default: a plain `keron plan` always exits `0` on
+/// success (even with pending changes) so it doesn't surprise a cron job
+/// that only checks for a nonzero exit on error.
+pub const PLAN_HAS_CHANGES: i32 = 2;
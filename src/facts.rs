@@ -0,0 +1,78 @@
+//! Host facts gathered fresh at plan/apply time: cheap, read-only details
+//! about the machine keron is running on. Exposed to a manifest both as the
+//! `facts()` Lua table and, merged into every `template()`'s variables, as
+//! `{{ facts.* }}` placeholders (see [`crate::render::with_facts`]), so a
+//! template can vary by host without a manifest re-deriving the same values
+//! itself via `env()`/`file_exists()`.
+
+use std::collections::BTreeMap;
+
+use crate::resource::TemplateValue;
+
+/// Snapshot of host details, gathered once per plan/apply run.
+pub struct Facts {
+    pub os: String,
+    pub arch: String,
+    pub hostname: String,
+    pub username: String,
+    pub home: String,
+    pub cpu_count: usize,
+    pub is_wsl: bool,
+}
+
+impl Facts {
+    /// Gathers facts from the current process's environment. `os`/`arch`
+    /// come from the compile-time target (`std::env::consts`), everything
+    /// else is read at runtime, so cross-compiling doesn't change what a
+    /// manifest sees for `os`/`arch` but a moved binary still reports the
+    /// host it's actually running on for the rest.
+    pub fn gather() -> Facts {
+        Facts {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            hostname: whoami::hostname().unwrap_or_else(|_| "unknown".to_string()),
+            username: whoami::username().unwrap_or_else(|_| "unknown".to_string()),
+            home: crate::xdg::home_dir().to_string_lossy().into_owned(),
+            cpu_count: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            is_wsl: is_wsl(),
+        }
+    }
+
+    /// Converts facts into a [`TemplateValue::Table`] for
+    /// [`crate::render::with_facts`].
+    pub fn as_template_value(&self) -> TemplateValue {
+        let mut fields = BTreeMap::new();
+        fields.insert("os".to_string(), TemplateValue::Str(self.os.clone()));
+        fields.insert("arch".to_string(), TemplateValue::Str(self.arch.clone()));
+        fields.insert(
+            "hostname".to_string(),
+            TemplateValue::Str(self.hostname.clone()),
+        );
+        fields.insert(
+            "username".to_string(),
+            TemplateValue::Str(self.username.clone()),
+        );
+        fields.insert("home".to_string(), TemplateValue::Str(self.home.clone()));
+        fields.insert(
+            "cpu_count".to_string(),
+            TemplateValue::Str(self.cpu_count.to_string()),
+        );
+        fields.insert(
+            "is_wsl".to_string(),
+            TemplateValue::Str(self.is_wsl.to_string()),
+        );
+        TemplateValue::Table(fields)
+    }
+}
+
+/// Detects the Windows Subsystem for Linux by looking for its telltale
+/// marker in the kernel release string, the same signal `/proc/version`
+/// itself carries (`Microsoft` or `microsoft-standard`), rather than relying
+/// on an environment variable a shell profile could unset.
+fn is_wsl() -> bool {
+    std::fs::read_to_string("/proc/version")
+        .map(|version| version.to_lowercase().contains("microsoft"))
+        .unwrap_or(false)
+}
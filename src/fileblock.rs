@@ -0,0 +1,44 @@
+//! Managed block support for the `file_block()` resource: inserts or
+//! replaces a marked block inside a file the manifest doesn't otherwise own
+//! (e.g. a `source ~/.keron/aliases.sh` line appended to `~/.zshrc`).
+
+fn begin_marker(marker: &str) -> String {
+    format!("# BEGIN {marker}")
+}
+
+fn end_marker(marker: &str) -> String {
+    format!("# END {marker}")
+}
+
+/// Renders the managed block, including its begin/end comment markers.
+pub fn render_block(content: &str, marker: &str) -> String {
+    format!(
+        "{}\n{}\n{}",
+        begin_marker(marker),
+        content.trim_end_matches('\n'),
+        end_marker(marker)
+    )
+}
+
+/// Returns `existing` with `block` inserted or replaced. If the markers
+/// aren't present yet, the block is appended, preceded by a newline if
+/// `existing` is non-empty and doesn't already end in one.
+pub fn merge_block(existing: &str, block: &str, marker: &str) -> String {
+    let begin = begin_marker(marker);
+    let end = end_marker(marker);
+
+    if let Some(start) = existing.find(&begin) {
+        if let Some(end_offset) = existing[start..].find(&end) {
+            let end_pos = start + end_offset + end.len();
+            return format!("{}{}{}", &existing[..start], block, &existing[end_pos..]);
+        }
+    }
+
+    if existing.is_empty() {
+        format!("{block}\n")
+    } else if existing.ends_with('\n') {
+        format!("{existing}{block}\n")
+    } else {
+        format!("{existing}\n{block}\n")
+    }
+}
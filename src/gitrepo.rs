@@ -0,0 +1,134 @@
+//! Shared git plumbing for the `git_repo()` resource. `keron-source` (see
+//! [`crate::source`]) has its own clone/fetch flow for evaluating manifests
+//! from a git URL; this module is the equivalent for repositories that a
+//! manifest wants checked out onto disk as a managed resource (plugin
+//! managers, vim/tmux plugins, ...).
+
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::subprocess::{self, Limits};
+
+/// Clones and fetches get more slack than a quick metadata query, since
+/// they're bounded by network/repo size rather than a stuck process.
+const CLONE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The commit `destination` is currently checked out at, or `None` if it
+/// doesn't exist yet or isn't a git repository.
+pub fn current_commit(destination: &Path) -> Option<String> {
+    if !destination.join(".git").exists() {
+        return None;
+    }
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(destination)
+        .arg("rev-parse")
+        .arg("HEAD");
+    let output = subprocess::run_captured(&mut command, &Limits::default()).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+/// The commit `reference` (or the default branch, if `None`) currently
+/// points to on the remote, without cloning anything.
+pub fn remote_commit(url: &str, reference: Option<&str>) -> Result<String> {
+    let mut command = Command::new("git");
+    command.arg("ls-remote").arg(url);
+    if let Some(reference) = reference {
+        command.arg(reference);
+    } else {
+        command.arg("HEAD");
+    }
+
+    let output = subprocess::run_captured(&mut command, &Limits::default())
+        .context("failed to run `git ls-remote`")?;
+    if !output.status.success() {
+        bail!(
+            "`git ls-remote {url}` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let stdout = String::from_utf8(output.stdout)?;
+    stdout
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("`git ls-remote {url}` returned no refs"))
+}
+
+/// Clones `url` into `destination`, which must not already exist.
+pub fn clone(
+    url: &str,
+    destination: &Path,
+    reference: Option<&str>,
+    depth: Option<u32>,
+) -> Result<()> {
+    tracing::debug!(url, destination = %destination.display(), reference, depth, "cloning git_repo()");
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+
+    let mut command = Command::new("git");
+    command.arg("clone").arg("--quiet");
+    if let Some(depth) = depth {
+        command.arg("--depth").arg(depth.to_string());
+    }
+    if let Some(reference) = reference {
+        command.arg("--branch").arg(reference);
+    }
+    command.arg(url).arg(destination);
+
+    let status = subprocess::run_with_timeout(&mut command, CLONE_TIMEOUT)
+        .with_context(|| format!("failed to run `git clone {url}`"))?;
+    if !status.success() {
+        bail!("`git clone {url}` failed with {status}");
+    }
+    Ok(())
+}
+
+/// Fetches and checks out `reference` (or `HEAD`) in an existing clone.
+pub fn fetch_and_checkout(
+    destination: &Path,
+    reference: Option<&str>,
+    depth: Option<u32>,
+) -> Result<()> {
+    let fetch_ref = reference.unwrap_or("HEAD");
+    tracing::debug!(destination = %destination.display(), fetch_ref, depth, "fetching and checking out git_repo()");
+
+    let mut fetch = Command::new("git");
+    fetch.arg("-C").arg(destination).arg("fetch").arg("--quiet");
+    if let Some(depth) = depth {
+        fetch.arg("--depth").arg(depth.to_string());
+    }
+    fetch.arg("origin").arg(fetch_ref);
+
+    let status = subprocess::run_with_timeout(&mut fetch, CLONE_TIMEOUT)
+        .with_context(|| format!("failed to run `git fetch origin {fetch_ref}`"))?;
+    if !status.success() {
+        bail!("`git fetch origin {fetch_ref}` failed with {status}");
+    }
+
+    let mut checkout = Command::new("git");
+    checkout
+        .arg("-C")
+        .arg(destination)
+        .arg("checkout")
+        .arg("--quiet")
+        .arg("--detach")
+        .arg("FETCH_HEAD");
+    let status = subprocess::run_with_timeout(&mut checkout, CLONE_TIMEOUT)
+        .context("failed to run `git checkout FETCH_HEAD`")?;
+    if !status.success() {
+        bail!("`git checkout FETCH_HEAD` failed with {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! A local, append-only record of `keron apply` runs across this machine
+//! (unlike [`crate::journal`], which only remembers the most recent run in
+//! a single directory), so "what changed here last Tuesday" has an answer
+//! without reaching for shell history or a package manager's own log.
+//! Nothing here leaves the machine.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::apply::{ApplyOutcome, Preserved};
+
+/// Bumped whenever [`HistoryEntry`]'s shape changes incompatibly. Old
+/// entries in the file aren't rewritten, so `list()`/`show()` skip (rather
+/// than fail on) a line written by an incompatible version.
+const HISTORY_VERSION: u32 = 1;
+
+/// One operation's outcome, as recorded for `keron history show`. A
+/// stripped-down [`crate::apply::AppliedOperation`]: history only needs
+/// enough to re-render a report, not enough to undo it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryOperation {
+    pub description: String,
+    pub outcome: String,
+    pub preserved: Preserved,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    version: u32,
+    pub id: u64,
+    /// RFC 3339, local time, e.g. `2026-08-09T14:03:21+02:00`.
+    pub timestamp: String,
+    /// The `keron apply <target>` source argument, as given on the command
+    /// line.
+    pub target: String,
+    pub cwd: PathBuf,
+    /// `0` if nothing failed, `1` otherwise — the same convention `main`
+    /// uses for a generic failure, since a `--keep-going` apply can finish
+    /// with some operations failed but still return `Ok` from `run()`.
+    pub exit_code: i32,
+    pub applied: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub noop: usize,
+    pub operations: Vec<HistoryOperation>,
+}
+
+fn history_path() -> PathBuf {
+    crate::xdg::data_dir().join("keron").join("history.jsonl")
+}
+
+fn outcome_name(outcome: ApplyOutcome) -> &'static str {
+    match outcome {
+        ApplyOutcome::Applied => "applied",
+        ApplyOutcome::Noop => "noop",
+        ApplyOutcome::Failed => "failed",
+        ApplyOutcome::Skipped => "skipped",
+        ApplyOutcome::SkippedDependency => "skipped_dependency",
+        ApplyOutcome::SkippedElevation => "skipped_elevation",
+    }
+}
+
+/// Appends a new entry for a completed `keron apply` run. `next_id` is
+/// simply the highest id seen in the file so far, plus one — good enough
+/// for a single-machine, mostly-sequential log; not meant to survive
+/// concurrent applies racing each other.
+pub fn record(
+    target: &str,
+    cwd: &std::path::Path,
+    applied: usize,
+    failed: usize,
+    skipped: usize,
+    noop: usize,
+    operations: Vec<(String, ApplyOutcome, Preserved)>,
+) -> Result<()> {
+    let path = history_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+
+    let next_id = list()?
+        .iter()
+        .map(|entry| entry.id)
+        .max()
+        .map_or(0, |max| max + 1);
+
+    let entry = HistoryEntry {
+        version: HISTORY_VERSION,
+        id: next_id,
+        timestamp: chrono::Local::now().to_rfc3339(),
+        target: target.to_string(),
+        cwd: cwd.to_path_buf(),
+        exit_code: if failed > 0 { 1 } else { 0 },
+        applied,
+        failed,
+        skipped,
+        noop,
+        operations: operations
+            .into_iter()
+            .map(|(description, outcome, preserved)| HistoryOperation {
+                description,
+                outcome: outcome_name(outcome).to_string(),
+                preserved,
+            })
+            .collect(),
+    };
+
+    let line = serde_json::to_string(&entry).context("failed to serialize history entry")?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open `{}`", path.display()))?;
+    use std::io::Write;
+    writeln!(file, "{line}").with_context(|| format!("failed to write `{}`", path.display()))
+}
+
+/// Every recorded entry, oldest first. A line that fails to parse (a
+/// version bump, a truncated write) is skipped rather than failing the
+/// whole read.
+pub fn list() -> Result<Vec<HistoryEntry>> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    Ok(contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str::<HistoryEntry>(line).ok())
+        .filter(|entry| entry.version == HISTORY_VERSION)
+        .collect())
+}
+
+/// The entry with the given id, if one was recorded.
+pub fn show(id: u64) -> Result<Option<HistoryEntry>> {
+    Ok(list()?.into_iter().find(|entry| entry.id == id))
+}
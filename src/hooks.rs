@@ -0,0 +1,43 @@
+//! Manifest-level `pre_cmd()`/`post_cmd()` hooks: a shell command run once
+//! per manifest file, before or after its resources are applied, rather
+//! than once per resource like `cmd()`. Useful for reloading a service
+//! (`systemctl --user restart sway`) or sourcing a file only when something
+//! in that manifest actually changed.
+
+use anyhow::Result;
+
+use crate::cmd::{self, RunOptions};
+use crate::secrets::RedactionRules;
+use crate::subprocess::DEFAULT_TIMEOUT;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HookCmd {
+    pub command: String,
+    /// `post_cmd()` only: run even if nothing in the manifest changed.
+    /// Ignored for `pre_cmd()`, which always runs.
+    pub always: bool,
+}
+
+/// Runs every hook in `hooks`, in declaration order, stopping at the first
+/// failure.
+pub fn run_all(
+    hooks: &[HookCmd],
+    forward_output: bool,
+    redact_patterns: &RedactionRules,
+) -> Result<()> {
+    for hook in hooks {
+        cmd::run(
+            &hook.command,
+            &RunOptions {
+                env: &Default::default(),
+                cwd: None,
+                redact: &[],
+                redact_patterns,
+                retries: 0,
+                timeout: DEFAULT_TIMEOUT,
+                forward_output,
+            },
+        )?;
+    }
+    Ok(())
+}
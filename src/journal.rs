@@ -0,0 +1,87 @@
+//! Records what `keron apply` actually did, so `keron undo` can attempt to
+//! reverse the most recent run. Each apply overwrites the journal with just
+//! that run's mutations, matching the "most recent apply" scope `keron undo`
+//! supports. Lives under [`crate::xdg::data_dir`] rather than the current
+//! directory, like [`crate::history`], so `keron undo` finds it regardless
+//! of where it's run from relative to the `keron apply` it's reversing.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::apply::AppliedOperation;
+
+pub const JOURNAL_FILE_NAME: &str = "keron.journal.json";
+
+fn journal_path() -> PathBuf {
+    crate::xdg::data_dir().join("keron").join(JOURNAL_FILE_NAME)
+}
+
+/// Bumped whenever [`Journal`]'s shape changes incompatibly, so an old
+/// `keron undo` build fails loudly on a journal written by a newer one (or
+/// vice versa) instead of misinterpreting the JSON.
+const JOURNAL_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Journal {
+    version: u32,
+    operations: Vec<AppliedOperation>,
+}
+
+/// Writes `operations` to `dir`'s journal, replacing whatever a previous
+/// apply left there. Does nothing if `operations` is empty, so a no-op apply
+/// doesn't erase the journal from the last apply that actually changed
+/// something.
+pub fn record(operations: Vec<AppliedOperation>) -> Result<()> {
+    if operations.is_empty() {
+        return Ok(());
+    }
+
+    let path = journal_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create `{}`", parent.display()))?;
+    }
+    let journal = Journal {
+        version: JOURNAL_VERSION,
+        operations,
+    };
+    let json =
+        serde_json::to_string_pretty(&journal).context("failed to serialize apply journal")?;
+    std::fs::write(&path, json).with_context(|| format!("failed to write `{}`", path.display()))
+}
+
+/// Loads the journal, if one exists.
+pub fn load() -> Result<Option<Vec<AppliedOperation>>> {
+    let path = journal_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    let journal: Journal = serde_json::from_str(&json)
+        .with_context(|| format!("failed to parse journal `{}`", path.display()))?;
+
+    if journal.version != JOURNAL_VERSION {
+        bail!(
+            "`{}` was written by journal format v{}, but this build only understands v{JOURNAL_VERSION}",
+            path.display(),
+            journal.version,
+        );
+    }
+
+    Ok(Some(journal.operations))
+}
+
+/// Removes the journal after a successful `keron undo`, so a second `keron
+/// undo` doesn't try to reverse the same apply again.
+pub fn clear() -> Result<()> {
+    let path = journal_path();
+    if path.exists() {
+        std::fs::remove_file(&path)
+            .with_context(|| format!("failed to remove `{}`", path.display()))?;
+    }
+    Ok(())
+}
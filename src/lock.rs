@@ -0,0 +1,105 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+pub const LOCK_FILE_NAME: &str = "keron.lock";
+
+/// Records the resolved state of a remote source, so that `--locked` runs
+/// can detect when the upstream has moved.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub source: String,
+    pub commit: String,
+}
+
+impl Lockfile {
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(LOCK_FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        let lockfile: Lockfile = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        Ok(Some(lockfile))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(LOCK_FILE_NAME);
+        let contents = toml::to_string_pretty(self)?;
+        std::fs::write(&path, contents)
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+        Ok(())
+    }
+
+    /// Fails if `commit` doesn't match what was previously recorded.
+    pub fn verify(&self, commit: &str) -> Result<()> {
+        if self.commit != commit {
+            bail!(
+                "source `{}` has moved: locked to {}, but resolved to {}. Run without --locked to update the lock file.",
+                self.source,
+                self.commit,
+                commit
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_passes_when_the_commit_matches() {
+        let lockfile = Lockfile {
+            source: "https://example.com/repo.git".to_string(),
+            commit: "abc123".to_string(),
+        };
+        assert!(lockfile.verify("abc123").is_ok());
+    }
+
+    #[test]
+    fn verify_fails_when_the_source_has_moved() {
+        let lockfile = Lockfile {
+            source: "https://example.com/repo.git".to_string(),
+            commit: "abc123".to_string(),
+        };
+        let error = lockfile.verify("def456").unwrap_err();
+        assert!(error.to_string().contains("locked to abc123"));
+        assert!(error.to_string().contains("resolved to def456"));
+    }
+
+    #[test]
+    fn load_returns_none_when_no_lock_file_exists() {
+        let dir =
+            std::env::temp_dir().join(format!("keron-lock-test-missing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let result = Lockfile::load(&dir).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let dir =
+            std::env::temp_dir().join(format!("keron-lock-test-roundtrip-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let lockfile = Lockfile {
+            source: "https://example.com/repo.git".to_string(),
+            commit: "abc123".to_string(),
+        };
+
+        lockfile.save(&dir).unwrap();
+        let loaded = Lockfile::load(&dir).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.source, lockfile.source);
+        assert_eq!(loaded.commit, lockfile.commit);
+    }
+}
@@ -0,0 +1,45 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Initializes the global `tracing` subscriber. `log_level` (`--log-level`)
+/// takes priority over `RUST_LOG`, which takes priority over the default of
+/// `warn`. When `log_file` is set, logs go to both it and stderr; otherwise
+/// just stderr.
+///
+/// Every event is expected to go through [`crate::secrets::redact`] before
+/// it can contain a resolved secret value, the same rule that governs error
+/// messages and reports; nothing here redacts on the way out.
+pub fn init(log_level: Option<&str>, log_file: Option<&Path>) -> Result<()> {
+    let filter = match log_level {
+        Some(level) => EnvFilter::try_new(level)
+            .with_context(|| format!("invalid `--log-level` value `{level}`"))?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+    };
+
+    let stderr_layer = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_target(false);
+
+    let file_layer = log_file
+        .map(|path| -> Result<_> {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("failed to create log file `{}`", path.display()))?;
+            Ok(tracing_subscriber::fmt::layer()
+                .with_writer(file)
+                .with_ansi(false)
+                .with_target(false))
+        })
+        .transpose()?;
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(stderr_layer)
+        .with(file_layer)
+        .init();
+
+    Ok(())
+}
@@ -1,3 +1,56 @@
+mod agefile;
+mod apply;
+mod cargo_pkg;
+mod cli;
+mod cmd;
+mod commands;
+mod elevate;
+mod exitcode;
+mod facts;
+mod fileblock;
+mod gitrepo;
+mod history;
+mod hooks;
+mod journal;
+mod lock;
+mod logging;
+mod manifest;
+mod notify;
+mod ownership;
+mod pager;
+mod pipx;
+mod plan;
+mod planfile;
+mod providers;
+mod render;
+mod report;
+mod resource;
+mod secrets;
+mod source;
+mod subprocess;
+mod sudo;
+mod xdg;
+
+use clap::Parser;
+use cli::{Cli, Command};
+
 fn main() -> anyhow::Result<()> {
-    Ok(())
+    let cli = Cli::parse();
+    logging::init(cli.log_level.as_deref(), cli.log_file.as_deref())?;
+
+    match &cli.command {
+        Command::Plan(args) => commands::plan::run(args),
+        Command::PlanDiff(args) => commands::plan_diff::run(args),
+        Command::Apply(args) => commands::apply::run(args),
+        Command::Providers(args) => commands::providers::run(args),
+        Command::CheckDrift(args) => commands::check_drift::run(args),
+        Command::Undo(args) => commands::undo::run(args),
+        Command::Import(args) => commands::import::run(args),
+        Command::Completions(args) => commands::completions::run(args),
+        Command::Docs(args) => commands::docs::run(args),
+        Command::History(args) => commands::history::run(args),
+        Command::Tui(args) => commands::tui::run(args),
+        Command::Graph(args) => commands::graph::run(args),
+        Command::Migrate(args) => commands::migrate::run(args),
+    }
 }
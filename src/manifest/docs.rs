@@ -0,0 +1,172 @@
+/// One entry per Lua global `manifest::lua` registers, kept next to the
+/// registration code it describes so `keron docs lua` can't drift far from
+/// what a manifest can actually call — a new `register_*` function should
+/// add its entry here in the same commit.
+pub struct LuaFunctionDoc {
+    pub signature: &'static str,
+    pub description: &'static str,
+}
+
+pub const LUA_FUNCTIONS: &[LuaFunctionDoc] = &[
+    LuaFunctionDoc {
+        signature: "link(source, destination, opts?)",
+        description: "Symlinks `destination` to `source`. `opts.windows_fallback` (`junction`, `hardlink` or \
+            `copy`) picks a degraded stand-in when a real symlink can't be created on Windows. `opts.adopt` \
+            (default `false`) replaces an existing regular `destination` with matching (or not-yet-existing) \
+            content as a non-destructive `adopt` instead of an ordinary update. `opts.owner`/`opts.group` chown \
+            `destination` to a user/group other than whoever's running keron; `opts.elevate` runs that chown \
+            with the detected privilege escalation helper, required unless `owner` is the current user. \
+            `opts.state` (`present`, the default, or `absent`) removes `destination` instead of creating or \
+            updating it, but only when it's still the symlink this resource would have made; anything else \
+            there is left alone as a conflict.",
+    },
+    LuaFunctionDoc {
+        signature: "template(source, destination, vars?, opts?)",
+        description: "Renders `{{ name }}` placeholders (and `{{#each name}}...{{/each}}` loops) in `source` \
+            into `destination`. `vars` is a table of plain values, `secret_ref()`/`env()` handles, nested \
+            tables and lists. `opts.newline` (`crlf`, `lf` or `native`) normalizes the rendered output's line \
+            endings. `opts.sensitive_vars` (a list of names) treats the named plain-string `vars` entries like \
+            `secret_ref()`: hidden from plan-time diffing unless `--resolve-secrets`. The reserved `existing` \
+            variable holds `destination`'s current content, if any. `opts.owner`/`opts.group`/`opts.elevate` \
+            work the same as on `link()`. `opts.state` (`present`, the default, or `absent`) removes \
+            `destination` instead of rendering it, but only when its content still matches what `template()` \
+            would currently render; anything else there is left alone as a conflict.",
+    },
+    LuaFunctionDoc {
+        signature: "git_repo(url, destination, opts?)",
+        description: "Clones `url` into `destination`, or checks it out fresh if it isn't a git repo yet. \
+            `opts.ref` pins a branch/tag/commit; `opts.depth` does a shallow clone.",
+    },
+    LuaFunctionDoc {
+        signature: "file_block(destination, content, opts?)",
+        description: "Merges `content` into `destination` between a pair of marker comments, leaving the rest \
+            of the file untouched. `opts.marker` names the block (default `keron`), so a file can hold several \
+            independently-managed blocks.",
+    },
+    LuaFunctionDoc {
+        signature: "cmd(command, opts?)",
+        description: "Runs `command` through the shell. `opts.creates`/`opts.creates_hash` make it idempotent \
+            by checking a file's existence/hash first; `opts.unless`/`opts.only_if` gate it on another \
+            command's exit code; `opts.env` sets environment variables (accepts `secret_ref()`/`env()` \
+            handles); `opts.cwd` sets the working directory; `opts.retries` and `opts.timeout` (e.g. `\"30s\"`) \
+            control resilience.",
+    },
+    LuaFunctionDoc {
+        signature: "dir(path, opts?)",
+        description: "Ensures `path` exists as a directory. `opts.mode` sets its permissions (octal string, \
+            e.g. `\"0755\"`); `opts.mkdirs` (default `true`) creates missing parents; `opts.elevate` runs the \
+            creation with the detected privilege escalation helper.",
+    },
+    LuaFunctionDoc {
+        signature: "pipx_package(name, opts?)",
+        description: "Installs the Python CLI tool `name`. `opts.version` pins a version (or version \
+            constraint); `opts.provider` (`pipx`, the default, or `uv`) picks the installer; `opts.retries` and \
+            `opts.timeout` (e.g. `\"30s\"`) control resilience.",
+    },
+    LuaFunctionDoc {
+        signature: "cargo_package(name, opts?)",
+        description: "Installs the Rust binary `name` via `cargo install`. `opts.version` pins an exact \
+            version; `opts.locked` passes `--locked`; `opts.git` installs from a git repository instead of \
+            crates.io; `opts.features` passes a list of feature names via `--features`; `opts.retries` and \
+            `opts.timeout` (e.g. `\"30s\"`) control resilience.",
+    },
+    LuaFunctionDoc {
+        signature: "template_encrypted(source, destination, opts?)",
+        description: "Decrypts the age-encrypted file `source` into `destination`. `opts.identity` overrides \
+            the age identity file (default `~/.config/age/keys.txt`).",
+    },
+    LuaFunctionDoc {
+        signature: "secret_ref(uri)",
+        description: "A lazy handle to a secret, resolved only at apply time (or with `--resolve-secrets` at \
+            plan time). Supported URI schemes: `op://vault/item/field` (1Password CLI) and \
+            `keyring://service/account` (the OS keyring).",
+    },
+    LuaFunctionDoc {
+        signature: "env(name, opts?)",
+        description: "Reads environment variable `name` from keron's own environment. `opts.sensitive` \
+            (default `true`) controls whether the value is shown in a plan-time diff or command output.",
+    },
+    LuaFunctionDoc {
+        signature: "xdg_config_home() / xdg_data_home() / xdg_state_home()",
+        description: "The user's XDG config/data/state directory, honouring `XDG_CONFIG_HOME`/`XDG_DATA_HOME`/\
+            `XDG_STATE_HOME` and falling back to `~/.config`, `~/.local/share` and `~/.local/state`.",
+    },
+    LuaFunctionDoc {
+        signature: "appdata()",
+        description: "The Windows per-user roaming application data directory (`%APPDATA%`), falling back to \
+            `xdg_config_home()` on platforms that don't set it.",
+    },
+    LuaFunctionDoc {
+        signature: "facts()",
+        description: "A table of host details gathered fresh for this evaluation: `os`, `arch`, `hostname`, \
+            `username`, `home`, `cpu_count` and `is_wsl`. The same values are merged into every `template()`'s \
+            variables as `{{ facts.* }}`, so a template varies by host without a manifest passing them through \
+            `vars` itself.",
+    },
+    LuaFunctionDoc {
+        signature: "path_join(a, b, ...)",
+        description: "Joins path segments with the current platform's separator, instead of concatenating \
+            strings with `..` and getting it wrong on Windows.",
+    },
+    LuaFunctionDoc {
+        signature: "expand(path)",
+        description: "Expands a leading `~` or `~/` in `path` to the user's home directory. Doesn't understand \
+            `~other_user`.",
+    },
+    LuaFunctionDoc {
+        signature: "file_exists(path)",
+        description: "Reports whether `path` exists on disk. `path` is used exactly as given, with no \
+            manifest-directory sandboxing, since the point is checking host state like `/etc/arch-release`.",
+    },
+    LuaFunctionDoc {
+        signature: "read_file(path, opts?)",
+        description: "Reads `path`'s entire contents as a string. `opts.max_bytes` (default 4096) caps how \
+            large a file can be read; if `path` is larger than that, `read_file` errors instead of silently \
+            returning a truncated prefix. Like `file_exists()`, `path` has no manifest-directory sandboxing.",
+    },
+    LuaFunctionDoc {
+        signature: "vars_file(path)",
+        description: "Reads and evaluates the Lua file at `path` (relative to the current manifest, must not \
+            escape its directory) as a vars table, the same shape `template()`'s `vars` argument takes.",
+    },
+    LuaFunctionDoc {
+        signature: "render(source, vars?)",
+        description: "Renders the template file `source` (resolved relative to the manifest, must not escape \
+            its directory) against `vars` and returns the result as a string immediately, instead of writing \
+            it to a destination like `template()` does. `secret_ref()`/`env()` values in `vars` are always \
+            resolved, never redacted, since the result is a plain string the manifest already has access to.",
+    },
+    LuaFunctionDoc {
+        signature: "import(path)",
+        description: "Reads and evaluates the Lua file at `path` (relative to the current manifest, must not \
+            escape its directory), returning whatever it `return`s. Results are cached per manifest \
+            evaluation, so importing the same file twice only reads it once.",
+    },
+    LuaFunctionDoc {
+        signature: "register_provider(name, opts)",
+        description: "Registers a custom package provider for `keron providers` to detect and report on.",
+    },
+    LuaFunctionDoc {
+        signature: "pre_cmd(command, opts?) / post_cmd(command, opts?)",
+        description: "Runs `command` through the shell once per manifest, before or after all of its resources \
+            are applied, instead of once per resource like `cmd()`. `post_cmd()` only runs when something in \
+            the manifest actually changed, unless `opts.always` is set.",
+    },
+    LuaFunctionDoc {
+        signature: "depends_on(name, ...)",
+        description: "Names other manifest file(s) (e.g. `depends_on(\"base.lua\")`) that must fully apply, \
+            without any operation failing, before this manifest's own resources start applying. Unlike \
+            `after = {...}`, which only orders resources within one manifest, this is what sequences two \
+            manifests against each other; a manifest blocked this way is reported as `SkippedDependency`, the \
+            same outcome an `after = {...}` dependency failure produces.",
+    },
+];
+
+/// Every resource constructor above also accepts `name = "..."` (an
+/// identifier for `--only name:<name>` and reports), `after = {"..."}`
+/// (names this resource must apply after, within the same manifest file),
+/// and `notify = {"..."}` (names of `cmd()` resources, within the same
+/// manifest file, to run only when this resource actually changes, instead
+/// of on every apply).
+pub const RESERVED_OPTS: &str =
+    "name = \"...\", after = {\"other-resource-name\", ...}, notify = {\"cmd-resource-name\", ...}";
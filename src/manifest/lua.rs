@@ -0,0 +1,1780 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use mlua::{Lua, Table, Value, Variadic};
+
+use super::NamedResource;
+use crate::hooks::HookCmd;
+use crate::providers::CustomProvider;
+use crate::resource::{
+    AgeFileResource, CargoPackageResource, CmdResource, DirResource, EnvVarValue,
+    FileBlockResource, GitRepoResource, LinkFallback, LinkResource, Newline, PipxPackageResource,
+    PipxProvider, Resource, SecretRef, State, TemplateResource, TemplateValue,
+};
+use crate::secrets;
+use crate::subprocess;
+
+/// Table key used to tag the table returned by `secret_ref()`, so template
+/// variable tables can tell a lazy secret handle apart from a plain string.
+const SECRET_REF_MARKER: &str = "__keron_secret_ref";
+
+/// Table keys used to tag the table returned by `env()`, so template
+/// variable tables can tell a host-environment handle apart from a plain
+/// string or a `secret_ref()` handle.
+const ENV_VAR_NAME_MARKER: &str = "__keron_env_var_name";
+const ENV_VAR_SENSITIVE_MARKER: &str = "__keron_env_var_sensitive";
+
+/// Default marker for `file_block()` when `opts.marker` isn't set.
+const DEFAULT_FILE_BLOCK_MARKER: &str = "keron";
+
+/// What a single manifest file evaluated to: the resources it declared plus
+/// any custom package providers it registered via `register_provider()`.
+pub struct EvaluatedManifest {
+    pub resources: Vec<NamedResource>,
+    pub providers: Vec<CustomProvider>,
+    /// `pre_cmd()`/`post_cmd()` hooks, in declaration order.
+    pub pre_cmds: Vec<HookCmd>,
+    pub post_cmds: Vec<HookCmd>,
+    /// Manifest filenames named via `depends_on(...)`: this manifest's
+    /// resources must not start applying until every operation from each of
+    /// these manifests has finished. Resolved against the other discovered
+    /// manifests by [`super::resolve_manifest_dependencies`].
+    pub depends_on: Vec<String>,
+    /// Unknown-option warnings collected while parsing resource constructors
+    /// (e.g. a typo'd `forse = true` on `link()`), in declaration order. See
+    /// `extract_meta`.
+    pub warnings: Vec<String>,
+}
+
+/// Dependency-ordering metadata attached to a resource via `name = "..."`,
+/// `after = {"other-name", ...}` and `notify = {"other-name", ...}` on any
+/// resource constructor. Kept separate from [`Resource`] since it only
+/// matters while evaluating a manifest: by the time `EvaluatedManifest` is
+/// built, `resources` is already in its final, dependency-respecting order.
+#[derive(Debug, Default)]
+struct ResourceMeta {
+    name: Option<String>,
+    after: Vec<String>,
+    notify: Vec<String>,
+    /// The 1-based line in the manifest this resource was declared on, if
+    /// mlua's debug info could resolve it. See [`caller_line`].
+    line: Option<u32>,
+}
+
+/// The line number, in the manifest currently being evaluated, that called
+/// into the native function mlua is currently running — i.e. the line a
+/// resource constructor like `dir(...)` was invoked from. Level `0` is the
+/// native function itself, which (being native, not Lua) has no line of its
+/// own; level `1` is its Lua caller. Returns `None` if the interpreter has
+/// debug info stripped, which none of keron's own manifests do, but is
+/// possible for oddly-built Lua binaries.
+fn caller_line(lua: &Lua) -> Option<u32> {
+    lua.inspect_stack(1, |debug| debug.current_line())
+        .flatten()
+        .map(|line| line as u32)
+}
+
+/// Option keys every resource constructor accepts on top of its own,
+/// resource-specific ones (`name`, `after`, `notify` — see
+/// `docs::RESERVED_OPTS`), so `extract_meta`'s unknown-option check doesn't
+/// flag them.
+const RESERVED_META_KEYS: &[&str] = &["name", "after", "notify"];
+
+fn extract_meta(
+    lua: &Lua,
+    opts: Option<&Table>,
+    known_opts: &[&str],
+    label: &str,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> mlua::Result<ResourceMeta> {
+    let line = caller_line(lua);
+    let Some(opts) = opts else {
+        return Ok(ResourceMeta {
+            line,
+            ..ResourceMeta::default()
+        });
+    };
+    let name = opts.get::<Option<String>>("name")?;
+    let after = match opts.get::<Option<Table>>("after")? {
+        Some(table) => table
+            .sequence_values::<String>()
+            .collect::<mlua::Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    let notify = match opts.get::<Option<Table>>("notify")? {
+        Some(table) => table
+            .sequence_values::<String>()
+            .collect::<mlua::Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+
+    for pair in opts.clone().pairs::<String, Value>() {
+        let (key, _) = pair?;
+        if !known_opts.contains(&key.as_str()) && !RESERVED_META_KEYS.contains(&key.as_str()) {
+            warnings.borrow_mut().push(format!(
+                "unknown option `{key}` passed to {label}(){}",
+                at_line(line)
+            ));
+        }
+    }
+
+    Ok(ResourceMeta {
+        name,
+        after,
+        notify,
+        line,
+    })
+}
+
+/// Reorders `resources` so every resource runs after everything named in its
+/// `after` list, preserving declaration order among resources with no such
+/// constraint (a stable topological sort, breaking ties by original index),
+/// and attaches each resource's `name` for downstream reports/filters.
+fn order_by_dependencies(
+    resources: Vec<Resource>,
+    meta: Vec<ResourceMeta>,
+) -> Result<Vec<NamedResource>> {
+    let mut name_to_index = HashMap::new();
+    for (index, entry) in meta.iter().enumerate() {
+        if let Some(name) = &entry.name {
+            if name_to_index.insert(name.clone(), index).is_some() {
+                bail!("duplicate resource name `{name}`");
+            }
+        }
+    }
+
+    let len = resources.len();
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); len];
+    let mut remaining_deps = vec![0usize; len];
+    for (index, entry) in meta.iter().enumerate() {
+        for dependency_name in &entry.after {
+            let &dependency_index = name_to_index.get(dependency_name).with_context(|| {
+                format!(
+                    "`after` references unknown resource name `{dependency_name}`{}",
+                    at_line(entry.line)
+                )
+            })?;
+            dependents[dependency_index].push(index);
+            remaining_deps[index] += 1;
+        }
+        // A `notify` target must run after its notifier, the same way an
+        // `after` dependency does, so the plan knows the notifier's outcome
+        // before deciding whether to fire the target (see
+        // `plan::apply_notify_overrides`).
+        for target_name in &entry.notify {
+            let &target_index = name_to_index.get(target_name).with_context(|| {
+                format!(
+                    "`notify` references unknown resource name `{target_name}`{}",
+                    at_line(entry.line)
+                )
+            })?;
+            if !matches!(resources[target_index], Resource::Cmd(_)) {
+                bail!(
+                    "`notify` target `{target_name}` must be a `cmd()` resource{}",
+                    at_line(entry.line)
+                );
+            }
+            dependents[index].push(target_index);
+            remaining_deps[target_index] += 1;
+        }
+    }
+
+    // A min-heap over ready indices keeps the sort stable: with no
+    // constraints at all, this always drains in declaration order.
+    let mut ready: std::collections::BinaryHeap<std::cmp::Reverse<usize>> = (0..len)
+        .filter(|&index| remaining_deps[index] == 0)
+        .map(std::cmp::Reverse)
+        .collect();
+
+    let mut order = Vec::with_capacity(len);
+    while let Some(std::cmp::Reverse(index)) = ready.pop() {
+        order.push(index);
+        for &dependent in &dependents[index] {
+            remaining_deps[dependent] -= 1;
+            if remaining_deps[dependent] == 0 {
+                ready.push(std::cmp::Reverse(dependent));
+            }
+        }
+    }
+
+    if order.len() != len {
+        let stuck: Vec<usize> = (0..len)
+            .filter(|&index| remaining_deps[index] > 0)
+            .collect();
+        let Some(cycle) = find_cycle(&stuck, &dependents) else {
+            // Every `stuck` resource depends (directly or transitively) on
+            // something that never became ready, which can only happen if
+            // some cycle exists among them — but fall back to a generic
+            // message instead of panicking if that reasoning is ever wrong.
+            bail!("dependency cycle detected among resource `after`/`notify` constraints, but the specific cycle could not be isolated");
+        };
+        let cycle = cycle
+            .into_iter()
+            .map(|index| resource_label(&meta[index], index))
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        bail!("dependency cycle detected among resource `after`/`notify` constraints: {cycle}");
+    }
+
+    let mut resources: Vec<Option<Resource>> = resources.into_iter().map(Some).collect();
+    let mut meta: Vec<Option<ResourceMeta>> = meta.into_iter().map(Some).collect();
+    Ok(order
+        .into_iter()
+        .map(|index| {
+            let entry = meta[index].take().unwrap();
+            NamedResource {
+                resource: resources[index].take().unwrap(),
+                name: entry.name,
+                after: entry.after,
+                notify: entry.notify,
+                line: entry.line,
+            }
+        })
+        .collect())
+}
+
+/// Finds one concrete cycle among `stuck` (every resource that
+/// [`order_by_dependencies`]'s topological sort couldn't place — either part
+/// of a cycle itself, or dependent on one). `stuck` alone isn't enough to
+/// walk directly: a resource can be stuck because it depends on a cycle
+/// without being part of one itself (nothing depends back on it), so this
+/// does a proper depth-first search restricted to `stuck` nodes, tracking
+/// each node's position on the current path to detect a repeat, rather than
+/// assuming the first `stuck` node has an outgoing edge back into `stuck`.
+/// Returns `None` if `stuck` doesn't actually contain a cycle.
+fn find_cycle(stuck: &[usize], dependents: &[Vec<usize>]) -> Option<Vec<usize>> {
+    let stuck_set: HashSet<usize> = stuck.iter().copied().collect();
+    let mut on_path: HashMap<usize, usize> = HashMap::new();
+    let mut done: HashSet<usize> = HashSet::new();
+    let mut path: Vec<usize> = Vec::new();
+
+    for &start in stuck {
+        if !done.contains(&start) {
+            if let Some(cycle) = find_cycle_from(
+                start,
+                &stuck_set,
+                dependents,
+                &mut on_path,
+                &mut done,
+                &mut path,
+            ) {
+                return Some(cycle);
+            }
+        }
+    }
+    None
+}
+
+/// The recursive DFS step behind [`find_cycle`]. `on_path` maps every node
+/// currently on the open path to its index within `path`, so hitting an edge
+/// into one of them both detects the cycle and slices out exactly its span
+/// (everything from that index onward) without a separate search. `done`
+/// marks nodes whose subtree is fully explored with no cycle found, so a
+/// node reachable from multiple starting points is never re-walked.
+fn find_cycle_from(
+    node: usize,
+    stuck: &HashSet<usize>,
+    dependents: &[Vec<usize>],
+    on_path: &mut HashMap<usize, usize>,
+    done: &mut HashSet<usize>,
+    path: &mut Vec<usize>,
+) -> Option<Vec<usize>> {
+    on_path.insert(node, path.len());
+    path.push(node);
+
+    for &next in &dependents[node] {
+        if !stuck.contains(&next) {
+            continue;
+        }
+        if let Some(&start_of_cycle) = on_path.get(&next) {
+            let mut cycle = path[start_of_cycle..].to_vec();
+            cycle.push(next);
+            return Some(cycle);
+        }
+        if !done.contains(&next) {
+            if let Some(cycle) = find_cycle_from(next, stuck, dependents, on_path, done, path) {
+                return Some(cycle);
+            }
+        }
+    }
+
+    path.pop();
+    on_path.remove(&node);
+    done.insert(node);
+    None
+}
+
+/// A resource's `name`, or its declaration line if it has none, for naming
+/// it in a cycle error where a bare index would mean nothing to the person
+/// reading the manifest.
+fn resource_label(meta: &ResourceMeta, index: usize) -> String {
+    match &meta.name {
+        Some(name) => name.clone(),
+        None => match meta.line {
+            Some(line) => format!("<resource at line {line}>"),
+            None => format!("<resource #{index}>"),
+        },
+    }
+}
+
+/// Formats `", declared at line N"` for an error message, or an empty string
+/// when the line couldn't be resolved (see [`caller_line`]).
+fn at_line(line: Option<u32>) -> String {
+    match line {
+        Some(line) => format!(", declared at line {line}"),
+        None => String::new(),
+    }
+}
+
+/// Evaluates a single manifest file, running its Lua script in a sandbox
+/// that exposes the resource-declaration functions (`link()`, `template()`,
+/// ...) and collects every resource it declares, in declaration order.
+/// `globals` (from `globals.lua`, see [`super::GLOBALS_FILE_NAME`]) is
+/// exposed as the `globals` table.
+pub fn evaluate_manifest(
+    path: &Path,
+    globals: &BTreeMap<String, TemplateValue>,
+) -> Result<EvaluatedManifest> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read manifest `{}`", path.display()))?;
+
+    let lua = Lua::new();
+    let resources = Rc::new(RefCell::new(Vec::new()));
+    let meta = Rc::new(RefCell::new(Vec::new()));
+    let providers = Rc::new(RefCell::new(Vec::new()));
+    let pre_cmds = Rc::new(RefCell::new(Vec::new()));
+    let post_cmds = Rc::new(RefCell::new(Vec::new()));
+    let depends_on = Rc::new(RefCell::new(Vec::new()));
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+
+    register_globals(&lua, globals)?;
+    register_link(&lua, &resources, &meta, &warnings)?;
+    register_template(&lua, &resources, &meta, &warnings)?;
+    register_secret_ref(&lua)?;
+    register_env(&lua)?;
+    register_xdg(&lua)?;
+    register_path(&lua)?;
+    register_facts(&lua)?;
+    register_file_helpers(&lua)?;
+    register_vars_file(&lua, path)?;
+    register_import(&lua, path)?;
+    register_render(&lua, path)?;
+    register_git_repo(&lua, &resources, &meta, &warnings)?;
+    register_file_block(&lua, &resources, &meta, &warnings)?;
+    register_cmd(&lua, &resources, &meta, &warnings)?;
+    register_dir(&lua, &resources, &meta, &warnings)?;
+    register_pipx_package(&lua, &resources, &meta, &warnings)?;
+    register_cargo_package(&lua, &resources, &meta, &warnings)?;
+    register_template_encrypted(&lua, &resources, &meta, &warnings)?;
+    register_provider(&lua, &providers)?;
+    register_hooks(&lua, &pre_cmds, &post_cmds)?;
+    register_depends_on(&lua, &depends_on)?;
+
+    lua.load(&source)
+        .set_name(path.to_string_lossy())
+        .exec()
+        .with_context(|| format!("failed to evaluate manifest `{}`", path.display()))?;
+
+    // Drop the Lua VM so it releases its references to `resources`, `meta`
+    // and `providers` before we try to unwrap the `Rc`s.
+    drop(lua);
+
+    let resources = Rc::try_unwrap(resources)
+        .expect("no outstanding references to the resource list after evaluation")
+        .into_inner();
+    let meta = Rc::try_unwrap(meta)
+        .expect("no outstanding references to the resource metadata list after evaluation")
+        .into_inner();
+
+    Ok(EvaluatedManifest {
+        resources: order_by_dependencies(resources, meta)
+            .with_context(|| format!("while ordering resources in `{}`", path.display()))?,
+        providers: Rc::try_unwrap(providers)
+            .expect("no outstanding references to the provider list after evaluation")
+            .into_inner(),
+        pre_cmds: Rc::try_unwrap(pre_cmds)
+            .expect("no outstanding references to the pre_cmd list after evaluation")
+            .into_inner(),
+        post_cmds: Rc::try_unwrap(post_cmds)
+            .expect("no outstanding references to the post_cmd list after evaluation")
+            .into_inner(),
+        depends_on: Rc::try_unwrap(depends_on)
+            .expect("no outstanding references to the depends_on list after evaluation")
+            .into_inner(),
+        warnings: Rc::try_unwrap(warnings)
+            .expect("no outstanding references to the warnings list after evaluation")
+            .into_inner(),
+    })
+}
+
+/// Registers `register_provider(name, { detect, list, install, remove })`
+/// for declaring a package manager keron has no built-in support for.
+fn register_provider(lua: &Lua, providers: &Rc<RefCell<Vec<CustomProvider>>>) -> Result<()> {
+    let providers = Rc::clone(providers);
+    let register_provider = lua.create_function(move |_, (name, opts): (String, Table)| {
+        let detect = opts.get::<String>("detect")?;
+        let list = opts.get::<Option<String>>("list")?;
+        let install = opts.get::<Option<String>>("install")?;
+        let remove = opts.get::<Option<String>>("remove")?;
+
+        providers.borrow_mut().push(CustomProvider {
+            name,
+            detect,
+            list,
+            install,
+            remove,
+        });
+        Ok(())
+    })?;
+    lua.globals().set("register_provider", register_provider)?;
+    Ok(())
+}
+
+/// Registers `pre_cmd(command, opts?)` and `post_cmd(command, opts?)`: shell
+/// commands run once for the whole manifest, before/after its resources are
+/// applied, rather than once per resource like `cmd()`. `post_cmd()` only
+/// runs when at least one of the manifest's resources actually changed,
+/// unless `opts.always` is set.
+fn register_hooks(
+    lua: &Lua,
+    pre_cmds: &Rc<RefCell<Vec<HookCmd>>>,
+    post_cmds: &Rc<RefCell<Vec<HookCmd>>>,
+) -> Result<()> {
+    let pre = Rc::clone(pre_cmds);
+    let pre_cmd = lua.create_function(move |_, (command, _opts): (String, Option<Table>)| {
+        pre.borrow_mut().push(HookCmd {
+            command,
+            always: false,
+        });
+        Ok(())
+    })?;
+    lua.globals().set("pre_cmd", pre_cmd)?;
+
+    let post = Rc::clone(post_cmds);
+    let post_cmd = lua.create_function(move |_, (command, opts): (String, Option<Table>)| {
+        let always = match &opts {
+            Some(opts) => opts.get::<Option<bool>>("always")?.unwrap_or(false),
+            None => false,
+        };
+        post.borrow_mut().push(HookCmd { command, always });
+        Ok(())
+    })?;
+    lua.globals().set("post_cmd", post_cmd)?;
+
+    Ok(())
+}
+
+/// Registers `depends_on(name, ...)`: names the manifest file(s) (e.g.
+/// `"base.lua"`) whose operations must all finish, without failing, before
+/// this manifest's own resources start applying. Unlike `after =` (which
+/// orders resources within one manifest), this is the only way to sequence
+/// two manifests against each other.
+fn register_depends_on(lua: &Lua, depends_on: &Rc<RefCell<Vec<String>>>) -> Result<()> {
+    let depends_on = Rc::clone(depends_on);
+    let depends_on_fn = lua.create_function(move |_, names: Variadic<String>| {
+        depends_on.borrow_mut().extend(names);
+        Ok(())
+    })?;
+    lua.globals().set("depends_on", depends_on_fn)?;
+    Ok(())
+}
+
+/// Parses the `state = "present" | "absent"` opt shared by `link()` and
+/// `template()`, defaulting to `present` so leaving it out never changes
+/// existing behavior.
+fn parse_state(opts: Option<&Table>) -> mlua::Result<State> {
+    let state = match opts {
+        Some(opts) => opts.get::<Option<String>>("state")?,
+        None => None,
+    };
+    match state.as_deref() {
+        None | Some("present") => Ok(State::Present),
+        Some("absent") => Ok(State::Absent),
+        Some(other) => Err(mlua::Error::runtime(format!(
+            "invalid `state` `{other}` (expected `present` or `absent`)"
+        ))),
+    }
+}
+
+const LINK_OPTS: &[&str] = &[
+    "windows_fallback",
+    "adopt",
+    "owner",
+    "group",
+    "elevate",
+    "state",
+];
+
+fn register_link(
+    lua: &Lua,
+    resources: &Rc<RefCell<Vec<Resource>>>,
+    meta: &Rc<RefCell<Vec<ResourceMeta>>>,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> Result<()> {
+    let resources = Rc::clone(resources);
+    let meta = Rc::clone(meta);
+    let warnings = Rc::clone(warnings);
+    let link = lua.create_function(
+        move |lua, (source, destination, opts): (String, String, Option<Table>)| {
+            let windows_fallback = match &opts {
+                Some(opts) => opts.get::<Option<String>>("windows_fallback")?,
+                None => None,
+            }
+            .map(|fallback| match fallback.as_str() {
+                "junction" => Ok(LinkFallback::Junction),
+                "hardlink" => Ok(LinkFallback::Hardlink),
+                "copy" => Ok(LinkFallback::Copy),
+                other => Err(mlua::Error::runtime(format!(
+                    "invalid `windows_fallback` `{other}` (expected `junction`, `hardlink` or `copy`)"
+                ))),
+            })
+            .transpose()?;
+
+            let adopt = match &opts {
+                Some(opts) => opts.get::<Option<bool>>("adopt")?,
+                None => None,
+            }
+            .unwrap_or(false);
+            let owner = match &opts {
+                Some(opts) => opts.get::<Option<String>>("owner")?,
+                None => None,
+            };
+            let group = match &opts {
+                Some(opts) => opts.get::<Option<String>>("group")?,
+                None => None,
+            };
+            let elevate = match &opts {
+                Some(opts) => opts.get::<Option<bool>>("elevate")?,
+                None => None,
+            }
+            .unwrap_or(false);
+            let state = parse_state(opts.as_ref())?;
+
+            meta.borrow_mut().push(extract_meta(lua, opts.as_ref(), LINK_OPTS, "link", &warnings)?);
+            resources.borrow_mut().push(Resource::Link(LinkResource {
+                source: source.into(),
+                destination: destination.into(),
+                windows_fallback,
+                adopt,
+                owner,
+                group,
+                elevate,
+                state,
+            }));
+            Ok(())
+        },
+    )?;
+    lua.globals().set("link", link)?;
+    Ok(())
+}
+
+const TEMPLATE_OPTS: &[&str] = &[
+    "sensitive_vars",
+    "newline",
+    "owner",
+    "group",
+    "elevate",
+    "state",
+];
+
+fn register_template(
+    lua: &Lua,
+    resources: &Rc<RefCell<Vec<Resource>>>,
+    meta: &Rc<RefCell<Vec<ResourceMeta>>>,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> Result<()> {
+    let resources = Rc::clone(resources);
+    let meta = Rc::clone(meta);
+    let warnings = Rc::clone(warnings);
+    let template =
+        lua.create_function(
+            move |lua,
+                  (source, destination, vars, opts): (
+                String,
+                String,
+                Option<Table>,
+                Option<Table>,
+            )| {
+                let mut vars = match vars {
+                    Some(table) => table_to_vars(&table)?,
+                    None => BTreeMap::new(),
+                };
+                let sensitive_vars: Vec<String> = match &opts {
+                    Some(opts) => opts
+                        .get::<Option<Vec<String>>>("sensitive_vars")?
+                        .unwrap_or_default(),
+                    None => Vec::new(),
+                };
+                for name in &sensitive_vars {
+                    if let Some(TemplateValue::Str(value)) = vars.get(name) {
+                        vars.insert(name.clone(), TemplateValue::SensitiveStr(value.clone()));
+                    }
+                }
+                let newline = match &opts {
+                    Some(opts) => opts.get::<Option<String>>("newline")?,
+                    None => None,
+                }
+                .map(|newline| match newline.as_str() {
+                    "crlf" => Ok(Newline::Crlf),
+                    "lf" => Ok(Newline::Lf),
+                    "native" => Ok(Newline::Native),
+                    other => Err(mlua::Error::runtime(format!(
+                        "invalid `newline` `{other}` (expected `crlf`, `lf` or `native`)"
+                    ))),
+                })
+                .transpose()?;
+                let owner = match &opts {
+                    Some(opts) => opts.get::<Option<String>>("owner")?,
+                    None => None,
+                };
+                let group = match &opts {
+                    Some(opts) => opts.get::<Option<String>>("group")?,
+                    None => None,
+                };
+                let elevate = match &opts {
+                    Some(opts) => opts.get::<Option<bool>>("elevate")?,
+                    None => None,
+                }
+                .unwrap_or(false);
+                let state = parse_state(opts.as_ref())?;
+
+                meta.borrow_mut().push(extract_meta(
+                    lua,
+                    opts.as_ref(),
+                    TEMPLATE_OPTS,
+                    "template",
+                    &warnings,
+                )?);
+                resources
+                    .borrow_mut()
+                    .push(Resource::Template(TemplateResource {
+                        source: source.into(),
+                        destination: destination.into(),
+                        vars,
+                        newline,
+                        owner,
+                        group,
+                        elevate,
+                        state,
+                    }));
+                Ok(())
+            },
+        )?;
+    lua.globals().set("template", template)?;
+    Ok(())
+}
+
+const GIT_REPO_OPTS: &[&str] = &["ref", "depth"];
+
+fn register_git_repo(
+    lua: &Lua,
+    resources: &Rc<RefCell<Vec<Resource>>>,
+    meta: &Rc<RefCell<Vec<ResourceMeta>>>,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> Result<()> {
+    let resources = Rc::clone(resources);
+    let meta = Rc::clone(meta);
+    let warnings = Rc::clone(warnings);
+    let git_repo = lua.create_function(
+        move |lua, (url, destination, opts): (String, String, Option<Table>)| {
+            let reference = match &opts {
+                Some(opts) => opts.get::<Option<String>>("ref")?,
+                None => None,
+            };
+            let depth = match &opts {
+                Some(opts) => opts.get::<Option<u32>>("depth")?,
+                None => None,
+            };
+
+            meta.borrow_mut().push(extract_meta(
+                lua,
+                opts.as_ref(),
+                GIT_REPO_OPTS,
+                "git_repo",
+                &warnings,
+            )?);
+            resources
+                .borrow_mut()
+                .push(Resource::GitRepo(GitRepoResource {
+                    url,
+                    destination: destination.into(),
+                    reference,
+                    depth,
+                }));
+            Ok(())
+        },
+    )?;
+    lua.globals().set("git_repo", git_repo)?;
+    Ok(())
+}
+
+const FILE_BLOCK_OPTS: &[&str] = &["marker"];
+
+fn register_file_block(
+    lua: &Lua,
+    resources: &Rc<RefCell<Vec<Resource>>>,
+    meta: &Rc<RefCell<Vec<ResourceMeta>>>,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> Result<()> {
+    let resources = Rc::clone(resources);
+    let meta = Rc::clone(meta);
+    let warnings = Rc::clone(warnings);
+    let file_block = lua.create_function(
+        move |lua, (destination, content, opts): (String, String, Option<Table>)| {
+            let marker = match &opts {
+                Some(opts) => opts.get::<Option<String>>("marker")?,
+                None => None,
+            }
+            .unwrap_or_else(|| DEFAULT_FILE_BLOCK_MARKER.to_string());
+
+            meta.borrow_mut().push(extract_meta(
+                lua,
+                opts.as_ref(),
+                FILE_BLOCK_OPTS,
+                "file_block",
+                &warnings,
+            )?);
+            resources
+                .borrow_mut()
+                .push(Resource::FileBlock(FileBlockResource {
+                    destination: destination.into(),
+                    content,
+                    marker,
+                }));
+            Ok(())
+        },
+    )?;
+    lua.globals().set("file_block", file_block)?;
+    Ok(())
+}
+
+const CMD_OPTS: &[&str] = &[
+    "creates",
+    "creates_hash",
+    "unless",
+    "only_if",
+    "env",
+    "cwd",
+    "retries",
+    "timeout",
+];
+
+fn register_cmd(
+    lua: &Lua,
+    resources: &Rc<RefCell<Vec<Resource>>>,
+    meta: &Rc<RefCell<Vec<ResourceMeta>>>,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> Result<()> {
+    let resources = Rc::clone(resources);
+    let meta = Rc::clone(meta);
+    let warnings = Rc::clone(warnings);
+    let cmd = lua.create_function(move |lua, (command, opts): (String, Option<Table>)| {
+        let creates = match &opts {
+            Some(opts) => opts.get::<Option<String>>("creates")?,
+            None => None,
+        };
+        let creates_hash = match &opts {
+            Some(opts) => opts.get::<Option<String>>("creates_hash")?,
+            None => None,
+        };
+        let unless = match &opts {
+            Some(opts) => opts.get::<Option<String>>("unless")?,
+            None => None,
+        };
+        let only_if = match &opts {
+            Some(opts) => opts.get::<Option<String>>("only_if")?,
+            None => None,
+        };
+        let env = match &opts {
+            Some(opts) => match opts.get::<Option<Table>>("env")? {
+                Some(table) => table_to_vars(&table)?,
+                None => BTreeMap::new(),
+            },
+            None => BTreeMap::new(),
+        };
+        let cwd = match &opts {
+            Some(opts) => opts.get::<Option<String>>("cwd")?,
+            None => None,
+        };
+        let retries = match &opts {
+            Some(opts) => opts.get::<Option<u32>>("retries")?,
+            None => None,
+        }
+        .unwrap_or(0);
+        let timeout = match &opts {
+            Some(opts) => opts.get::<Option<String>>("timeout")?,
+            None => None,
+        }
+        .map(|timeout| parse_duration(&timeout))
+        .transpose()
+        .map_err(|err| mlua::Error::runtime(format!("invalid `timeout`: {err}")))?
+        .unwrap_or(subprocess::DEFAULT_TIMEOUT);
+
+        meta.borrow_mut().push(extract_meta(
+            lua,
+            opts.as_ref(),
+            CMD_OPTS,
+            "cmd",
+            &warnings,
+        )?);
+        resources.borrow_mut().push(Resource::Cmd(CmdResource {
+            command,
+            creates: creates.map(Into::into),
+            creates_hash,
+            unless,
+            only_if,
+            env,
+            cwd: cwd.map(Into::into),
+            retries,
+            timeout,
+        }));
+        Ok(())
+    })?;
+    lua.globals().set("cmd", cmd)?;
+    Ok(())
+}
+
+const DIR_OPTS: &[&str] = &["mode", "mkdirs", "elevate"];
+
+fn register_dir(
+    lua: &Lua,
+    resources: &Rc<RefCell<Vec<Resource>>>,
+    meta: &Rc<RefCell<Vec<ResourceMeta>>>,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> Result<()> {
+    let resources = Rc::clone(resources);
+    let meta = Rc::clone(meta);
+    let warnings = Rc::clone(warnings);
+    let dir = lua.create_function(move |lua, (path, opts): (String, Option<Table>)| {
+        let mode = match &opts {
+            Some(opts) => opts.get::<Option<String>>("mode")?,
+            None => None,
+        }
+        .map(|mode| u32::from_str_radix(&mode, 8))
+        .transpose()
+        .map_err(|err| mlua::Error::runtime(format!("invalid `mode`: {err}")))?;
+        let mkdirs = match &opts {
+            Some(opts) => opts.get::<Option<bool>>("mkdirs")?,
+            None => None,
+        }
+        .unwrap_or(true);
+        let elevate = match &opts {
+            Some(opts) => opts.get::<Option<bool>>("elevate")?,
+            None => None,
+        }
+        .unwrap_or(false);
+
+        meta.borrow_mut().push(extract_meta(
+            lua,
+            opts.as_ref(),
+            DIR_OPTS,
+            "dir",
+            &warnings,
+        )?);
+        resources.borrow_mut().push(Resource::Dir(DirResource {
+            path: path.into(),
+            mode,
+            mkdirs,
+            elevate,
+        }));
+        Ok(())
+    })?;
+    lua.globals().set("dir", dir)?;
+    Ok(())
+}
+
+const PIPX_PACKAGE_OPTS: &[&str] = &["version", "provider", "retries", "timeout"];
+
+/// Registers `pipx_package(name, { version, provider })` for installing a
+/// Python CLI tool via `pipx` (the default) or `uv tool`.
+fn register_pipx_package(
+    lua: &Lua,
+    resources: &Rc<RefCell<Vec<Resource>>>,
+    meta: &Rc<RefCell<Vec<ResourceMeta>>>,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> Result<()> {
+    let resources = Rc::clone(resources);
+    let meta = Rc::clone(meta);
+    let warnings = Rc::clone(warnings);
+    let pipx_package = lua.create_function(move |lua, (name, opts): (String, Option<Table>)| {
+        let version = match &opts {
+            Some(opts) => opts.get::<Option<String>>("version")?,
+            None => None,
+        };
+        let provider = match &opts {
+            Some(opts) => opts.get::<Option<String>>("provider")?,
+            None => None,
+        }
+        .unwrap_or_else(|| "pipx".to_string());
+        let provider = match provider.as_str() {
+            "pipx" => PipxProvider::Pipx,
+            "uv" => PipxProvider::Uv,
+            other => {
+                return Err(mlua::Error::runtime(format!(
+                    "invalid `provider` `{other}` (expected `pipx` or `uv`)"
+                )))
+            }
+        };
+        let retries = match &opts {
+            Some(opts) => opts.get::<Option<u32>>("retries")?,
+            None => None,
+        }
+        .unwrap_or(0);
+        let timeout = match &opts {
+            Some(opts) => opts.get::<Option<String>>("timeout")?,
+            None => None,
+        }
+        .map(|timeout| parse_duration(&timeout))
+        .transpose()
+        .map_err(|err| mlua::Error::runtime(format!("invalid `timeout`: {err}")))?
+        .unwrap_or(subprocess::DEFAULT_TIMEOUT);
+
+        meta.borrow_mut().push(extract_meta(
+            lua,
+            opts.as_ref(),
+            PIPX_PACKAGE_OPTS,
+            "pipx_package",
+            &warnings,
+        )?);
+        resources
+            .borrow_mut()
+            .push(Resource::PipxPackage(PipxPackageResource {
+                name,
+                version,
+                provider,
+                retries,
+                timeout,
+            }));
+        Ok(())
+    })?;
+    lua.globals().set("pipx_package", pipx_package)?;
+    Ok(())
+}
+
+const CARGO_PACKAGE_OPTS: &[&str] = &["version", "locked", "git", "features", "retries", "timeout"];
+
+/// Registers `cargo_package(name, { version, locked, git, features })` for
+/// installing a Rust binary via `cargo install`.
+fn register_cargo_package(
+    lua: &Lua,
+    resources: &Rc<RefCell<Vec<Resource>>>,
+    meta: &Rc<RefCell<Vec<ResourceMeta>>>,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> Result<()> {
+    let resources = Rc::clone(resources);
+    let meta = Rc::clone(meta);
+    let warnings = Rc::clone(warnings);
+    let cargo_package =
+        lua.create_function(move |lua, (name, opts): (String, Option<Table>)| {
+            let version = match &opts {
+                Some(opts) => opts.get::<Option<String>>("version")?,
+                None => None,
+            };
+            let locked = match &opts {
+                Some(opts) => opts.get::<Option<bool>>("locked")?,
+                None => None,
+            }
+            .unwrap_or(false);
+            let git = match &opts {
+                Some(opts) => opts.get::<Option<String>>("git")?,
+                None => None,
+            };
+            let features = match &opts {
+                Some(opts) => match opts.get::<Option<Table>>("features")? {
+                    Some(table) => table
+                        .sequence_values::<String>()
+                        .collect::<mlua::Result<Vec<_>>>()?,
+                    None => Vec::new(),
+                },
+                None => Vec::new(),
+            };
+            let retries = match &opts {
+                Some(opts) => opts.get::<Option<u32>>("retries")?,
+                None => None,
+            }
+            .unwrap_or(0);
+            let timeout = match &opts {
+                Some(opts) => opts.get::<Option<String>>("timeout")?,
+                None => None,
+            }
+            .map(|timeout| parse_duration(&timeout))
+            .transpose()
+            .map_err(|err| mlua::Error::runtime(format!("invalid `timeout`: {err}")))?
+            .unwrap_or(subprocess::DEFAULT_TIMEOUT);
+
+            meta.borrow_mut().push(extract_meta(
+                lua,
+                opts.as_ref(),
+                CARGO_PACKAGE_OPTS,
+                "cargo_package",
+                &warnings,
+            )?);
+            resources
+                .borrow_mut()
+                .push(Resource::CargoPackage(CargoPackageResource {
+                    name,
+                    version,
+                    locked,
+                    git,
+                    features,
+                    retries,
+                    timeout,
+                }));
+            Ok(())
+        })?;
+    lua.globals().set("cargo_package", cargo_package)?;
+    Ok(())
+}
+
+const TEMPLATE_ENCRYPTED_OPTS: &[&str] = &["identity"];
+
+/// Registers `template_encrypted(source, destination, { identity })` for
+/// decrypting an age-encrypted file at plan/apply time. `identity` defaults
+/// to `~/.config/age/keys.txt`, the file `age-keygen` writes to by default.
+fn register_template_encrypted(
+    lua: &Lua,
+    resources: &Rc<RefCell<Vec<Resource>>>,
+    meta: &Rc<RefCell<Vec<ResourceMeta>>>,
+    warnings: &Rc<RefCell<Vec<String>>>,
+) -> Result<()> {
+    let resources = Rc::clone(resources);
+    let meta = Rc::clone(meta);
+    let warnings = Rc::clone(warnings);
+    let template_encrypted = lua.create_function(
+        move |lua, (source, destination, opts): (String, String, Option<Table>)| {
+            let identity = match &opts {
+                Some(opts) => opts.get::<Option<String>>("identity")?,
+                None => None,
+            };
+            let identity = identity
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(crate::agefile::default_identity_file);
+
+            meta.borrow_mut().push(extract_meta(
+                lua,
+                opts.as_ref(),
+                TEMPLATE_ENCRYPTED_OPTS,
+                "template_encrypted",
+                &warnings,
+            )?);
+            resources
+                .borrow_mut()
+                .push(Resource::AgeFile(AgeFileResource {
+                    source: source.into(),
+                    destination: destination.into(),
+                    identity,
+                }));
+            Ok(())
+        },
+    )?;
+    lua.globals()
+        .set("template_encrypted", template_encrypted)?;
+    Ok(())
+}
+
+fn register_secret_ref(lua: &Lua) -> Result<()> {
+    let secret_ref = lua.create_function(|lua, uri: String| {
+        let table = lua.create_table()?;
+        table.set(SECRET_REF_MARKER, uri)?;
+        Ok(table)
+    })?;
+    lua.globals().set("secret_ref", secret_ref)?;
+    Ok(())
+}
+
+/// Registers `env(name, { sensitive = true })` for pulling a value from
+/// keron's own environment into a `template()`/`cmd()` variable. `sensitive`
+/// defaults to `true`, since host environment variables commonly carry
+/// credentials picked up from the shell (`AWS_SECRET_ACCESS_KEY`, ...); pass
+/// `sensitive = false` for values like `env("HOME")` that are fine to show
+/// in a plan-time diff or command failure output.
+fn register_env(lua: &Lua) -> Result<()> {
+    let env = lua.create_function(|lua, (name, opts): (String, Option<Table>)| {
+        let sensitive = match &opts {
+            Some(opts) => opts.get::<Option<bool>>("sensitive")?.unwrap_or(true),
+            None => true,
+        };
+
+        let table = lua.create_table()?;
+        table.set(ENV_VAR_NAME_MARKER, name)?;
+        table.set(ENV_VAR_SENSITIVE_MARKER, sensitive)?;
+        Ok(table)
+    })?;
+    lua.globals().set("env", env)?;
+    Ok(())
+}
+
+/// Registers `xdg_config_home()`, `xdg_data_home()`, `xdg_state_home()` and
+/// `appdata()` as plain string-returning functions, so a manifest can build
+/// portable paths (`xdg_config_home() .. "/nvim/init.lua"`) instead of
+/// hard-coding `$HOME/.config` and friends.
+fn register_xdg(lua: &Lua) -> Result<()> {
+    let globals = lua.globals();
+    globals.set(
+        "xdg_config_home",
+        lua.create_function(|_, ()| Ok(crate::xdg::config_dir().to_string_lossy().into_owned()))?,
+    )?;
+    globals.set(
+        "xdg_data_home",
+        lua.create_function(|_, ()| Ok(crate::xdg::data_dir().to_string_lossy().into_owned()))?,
+    )?;
+    globals.set(
+        "xdg_state_home",
+        lua.create_function(|_, ()| Ok(crate::xdg::state_dir().to_string_lossy().into_owned()))?,
+    )?;
+    globals.set(
+        "appdata",
+        lua.create_function(|_, ()| Ok(crate::xdg::appdata_dir().to_string_lossy().into_owned()))?,
+    )?;
+    Ok(())
+}
+
+/// Registers `facts()`, returning a table of host details (`os`, `arch`,
+/// `hostname`, `username`, `home`, `cpu_count`, `is_wsl`) gathered fresh for
+/// this evaluation, so a manifest can branch on the host it's running on
+/// (`if facts().os == "linux" then ... end`) without shelling out itself.
+/// The same values are merged into every `template()`'s variables as
+/// `{{ facts.* }}` (see [`crate::render::with_facts`]), so a template gets
+/// them without a manifest passing them through `vars` by hand.
+fn register_facts(lua: &Lua) -> Result<()> {
+    let facts = crate::facts::Facts::gather();
+    let table = lua.create_table()?;
+    table.set("os", facts.os)?;
+    table.set("arch", facts.arch)?;
+    table.set("hostname", facts.hostname)?;
+    table.set("username", facts.username)?;
+    table.set("home", facts.home)?;
+    table.set("cpu_count", facts.cpu_count)?;
+    table.set("is_wsl", facts.is_wsl)?;
+
+    lua.globals().set(
+        "facts",
+        lua.create_function(move |_, ()| Ok(table.clone()))?,
+    )?;
+    Ok(())
+}
+
+/// Registers `path_join(a, b, ...)` and `expand("~/foo")`, so a manifest
+/// builds paths with `std::path::Path::join` semantics (one separator per
+/// platform, no accumulated `..` string concatenation) and can spell a
+/// home-relative path without reaching for `env("HOME")` itself.
+fn register_path(lua: &Lua) -> Result<()> {
+    let globals = lua.globals();
+    globals.set(
+        "path_join",
+        lua.create_function(|_, segments: Variadic<String>| {
+            let mut path = std::path::PathBuf::new();
+            for segment in segments {
+                path.push(segment);
+            }
+            Ok(path.to_string_lossy().into_owned())
+        })?,
+    )?;
+    globals.set(
+        "expand",
+        lua.create_function(|_, path: String| {
+            Ok(crate::xdg::expand_tilde(&path)
+                .to_string_lossy()
+                .into_owned())
+        })?,
+    )?;
+    Ok(())
+}
+
+/// Default `opts.max_bytes` for `read_file()` when the manifest doesn't ask
+/// for a specific limit: enough for a marker file or a small config
+/// fragment, small enough that a manifest checking a handful of these on
+/// every evaluation stays fast.
+const DEFAULT_READ_FILE_MAX_BYTES: u64 = 4096;
+
+/// Registers `file_exists(path)` and `read_file(path, opts?)`, the only
+/// filesystem access a manifest script gets: the sandbox has no `io`
+/// library at all, but manifests legitimately need to branch on
+/// host-specific state (does `/etc/arch-release` exist?) or fold a small
+/// marker file's contents into a template variable. Both take `path`
+/// exactly as given — unlike `vars_file()`/`import()`/`render()`, there's no
+/// manifest-directory sandboxing here, since the whole point is reading
+/// files *outside* the manifest tree (`/etc/os-release` and the like); the
+/// only limit enforced is `read_file`'s `opts.max_bytes` (default
+/// [`DEFAULT_READ_FILE_MAX_BYTES`]), which errors rather than silently
+/// truncates, so a manifest never gets back a partial config file without
+/// realizing it.
+fn register_file_helpers(lua: &Lua) -> Result<()> {
+    let globals = lua.globals();
+    globals.set(
+        "file_exists",
+        lua.create_function(|_, path: String| Ok(Path::new(&path).exists()))?,
+    )?;
+    globals.set(
+        "read_file",
+        lua.create_function(|_, (path, opts): (String, Option<Table>)| {
+            let max_bytes = match &opts {
+                Some(opts) => opts.get::<Option<u64>>("max_bytes")?,
+                None => None,
+            }
+            .unwrap_or(DEFAULT_READ_FILE_MAX_BYTES);
+
+            let metadata = std::fs::metadata(&path)
+                .map_err(|err| mlua::Error::runtime(format!("read_file(\"{path}\"): {err}")))?;
+            if metadata.len() > max_bytes {
+                return Err(mlua::Error::runtime(format!(
+                    "read_file(\"{path}\"): file is {} byte(s), exceeding max_bytes ({max_bytes}); pass a larger \
+                     opts.max_bytes if you need all of it",
+                    metadata.len()
+                )));
+            }
+
+            let content = std::fs::read_to_string(&path)
+                .map_err(|err| mlua::Error::runtime(format!("read_file(\"{path}\"): {err}")))?;
+            Ok(content)
+        })?,
+    )?;
+    Ok(())
+}
+
+/// Evaluates `globals.lua`: it must `return` a table, which is parsed the
+/// same way a `template()` vars table is (plain values, `secret_ref()`
+/// handles, `env()` handles, nested tables, and lists all work).
+pub fn evaluate_globals(path: &Path) -> Result<BTreeMap<String, TemplateValue>> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+
+    let lua = Lua::new();
+    register_secret_ref(&lua)?;
+    register_env(&lua)?;
+    register_xdg(&lua)?;
+    register_path(&lua)?;
+    register_facts(&lua)?;
+
+    let value: Value = lua
+        .load(&source)
+        .set_name(path.to_string_lossy())
+        .eval()
+        .with_context(|| format!("failed to evaluate `{}`", path.display()))?;
+
+    let Value::Table(table) = value else {
+        bail!(
+            "`{}` must `return` a table of shared variables",
+            path.display()
+        );
+    };
+
+    table_to_vars(&table).with_context(|| format!("failed to parse `{}`", path.display()))
+}
+
+/// Registers the `globals` table exposed to every manifest, populated from
+/// `globals.lua` (empty if there isn't one).
+fn register_globals(lua: &Lua, globals: &BTreeMap<String, TemplateValue>) -> Result<()> {
+    let table = lua.create_table()?;
+    for (key, value) in globals {
+        table.set(key.clone(), template_value_to_lua(lua, value)?)?;
+    }
+    lua.globals().set("globals", table)?;
+    Ok(())
+}
+
+/// Converts a [`TemplateValue`] back into a Lua value, e.g. to expose
+/// `globals.lua`'s parsed output to another manifest's own Lua VM. Round-trips
+/// through the same marker tables `secret_ref()`/`env()` produce, so a
+/// `secret_ref()` defined in `globals.lua` still resolves lazily wherever
+/// it's used.
+fn template_value_to_lua(lua: &Lua, value: &TemplateValue) -> mlua::Result<Value> {
+    match value {
+        TemplateValue::Str(s) | TemplateValue::SensitiveStr(s) => {
+            Ok(Value::String(lua.create_string(s)?))
+        }
+        TemplateValue::Secret(secret_ref) => {
+            let table = lua.create_table()?;
+            table.set(SECRET_REF_MARKER, secret_ref.uri.clone())?;
+            Ok(Value::Table(table))
+        }
+        TemplateValue::EnvVar(env_var) => {
+            let table = lua.create_table()?;
+            table.set(ENV_VAR_NAME_MARKER, env_var.name.clone())?;
+            table.set(ENV_VAR_SENSITIVE_MARKER, env_var.sensitive)?;
+            Ok(Value::Table(table))
+        }
+        TemplateValue::List(items) => {
+            let table = lua.create_table()?;
+            for (index, item) in items.iter().enumerate() {
+                table.set(index + 1, template_value_to_lua(lua, item)?)?;
+            }
+            Ok(Value::Table(table))
+        }
+        TemplateValue::Table(fields) => {
+            let table = lua.create_table()?;
+            for (key, value) in fields {
+                table.set(key.clone(), template_value_to_lua(lua, value)?)?;
+            }
+            Ok(Value::Table(table))
+        }
+    }
+}
+
+/// Registers `vars_file(path)` for loading a flat table of template
+/// variables from a JSON, TOML, or YAML file, e.g.
+/// `template(src, dest, vars_file("vars/work.toml"))`. `path` is resolved
+/// relative to the manifest's own directory, not keron's working directory,
+/// and is rejected if it escapes that directory (no `../` past the
+/// manifest), so a manifest can't be tricked into reading arbitrary files
+/// on disk.
+fn register_vars_file(lua: &Lua, manifest_path: &Path) -> Result<()> {
+    let manifest_dir = manifest_path
+        .parent()
+        .with_context(|| {
+            format!(
+                "manifest path `{}` has no parent directory",
+                manifest_path.display()
+            )
+        })?
+        .to_path_buf();
+
+    let vars_file = lua.create_function(move |lua, relative: String| {
+        let vars = load_vars_file(&manifest_dir, &relative).map_err(mlua::Error::runtime)?;
+        let table = lua.create_table()?;
+        for (key, value) in vars {
+            table.set(key, value)?;
+        }
+        Ok(table)
+    })?;
+    lua.globals().set("vars_file", vars_file)?;
+    Ok(())
+}
+
+/// Registers `import(path)` for sharing resource-factory functions between
+/// manifests, e.g. a `lib/helpers.lua` that returns `{ dev_tool = function(name) ... end }`.
+/// `path` is resolved relative to the importing manifest the same way as
+/// `vars_file()`. The imported script runs in the *same* Lua state, so it
+/// sees every global keron registers (`link()`, `template()`, `cmd()`, ...)
+/// and a `dev_tool()` helper it defines can call them directly to register
+/// resources, just as if the manifest had declared them itself. Importing
+/// the same path twice returns the first result without re-running the
+/// script, mirroring how Lua's own `require` caches modules.
+fn register_import(lua: &Lua, manifest_path: &Path) -> Result<()> {
+    let manifest_dir = manifest_path
+        .parent()
+        .with_context(|| {
+            format!(
+                "manifest path `{}` has no parent directory",
+                manifest_path.display()
+            )
+        })?
+        .to_path_buf();
+    let cache: Rc<RefCell<HashMap<PathBuf, Value>>> = Rc::new(RefCell::new(HashMap::new()));
+
+    let import = lua.create_function(move |lua, relative: String| {
+        let resolved =
+            resolve_manifest_relative(&manifest_dir, &relative, &format!("import(\"{relative}\")"))
+                .map_err(mlua::Error::runtime)?;
+
+        if let Some(cached) = cache.borrow().get(&resolved) {
+            return Ok(cached.clone());
+        }
+
+        let source = std::fs::read_to_string(&resolved).map_err(|err| {
+            mlua::Error::runtime(format!("failed to read `{}`: {err}", resolved.display()))
+        })?;
+        let value: Value = lua
+            .load(&source)
+            .set_name(resolved.to_string_lossy())
+            .eval()?;
+
+        cache.borrow_mut().insert(resolved, value.clone());
+        Ok(value)
+    })?;
+    lua.globals().set("import", import)?;
+    Ok(())
+}
+
+/// Registers `render(source, vars?)` for rendering a template file to a
+/// string immediately, at manifest evaluation time, instead of declaring a
+/// `template()` resource — so a manifest can compute a derived value (e.g.
+/// render a snippet and feed it into another resource's `vars`) without a
+/// separate on-disk destination. `source` is resolved relative to the
+/// manifest's own directory the same way as `vars_file()`, and `vars` takes
+/// the same shape as `template()`'s `vars` (plain values, `secret_ref()`/
+/// `env()` handles, nested tables and lists). Secrets are always resolved
+/// here, never redacted, since the result is a plain string the manifest
+/// script itself can already see; `secret_cache` is shared across every
+/// `render()` call in the same manifest so a secret used more than once is
+/// only fetched once.
+fn register_render(lua: &Lua, manifest_path: &Path) -> Result<()> {
+    let manifest_dir = manifest_path
+        .parent()
+        .with_context(|| {
+            format!(
+                "manifest path `{}` has no parent directory",
+                manifest_path.display()
+            )
+        })?
+        .to_path_buf();
+    let secret_cache = secrets::Cache::default();
+
+    let render = lua.create_function(move |_lua, (source, vars): (String, Option<Table>)| {
+        let resolved =
+            resolve_manifest_relative(&manifest_dir, &source, &format!("render(\"{source}\")"))
+                .map_err(mlua::Error::runtime)?;
+        let content = std::fs::read_to_string(&resolved).map_err(|err| {
+            mlua::Error::runtime(format!("failed to read `{}`: {err}", resolved.display()))
+        })?;
+        let vars = match vars {
+            Some(table) => table_to_vars(&table)?,
+            None => BTreeMap::new(),
+        };
+        crate::render::render(&content, &vars, true, &secret_cache)
+            .map_err(|err| mlua::Error::runtime(err.to_string()))
+    })?;
+    lua.globals().set("render", render)?;
+    Ok(())
+}
+
+/// Resolves `relative` against `manifest_dir`, rejecting an absolute path or
+/// one that escapes `manifest_dir` (no `../` past it), so a manifest can't
+/// be tricked into reading arbitrary files on disk. `context` names the
+/// calling function, for the error message (e.g. `vars_file("...")`).
+fn resolve_manifest_relative(
+    manifest_dir: &Path,
+    relative: &str,
+    context: &str,
+) -> Result<PathBuf> {
+    if Path::new(relative).is_absolute() {
+        bail!("{context} must be a path relative to the manifest, not absolute");
+    }
+
+    let joined = manifest_dir.join(relative);
+    let resolved = joined
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {context}"))?;
+    let manifest_dir = manifest_dir.canonicalize().with_context(|| {
+        format!(
+            "failed to resolve manifest directory `{}`",
+            manifest_dir.display()
+        )
+    })?;
+    if !resolved.starts_with(&manifest_dir) {
+        bail!("{context} escapes the manifest's directory");
+    }
+    Ok(resolved)
+}
+
+/// Resolves `relative` against `manifest_dir` and parses it into a flat
+/// `name -> value` table, dispatching on file extension (`.json`, `.toml`,
+/// `.yaml`/`.yml`). Values must be scalars: nested objects or arrays aren't
+/// supported until the template engine itself understands nested variables.
+fn load_vars_file(manifest_dir: &Path, relative: &str) -> Result<BTreeMap<String, String>> {
+    let resolved = resolve_manifest_relative(
+        manifest_dir,
+        relative,
+        &format!("vars_file(\"{relative}\")"),
+    )?;
+
+    let contents = std::fs::read_to_string(&resolved)
+        .with_context(|| format!("failed to read `{}`", resolved.display()))?;
+
+    let scalars: BTreeMap<String, ScalarValue> = match resolved.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&contents).with_context(|| format!("failed to parse `{}` as JSON", resolved.display()))?,
+        Some("toml") => toml::from_str(&contents).with_context(|| format!("failed to parse `{}` as TOML", resolved.display()))?,
+        Some("yaml") | Some("yml") => {
+            serde_yaml::from_str(&contents).with_context(|| format!("failed to parse `{}` as YAML", resolved.display()))?
+        }
+        Some(other) => bail!("vars_file(\"{relative}\") has unsupported extension `.{other}` (expected json, toml, yaml, or yml)"),
+        None => bail!("vars_file(\"{relative}\") has no file extension (expected json, toml, yaml, or yml)"),
+    };
+
+    Ok(scalars
+        .into_iter()
+        .map(|(key, value)| (key, value.to_string()))
+        .collect())
+}
+
+/// A leaf value in a `vars_file()`, rendered to its string form. Rejects
+/// nested tables/arrays with a deserialize error, since a flat `name ->
+/// value` map is all `template()` variables support today.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum ScalarValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl std::fmt::Display for ScalarValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScalarValue::String(s) => f.write_str(s),
+            ScalarValue::Int(i) => write!(f, "{i}"),
+            ScalarValue::Float(x) => write!(f, "{x}"),
+            ScalarValue::Bool(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+/// Parses a Go-style duration like `"120s"`, `"5m"`, or `"2h"`, as used by
+/// `cmd()`'s `timeout` option.
+fn parse_duration(input: &str) -> Result<Duration> {
+    let (number, unit) = input.split_at(
+        input
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(input.len()),
+    );
+    if number.is_empty() {
+        bail!("duration `{input}` must start with a number, e.g. `120s`");
+    }
+    let value: u64 = number
+        .parse()
+        .with_context(|| format!("invalid duration `{input}`"))?;
+
+    let seconds = match unit {
+        "s" => value,
+        "m" => value * 60,
+        "h" => value * 3600,
+        other => bail!("duration `{input}` has unknown unit `{other}` (expected `s`, `m`, or `h`)"),
+    };
+    Ok(Duration::from_secs(seconds))
+}
+
+fn table_to_vars(table: &Table) -> mlua::Result<BTreeMap<String, TemplateValue>> {
+    let mut vars = BTreeMap::new();
+    for pair in table.pairs::<String, Value>() {
+        let (key, value) = pair?;
+        vars.insert(key, value_to_template_value(&value)?);
+    }
+    Ok(vars)
+}
+
+fn value_to_template_value(value: &Value) -> mlua::Result<TemplateValue> {
+    match value {
+        Value::String(s) => Ok(TemplateValue::Str(s.to_str()?.to_string())),
+        Value::Integer(i) => Ok(TemplateValue::Str(i.to_string())),
+        Value::Number(n) => Ok(TemplateValue::Str(n.to_string())),
+        Value::Boolean(b) => Ok(TemplateValue::Str(b.to_string())),
+        Value::Table(table) => {
+            let uri: Option<String> = table.get(SECRET_REF_MARKER)?;
+            if let Some(uri) = uri {
+                return Ok(TemplateValue::Secret(SecretRef { uri }));
+            }
+
+            let name: Option<String> = table.get(ENV_VAR_NAME_MARKER)?;
+            if let Some(name) = name {
+                let sensitive: bool = table.get(ENV_VAR_SENSITIVE_MARKER)?;
+                return Ok(TemplateValue::EnvVar(EnvVarValue { name, sensitive }));
+            }
+
+            if table_is_sequence(table)? {
+                let mut items = Vec::new();
+                for item in table.sequence_values::<Value>() {
+                    items.push(value_to_template_value(&item?)?);
+                }
+                Ok(TemplateValue::List(items))
+            } else {
+                let mut fields = BTreeMap::new();
+                for pair in table.pairs::<String, Value>() {
+                    let (key, value) = pair?;
+                    fields.insert(key, value_to_template_value(&value)?);
+                }
+                Ok(TemplateValue::Table(fields))
+            }
+        }
+        other => Err(mlua::Error::runtime(format!(
+            "template variables must be strings, numbers, booleans, secret_ref()/env() handles, lists, or tables, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+/// Whether `table` is a Lua array: a contiguous `1..=n` integer-keyed
+/// sequence with no other keys. An empty table is treated as an (empty) map
+/// rather than a list, since Lua can't tell the two apart.
+fn table_is_sequence(table: &Table) -> mlua::Result<bool> {
+    let len = table.raw_len();
+    if len == 0 {
+        return Ok(false);
+    }
+
+    let mut count = 0usize;
+    for pair in table.pairs::<Value, Value>() {
+        let (key, _) = pair?;
+        match key {
+            Value::Integer(i) if i >= 1 && (i as usize) <= len => count += 1,
+            _ => return Ok(false),
+        }
+    }
+    Ok(count == len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_cycle;
+
+    /// A resource that's stuck because it depends on a cycle, but that
+    /// nothing else depends on, must not be picked as the cycle walk's
+    /// starting point — regression test for the panic this reproduced:
+    /// `c` (`after = {"a"}`) declared before the real `a <-> b` cycle used
+    /// to make `find_cycle` start from `c`, whose empty `dependents` entry
+    /// then panicked instead of reporting the cycle.
+    #[test]
+    fn ignores_a_stuck_node_with_no_way_back_into_the_cycle() {
+        // c -> a <-> b (a and b's `dependents` list each other; c's is empty)
+        let dependents = vec![
+            vec![2, 1], // 0 (a): b (1) and c (2) both depend on a
+            vec![0],    // 1 (b): a depends on b
+            vec![],     // 2 (c): nothing depends on c
+        ];
+        let stuck = vec![2, 0, 1];
+
+        let cycle = find_cycle(&stuck, &dependents).expect("a and b form a real cycle");
+        assert!(cycle.contains(&0) && cycle.contains(&1));
+        assert!(
+            !cycle.contains(&2),
+            "c isn't part of the cycle, only dependent on it"
+        );
+    }
+
+    #[test]
+    fn finds_a_direct_two_node_cycle() {
+        let dependents = vec![vec![1], vec![0]];
+        let stuck = vec![0, 1];
+
+        let cycle = find_cycle(&stuck, &dependents).expect("0 and 1 form a cycle");
+        assert!(cycle.contains(&0) && cycle.contains(&1));
+    }
+
+    #[test]
+    fn returns_none_when_stuck_has_no_cycle() {
+        // Not a realistic input for `order_by_dependencies` (a real
+        // topological-sort failure always contains one), but `find_cycle`
+        // should still degrade gracefully instead of assuming one exists.
+        let dependents = vec![vec![1], vec![]];
+        let stuck = vec![0, 1];
+
+        assert!(find_cycle(&stuck, &dependents).is_none());
+    }
+
+    use super::{order_by_dependencies, ResourceMeta};
+    use crate::resource::{CmdResource, DirResource, Resource};
+    use std::time::Duration;
+
+    fn dir(path: &str) -> Resource {
+        Resource::Dir(DirResource {
+            path: path.into(),
+            mode: None,
+            mkdirs: true,
+            elevate: false,
+        })
+    }
+
+    fn cmd(command: &str) -> Resource {
+        Resource::Cmd(CmdResource {
+            command: command.to_string(),
+            creates: None,
+            creates_hash: None,
+            unless: None,
+            only_if: None,
+            env: Default::default(),
+            cwd: None,
+            retries: 0,
+            timeout: Duration::from_secs(0),
+        })
+    }
+
+    fn meta(name: Option<&str>, after: &[&str], notify: &[&str]) -> ResourceMeta {
+        ResourceMeta {
+            name: name.map(str::to_string),
+            after: after.iter().map(|s| s.to_string()).collect(),
+            notify: notify.iter().map(|s| s.to_string()).collect(),
+            line: None,
+        }
+    }
+
+    #[test]
+    fn preserves_declaration_order_with_no_constraints() {
+        let resources = vec![dir("a"), dir("b"), dir("c")];
+        let meta = vec![
+            meta(None, &[], &[]),
+            meta(None, &[], &[]),
+            meta(None, &[], &[]),
+        ];
+
+        let ordered = order_by_dependencies(resources, meta).unwrap();
+        let paths: Vec<_> = ordered
+            .into_iter()
+            .map(|r| match r.resource {
+                Resource::Dir(d) => d.path.display().to_string(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(paths, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn after_moves_a_resource_behind_its_dependency() {
+        // declared first-to-last: b (after a), a
+        let resources = vec![dir("b"), dir("a")];
+        let meta = vec![meta(Some("b"), &["a"], &[]), meta(Some("a"), &[], &[])];
+
+        let ordered = order_by_dependencies(resources, meta).unwrap();
+        let names: Vec<_> = ordered.into_iter().map(|r| r.name.unwrap()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn notify_orders_the_target_cmd_after_the_notifier() {
+        let resources = vec![cmd("echo hi"), dir("a")];
+        let meta = vec![
+            meta(Some("reload"), &[], &[]),
+            meta(Some("a"), &[], &["reload"]),
+        ];
+
+        let ordered = order_by_dependencies(resources, meta).unwrap();
+        let names: Vec<_> = ordered.into_iter().map(|r| r.name.unwrap()).collect();
+        assert_eq!(names, vec!["a", "reload"]);
+    }
+
+    #[test]
+    fn unknown_after_target_errors() {
+        let resources = vec![dir("a")];
+        let meta = vec![meta(Some("a"), &["missing"], &[])];
+
+        let err = order_by_dependencies(resources, meta).unwrap_err();
+        assert!(err.to_string().contains("unknown resource name `missing`"));
+    }
+
+    #[test]
+    fn notify_target_must_be_a_cmd_resource() {
+        let resources = vec![dir("notifier"), dir("not-a-cmd")];
+        let meta = vec![
+            meta(Some("notifier"), &[], &["not-a-cmd"]),
+            meta(Some("not-a-cmd"), &[], &[]),
+        ];
+
+        let err = order_by_dependencies(resources, meta).unwrap_err();
+        assert!(err.to_string().contains("must be a `cmd()` resource"));
+    }
+
+    #[test]
+    fn duplicate_name_errors() {
+        let resources = vec![dir("a"), dir("b")];
+        let meta = vec![meta(Some("dup"), &[], &[]), meta(Some("dup"), &[], &[])];
+
+        let err = order_by_dependencies(resources, meta).unwrap_err();
+        assert!(err.to_string().contains("duplicate resource name"));
+    }
+
+    #[test]
+    fn a_real_cycle_reports_all_involved_names() {
+        let resources = vec![dir("a"), dir("b")];
+        let meta = vec![meta(Some("a"), &["b"], &[]), meta(Some("b"), &["a"], &[])];
+
+        let err = order_by_dependencies(resources, meta).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("dependency cycle detected"));
+        assert!(message.contains('a') && message.contains('b'));
+    }
+}
@@ -0,0 +1,420 @@
+pub mod docs;
+mod lua;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::hooks::HookCmd;
+use crate::providers::CustomProvider;
+use crate::resource::{Resource, TemplateValue};
+
+/// Reserved manifest filename, read once per `discover()` root and exposed
+/// to every other manifest as the `globals` table, so values like an email
+/// address or machine name only need to be defined in one place. Not itself
+/// treated as a manifest: it must `return` a table instead of declaring
+/// resources.
+const GLOBALS_FILE_NAME: &str = "globals.lua";
+
+/// Reserved config filename, read once per `discover()` root. Only
+/// `manifests` is understood so far.
+const CONFIG_FILE_NAME: &str = "keron.toml";
+
+/// Reserved ignore-patterns filename, read once per `discover()` root, in
+/// the same spirit as `.gitignore`: one pattern per line, `#` comments and
+/// blank lines skipped. A pattern without a `/` matches by name anywhere
+/// under `root` (e.g. `nvim` skips any directory or file named `nvim`); a
+/// pattern with a `/` is matched against the path relative to `root`. A
+/// trailing `/` restricts a pattern to directories. `*` matches any run of
+/// characters. Ignored only when `keron.toml` doesn't list explicit
+/// `manifests` (an explicit list is unambiguous, so there's nothing to
+/// ignore).
+const IGNORE_FILE_NAME: &str = ".keronignore";
+
+/// A single evaluated manifest file and the resources it declared.
+#[derive(Debug)]
+pub struct Manifest {
+    pub path: PathBuf,
+    pub resources: Vec<NamedResource>,
+    pub providers: Vec<CustomProvider>,
+    /// `pre_cmd()`/`post_cmd()` hooks declared in this manifest, run once
+    /// around its resources rather than around each one.
+    pub pre_cmds: Vec<HookCmd>,
+    pub post_cmds: Vec<HookCmd>,
+    /// Other manifests (by resolved path) named via `depends_on(...)`: every
+    /// operation from each of these must finish, without failing, before
+    /// this manifest's own resources start applying. Resolved from
+    /// `depends_on_names` by [`resolve_manifest_dependencies`], once every
+    /// manifest under a discovery root is known.
+    pub depends_on: Vec<PathBuf>,
+    /// Raw filenames passed to `depends_on(...)`, not yet resolved against
+    /// the other manifests under this discovery root.
+    depends_on_names: Vec<String>,
+    /// Unknown-option warnings from evaluating this manifest (e.g. a
+    /// typo'd `forse = true` on `link()`), already printed to stderr by
+    /// `evaluate()` but kept here too so `keron plan` can also show them
+    /// alongside the plan they affect.
+    pub warnings: Vec<String>,
+}
+
+/// A resource together with the identifier given via `name = "..."` on its
+/// constructor, if any. The name is opaque to the resource itself (it's
+/// manifest-authoring metadata, not part of what gets applied) but is
+/// threaded through planning so reports and `--only name:<name>` filters can
+/// refer to a resource without spelling out its whole description.
+#[derive(Debug)]
+pub struct NamedResource {
+    pub resource: Resource,
+    pub name: Option<String>,
+    /// The names given via `after = {...}` on this resource's constructor,
+    /// carried through past manifest evaluation (where they only decided
+    /// declaration order) so `apply()` can also skip a resource whose named
+    /// dependency failed or was itself skipped, even under `--keep-going`.
+    pub after: Vec<String>,
+    /// The names given via `notify = {...}` on this resource's constructor:
+    /// `cmd()` resources that should only run when this resource actually
+    /// changes, instead of on every apply. See `plan::apply_notify_overrides`.
+    pub notify: Vec<String>,
+    /// The 1-based line, in the manifest that declared this resource, its
+    /// constructor was called from, if mlua's debug info resolved it.
+    pub line: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+struct DiscoveryConfig {
+    /// Explicit manifest paths, relative to `root`. When set, `discover()`
+    /// uses exactly this list instead of walking the tree for `*.lua`
+    /// files, so a repo that keeps unrelated Lua alongside its manifests
+    /// (an nvim config, say) doesn't need `.keronignore` at all.
+    #[serde(default)]
+    manifests: Vec<String>,
+}
+
+/// Finds and evaluates every `*.lua` manifest under `root`. As a shortcut
+/// for quick experiments and per-tool repos, `root` may also point directly
+/// at a single manifest file instead of a directory.
+///
+/// If `root` is a directory:
+/// - a `globals.lua` in it is evaluated first and exposed to every other
+///   manifest as `globals` (see [`GLOBALS_FILE_NAME`]);
+/// - a `keron.toml` with a `manifests` list overrides discovery with that
+///   explicit list (see [`CONFIG_FILE_NAME`]);
+/// - otherwise, a `.keronignore` filters the `*.lua` tree walk (see
+///   [`IGNORE_FILE_NAME`]).
+pub fn discover(root: &Path) -> Result<Vec<Manifest>> {
+    discover_filtered(root, None)
+}
+
+/// Like [`discover`], but when `only_manifest` is given, evaluates just the
+/// manifest file whose name (e.g. `workstation.lua`) matches it instead of
+/// every manifest under `root` — the rest are never even parsed, so a large
+/// tree with an unrelated manifest that's slow to evaluate (a `cmd()` that
+/// shells out, say) doesn't pay for it on a plan restricted elsewhere.
+pub fn discover_filtered(root: &Path, only_manifest: Option<&str>) -> Result<Vec<Manifest>> {
+    if root.is_file() {
+        if root.extension().is_none_or(|ext| ext != "lua") {
+            bail!("`{}` is not a `.lua` manifest file", root.display());
+        }
+        return resolve_manifest_dependencies(vec![evaluate(root, &BTreeMap::new())?]);
+    }
+
+    let globals = load_globals(root)?;
+    let config = load_discovery_config(root)?;
+
+    let mut paths = if config.manifests.is_empty() {
+        let ignore = load_ignore_patterns(root)?;
+        let mut paths = Vec::new();
+        collect_lua_files(root, root, &ignore, &mut paths)?;
+        paths
+    } else {
+        config
+            .manifests
+            .iter()
+            .map(|relative| root.join(relative))
+            .collect()
+    };
+
+    if let Some(only_manifest) = only_manifest {
+        paths.retain(|path| path.file_name().is_some_and(|name| name == only_manifest));
+        if paths.is_empty() {
+            bail!(
+                "no manifest named `{only_manifest}` found under `{}`",
+                root.display()
+            );
+        }
+    }
+    paths.sort();
+
+    let manifests = paths
+        .into_iter()
+        .map(|path| evaluate(&path, &globals))
+        .collect::<Result<Vec<_>>>()?;
+    resolve_manifest_dependencies(manifests)
+}
+
+fn load_globals(root: &Path) -> Result<BTreeMap<String, TemplateValue>> {
+    let path = root.join(GLOBALS_FILE_NAME);
+    if !path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+    lua::evaluate_globals(&path)
+}
+
+fn load_discovery_config(root: &Path) -> Result<DiscoveryConfig> {
+    let path = root.join(CONFIG_FILE_NAME);
+    if !path.is_file() {
+        return Ok(DiscoveryConfig::default());
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    toml::from_str(&contents).with_context(|| format!("failed to parse `{}`", path.display()))
+}
+
+fn load_ignore_patterns(root: &Path) -> Result<Vec<String>> {
+    let path = root.join(IGNORE_FILE_NAME);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+fn collect_lua_files(
+    root: &Path,
+    dir: &Path,
+    ignore: &[String],
+    out: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("failed to read `{}`", dir.display()))?
+    {
+        let entry = entry?;
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        let is_dir = path.is_dir();
+
+        if is_ignored(ignore, relative, is_dir) {
+            continue;
+        }
+
+        if is_dir {
+            collect_lua_files(root, &path, ignore, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "lua")
+            && path.file_name().and_then(|name| name.to_str()) != Some(GLOBALS_FILE_NAME)
+        {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_ignored(patterns: &[String], relative: &Path, is_dir: bool) -> bool {
+    let relative = relative.to_string_lossy();
+    let basename = Path::new(relative.as_ref())
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(relative.as_ref());
+
+    patterns.iter().any(|pattern| {
+        let (pattern, dir_only) = match pattern.strip_suffix('/') {
+            Some(pattern) => (pattern, true),
+            None => (pattern.as_str(), false),
+        };
+        if dir_only && !is_dir {
+            return false;
+        }
+        if pattern.contains('/') {
+            glob_match(pattern, &relative)
+        } else {
+            glob_match(pattern, basename)
+        }
+    })
+}
+
+/// Matches `text` against `pattern`, where `*` matches any run of characters
+/// (including none). No other glob syntax (`?`, `[...]`, `**`) is supported.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(pattern: &[u8], text: &[u8]) -> bool {
+        match (pattern.first(), text.first()) {
+            (None, None) => true,
+            (Some(b'*'), _) => {
+                matches(&pattern[1..], text) || (!text.is_empty() && matches(pattern, &text[1..]))
+            }
+            (Some(p), Some(t)) if p == t => matches(&pattern[1..], &text[1..]),
+            _ => false,
+        }
+    }
+    matches(pattern.as_bytes(), text.as_bytes())
+}
+
+fn evaluate(path: &Path, globals: &BTreeMap<String, TemplateValue>) -> Result<Manifest> {
+    let evaluated = lua::evaluate_manifest(path, globals)?;
+    for warning in &evaluated.warnings {
+        eprintln!("warning: {}: {warning}", path.display());
+    }
+    Ok(Manifest {
+        path: path.to_path_buf(),
+        resources: evaluated.resources,
+        providers: evaluated.providers,
+        pre_cmds: evaluated.pre_cmds,
+        post_cmds: evaluated.post_cmds,
+        depends_on: Vec::new(),
+        depends_on_names: evaluated.depends_on,
+        warnings: evaluated.warnings,
+    })
+}
+
+/// Resolves every manifest's `depends_on_names` against `manifests`' own
+/// paths (matched by filename, so `depends_on("base.lua")` finds whichever
+/// discovered manifest is named `base.lua`) and reorders `manifests` so a
+/// dependency always precedes its dependents — the same stable topological
+/// sort `order_by_dependencies` uses for resources within one manifest,
+/// applied at manifest granularity so `apply()`'s per-manifest chunks come
+/// out in an order it can just run through in sequence.
+fn resolve_manifest_dependencies(mut manifests: Vec<Manifest>) -> Result<Vec<Manifest>> {
+    let mut path_by_name: HashMap<String, usize> = HashMap::new();
+    for (index, manifest) in manifests.iter().enumerate() {
+        let Some(name) = manifest.path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if path_by_name.insert(name.to_string(), index).is_some() {
+            bail!(
+                "multiple manifests are named `{name}`; `depends_on(\"{name}\")` can't tell them apart"
+            );
+        }
+    }
+
+    let len = manifests.len();
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); len];
+    let mut remaining_deps = vec![0usize; len];
+    let mut resolved_depends_on: Vec<Vec<usize>> = vec![Vec::new(); len];
+    for (index, manifest) in manifests.iter().enumerate() {
+        for dependency_name in &manifest.depends_on_names {
+            let &dependency_index = path_by_name.get(dependency_name.as_str()).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "`depends_on(\"{dependency_name}\")` in `{}` references a manifest that wasn't found",
+                    manifest.path.display()
+                )
+            })?;
+            dependents[dependency_index].push(index);
+            remaining_deps[index] += 1;
+            resolved_depends_on[index].push(dependency_index);
+        }
+    }
+
+    let mut ready: std::collections::BinaryHeap<std::cmp::Reverse<usize>> = (0..len)
+        .filter(|&index| remaining_deps[index] == 0)
+        .map(std::cmp::Reverse)
+        .collect();
+
+    let mut order = Vec::with_capacity(len);
+    while let Some(std::cmp::Reverse(index)) = ready.pop() {
+        order.push(index);
+        for &dependent in &dependents[index] {
+            remaining_deps[dependent] -= 1;
+            if remaining_deps[dependent] == 0 {
+                ready.push(std::cmp::Reverse(dependent));
+            }
+        }
+    }
+
+    if order.len() != len {
+        let cycle = (0..len)
+            .filter(|&index| remaining_deps[index] > 0)
+            .map(|index| manifests[index].path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        bail!("dependency cycle detected among `depends_on(...)` manifests: {cycle}");
+    }
+
+    let paths: Vec<PathBuf> = manifests.iter().map(|m| m.path.clone()).collect();
+    for (index, manifest) in manifests.iter_mut().enumerate() {
+        manifest.depends_on = resolved_depends_on[index]
+            .iter()
+            .map(|&dependency_index| paths[dependency_index].clone())
+            .collect();
+        manifest.depends_on_names.clear();
+    }
+
+    let mut manifests: Vec<Option<Manifest>> = manifests.into_iter().map(Some).collect();
+    Ok(order
+        .into_iter()
+        .map(|index| manifests[index].take().unwrap())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_manifest_dependencies, Manifest};
+
+    fn manifest(name: &str, depends_on: &[&str]) -> Manifest {
+        Manifest {
+            path: name.into(),
+            resources: Vec::new(),
+            providers: Vec::new(),
+            pre_cmds: Vec::new(),
+            post_cmds: Vec::new(),
+            depends_on: Vec::new(),
+            depends_on_names: depends_on.iter().map(|s| s.to_string()).collect(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn names(manifests: &[Manifest]) -> Vec<String> {
+        manifests
+            .iter()
+            .map(|m| m.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn preserves_declaration_order_with_no_dependencies() {
+        let manifests = vec![manifest("a.lua", &[]), manifest("b.lua", &[])];
+        let ordered = resolve_manifest_dependencies(manifests).unwrap();
+        assert_eq!(names(&ordered), vec!["a.lua", "b.lua"]);
+    }
+
+    #[test]
+    fn depends_on_moves_a_manifest_behind_its_dependency() {
+        // declared first-to-last: b (depends on a), a
+        let manifests = vec![manifest("b.lua", &["a.lua"]), manifest("a.lua", &[])];
+        let ordered = resolve_manifest_dependencies(manifests).unwrap();
+        assert_eq!(names(&ordered), vec!["a.lua", "b.lua"]);
+        assert_eq!(
+            ordered[1].depends_on,
+            vec![std::path::PathBuf::from("a.lua")]
+        );
+    }
+
+    #[test]
+    fn unknown_depends_on_target_errors() {
+        let manifests = vec![manifest("a.lua", &["missing.lua"])];
+        let err = resolve_manifest_dependencies(manifests).unwrap_err();
+        assert!(err.to_string().contains("wasn't found"));
+    }
+
+    #[test]
+    fn cycle_errors() {
+        let manifests = vec![manifest("a.lua", &["b.lua"]), manifest("b.lua", &["a.lua"])];
+        let err = resolve_manifest_dependencies(manifests).unwrap_err();
+        assert!(err.to_string().contains("dependency cycle"));
+    }
+
+    #[test]
+    fn duplicate_manifest_name_errors() {
+        let manifests = vec![
+            manifest("dir1/shared.lua", &[]),
+            manifest("dir2/shared.lua", &[]),
+        ];
+        let err = resolve_manifest_dependencies(manifests).unwrap_err();
+        assert!(err.to_string().contains("multiple manifests are named"));
+    }
+}
@@ -0,0 +1,67 @@
+//! Opt-in post-apply notifications, for unattended applies (a cron job, a
+//! systemd timer) where nobody is watching stdout. Best-effort: a
+//! notification failure is logged and swallowed rather than turning a
+//! successful apply into a failed one.
+
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::subprocess::{self, Limits};
+
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shows a desktop notification via `notify-send`, summarizing what an
+/// apply did (e.g. `"3 applied, 1 failed"`). Only Linux/`notify-send` is
+/// supported so far — no other desktop notifier is in reach of a headless
+/// `cmd()`-shelling tool like keron without pulling in a platform-specific
+/// crate for each one.
+pub fn desktop(summary: &str) {
+    let mut command = Command::new("notify-send");
+    command.arg("keron apply").arg(summary);
+
+    let limits = Limits {
+        timeout: NOTIFY_TIMEOUT,
+        ..Limits::default()
+    };
+    if let Err(err) = subprocess::run_captured(&mut command, &limits) {
+        tracing::warn!(error = %err, "failed to send desktop notification");
+    }
+}
+
+/// POSTs `report` as JSON to `url` via `curl`, the same way keron shells
+/// out to every other external tool it doesn't want to vendor a client
+/// library for.
+pub fn webhook(url: &str, report: &serde_json::Value) -> Result<()> {
+    let body = serde_json::to_string(report)?;
+
+    let mut command = Command::new("curl");
+    command
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--fail")
+        .arg("--request")
+        .arg("POST")
+        .arg("--header")
+        .arg("Content-Type: application/json")
+        .arg("--data")
+        .arg(body)
+        .arg(url);
+
+    let limits = Limits {
+        timeout: NOTIFY_TIMEOUT,
+        ..Limits::default()
+    };
+    match subprocess::run_captured(&mut command, &limits) {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => {
+            tracing::warn!(url, stderr = %String::from_utf8_lossy(&output.stderr).trim(), "webhook notification failed");
+            Ok(())
+        }
+        Err(err) => {
+            tracing::warn!(url, error = %err, "failed to run webhook notification");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Resolves a `link()`/`template()` resource's `owner`/`group` opts (user/
+//! group names) to uid/gid via `id`/`getent`, for comparing against and
+//! changing a destination's actual ownership. Shells out rather than
+//! linking against a passwd-parsing crate, the same tradeoff `sudo::
+//! home_of` already makes for the invoking user's home directory.
+
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::elevate;
+use crate::subprocess::{self, Limits};
+
+fn resolve_uid(name: &str) -> Result<u32> {
+    let mut command = Command::new("id");
+    command.arg("-u").arg(name);
+    let output = subprocess::run_captured(&mut command, &Limits::default())
+        .with_context(|| format!("failed to run `id -u {name}`"))?;
+    if !output.status.success() {
+        bail!(
+            "no such user `{name}`: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .with_context(|| format!("`id -u {name}` printed something unexpected"))
+}
+
+fn resolve_gid(name: &str) -> Result<u32> {
+    let mut command = Command::new("getent");
+    command.arg("group").arg(name);
+    let output = subprocess::run_captured(&mut command, &Limits::default())
+        .with_context(|| format!("failed to run `getent group {name}`"))?;
+    if !output.status.success() {
+        bail!(
+            "no such group `{name}`: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let line = String::from_utf8_lossy(&output.stdout);
+    line.trim()
+        .split(':')
+        .nth(2)
+        .with_context(|| format!("unexpected `getent group {name}` output"))?
+        .parse()
+        .with_context(|| format!("`getent group {name}` printed something unexpected"))
+}
+
+/// Whether `path`'s current owning user/group already match `owner`/`group`
+/// (a `None` side is always considered matching — nothing was asked of it).
+pub fn matches(path: &Path, owner: Option<&str>, group: Option<&str>) -> Result<bool> {
+    if owner.is_none() && group.is_none() {
+        return Ok(true);
+    }
+
+    let metadata = std::fs::symlink_metadata(path)
+        .with_context(|| format!("failed to stat `{}`", path.display()))?;
+    if let Some(owner) = owner {
+        if metadata.uid() != resolve_uid(owner)? {
+            return Ok(false);
+        }
+    }
+    if let Some(group) = group {
+        if metadata.gid() != resolve_gid(group)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Chowns `path` to `owner`/`group` (whichever is set; a no-op if neither
+/// is), directly or through `elevation`. Symlinks are chowned themselves,
+/// matching `sudo::chown_to_invoker`.
+pub fn chown(
+    path: &Path,
+    owner: Option<&str>,
+    group: Option<&str>,
+    elevate_chown: bool,
+    elevation: elevate::ElevationStrategy,
+) -> Result<()> {
+    if owner.is_none() && group.is_none() {
+        return Ok(());
+    }
+
+    let spec = format!("{}:{}", owner.unwrap_or(""), group.unwrap_or(""));
+    if !elevate_chown {
+        let mut command = Command::new("chown");
+        command.arg("-h").arg(&spec).arg(path);
+        let output = subprocess::run_captured(&mut command, &Limits::default())
+            .with_context(|| format!("failed to run `chown {spec} {}`", path.display()))?;
+        if !output.status.success() {
+            bail!(
+                "`chown {spec} {}` failed with {}: {}",
+                path.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        return Ok(());
+    }
+
+    let path_str = path.to_string_lossy();
+    elevate::run_privileged("chown", &["-h", &spec, &path_str], elevation)
+        .with_context(|| format!("failed to run `chown {spec} {path_str}` elevated"))?;
+    Ok(())
+}
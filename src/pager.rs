@@ -0,0 +1,69 @@
+//! A minimal `less`-style pager for output long enough to benefit from one.
+//! Piping a three-line result through a pager would be surprising, so
+//! [`page`] only bothers when stdout is a terminal and the text actually
+//! overflows it; a spawn failure (missing binary, a bogus `--pager`) falls
+//! back to printing directly rather than losing the output.
+
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// Options controlling whether/how [`page`] pages its output.
+pub struct PagerOptions {
+    /// Never page, no matter how long the output is.
+    pub disabled: bool,
+    /// Command to run instead of `$PAGER`/`less`, split like a shell would.
+    pub command: Option<String>,
+}
+
+/// Prints `text` through a pager if it's worth it (`options.disabled` isn't
+/// set, stdout is a terminal, and `text` has more lines than the terminal is
+/// tall), otherwise prints it directly.
+pub fn page(text: &str, options: &PagerOptions) {
+    if options.disabled || !std::io::stdout().is_terminal() || !exceeds_terminal_height(text) {
+        println!("{text}");
+        return;
+    }
+
+    let command = options
+        .command
+        .clone()
+        .or_else(|| std::env::var("PAGER").ok())
+        .unwrap_or_else(|| "less".to_string());
+
+    let Some(mut words) = shell_words::split(&command)
+        .ok()
+        .filter(|words| !words.is_empty())
+    else {
+        println!("{text}");
+        return;
+    };
+    let program = words.remove(0);
+
+    let Ok(mut child) = Command::new(&program)
+        .args(&words)
+        .stdin(Stdio::piped())
+        .spawn()
+    else {
+        println!("{text}");
+        return;
+    };
+
+    // A reader that quits early (`q` in `less`) closes its end of the pipe;
+    // nothing more to do about a broken-pipe write error at that point.
+    if let Some(stdin) = child.stdin.as_mut() {
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let _ = child.wait();
+}
+
+/// Whether `text` has more lines than the terminal is tall. Assumes it's
+/// worth paging if the terminal size can't be determined at all (e.g. a
+/// terminal that reports a `0` height), same as always paging in that case
+/// would be the safer default.
+fn exceeds_terminal_height(text: &str) -> bool {
+    let height = console::Term::stdout().size().0;
+    if height == 0 {
+        return true;
+    }
+    text.lines().count() > height as usize
+}
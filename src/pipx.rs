@@ -0,0 +1,195 @@
+//! Support for the `pipx_package()` resource: installing Python CLI tools
+//! via `pipx` or `uv tool`, and checking what's already installed by
+//! parsing each tool's own package listing.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::resource::PipxProvider;
+use crate::subprocess::{self, Limits};
+
+/// Lists installed packages and their versions, keyed by package name.
+pub fn list_installed(provider: PipxProvider) -> Result<BTreeMap<String, String>> {
+    match provider {
+        PipxProvider::Pipx => list_pipx(),
+        PipxProvider::Uv => list_uv(),
+    }
+}
+
+/// Caches each provider's package listing for the lifetime of one
+/// `plan`/`apply` run, so any number of `pipx_package()` resources on the
+/// same provider trigger at most one `pipx list --json` / `uv tool list`
+/// call between them, instead of one per resource.
+#[derive(Default)]
+pub struct Snapshot {
+    pipx: RefCell<Option<BTreeMap<String, String>>>,
+    uv: RefCell<Option<BTreeMap<String, String>>>,
+}
+
+impl Snapshot {
+    /// Returns `provider`'s installed-package listing, querying it only on
+    /// the first call for that provider.
+    pub fn installed(&self, provider: PipxProvider) -> Result<BTreeMap<String, String>> {
+        let cell = match provider {
+            PipxProvider::Pipx => &self.pipx,
+            PipxProvider::Uv => &self.uv,
+        };
+
+        if let Some(installed) = cell.borrow().as_ref() {
+            return Ok(installed.clone());
+        }
+
+        let installed = list_installed(provider)?;
+        *cell.borrow_mut() = Some(installed.clone());
+        Ok(installed)
+    }
+}
+
+#[derive(Deserialize)]
+struct PipxList {
+    venvs: BTreeMap<String, PipxVenv>,
+}
+
+#[derive(Deserialize)]
+struct PipxVenv {
+    metadata: PipxMetadata,
+}
+
+#[derive(Deserialize)]
+struct PipxMetadata {
+    main_package: PipxMainPackage,
+}
+
+#[derive(Deserialize)]
+struct PipxMainPackage {
+    package_version: String,
+}
+
+fn list_pipx() -> Result<BTreeMap<String, String>> {
+    let stdout = run_captured_stdout("pipx", &["list", "--json"])?;
+    let parsed: PipxList =
+        serde_json::from_str(&stdout).context("failed to parse `pipx list --json` output")?;
+    Ok(parsed
+        .venvs
+        .into_iter()
+        .map(|(name, venv)| (name, venv.metadata.main_package.package_version))
+        .collect())
+}
+
+/// `uv tool list` prints one line per installed tool (`name v1.2.3`),
+/// followed by indented lines naming the binaries it exposes.
+fn list_uv() -> Result<BTreeMap<String, String>> {
+    let stdout = run_captured_stdout("uv", &["tool", "list"])?;
+    Ok(stdout
+        .lines()
+        .filter(|line| !line.starts_with(char::is_whitespace))
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?;
+            let version = parts.next()?.trim_start_matches('v');
+            Some((name.to_string(), version.to_string()))
+        })
+        .collect())
+}
+
+fn run_captured_stdout(binary: &str, args: &[&str]) -> Result<String> {
+    let mut command = Command::new(binary);
+    command.args(args);
+    let output = subprocess::run_captured(&mut command, &Limits::default())
+        .with_context(|| format!("failed to run `{binary} {}`", args.join(" ")))?;
+    if !output.status.success() {
+        bail!(
+            "`{binary} {}` failed with {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Operators pip-style version specifiers may start with, in the order
+/// checked (`==`/`!=` before their single-character prefixes would matter,
+/// though none currently overlap).
+const VERSION_OPERATORS: &[&str] = &["==", "!=", ">=", "<=", "~=", ">", "<"];
+
+/// How a `pipx_package()` `version` option constrains the installed
+/// version. A bare version string like `"1.2.3"` implies exact equality;
+/// a string already starting with a pip-style operator (`>=1.2`, `!=2.0`,
+/// ...) is passed straight through, but pipx/uv have no API to ask "does
+/// what's installed satisfy this constraint" the way `apt`/`brew` might —
+/// keron can only compare for exact equality, so a constrained pin is
+/// reported as satisfied whenever the package is present at all.
+pub enum VersionPin<'a> {
+    None,
+    Exact(&'a str),
+    Constrained(&'a str),
+}
+
+pub fn parse_version_pin(version: Option<&str>) -> VersionPin<'_> {
+    match version {
+        None => VersionPin::None,
+        Some(version) if VERSION_OPERATORS.iter().any(|op| version.starts_with(op)) => {
+            VersionPin::Constrained(version)
+        }
+        Some(version) => VersionPin::Exact(version),
+    }
+}
+
+/// Builds the pip-style package specifier passed to `pipx install` / `uv
+/// tool install`.
+fn spec_for(name: &str, version: Option<&str>) -> String {
+    match parse_version_pin(version) {
+        VersionPin::None => name.to_string(),
+        VersionPin::Exact(version) => format!("{name}=={version}"),
+        VersionPin::Constrained(constraint) => format!("{name}{constraint}"),
+    }
+}
+
+/// Installs `name` (optionally pinned to `version`) via `provider`, retrying
+/// up to `retries` times (with exponential backoff) if an attempt fails, and
+/// giving each attempt up to `timeout` to complete.
+pub fn install(
+    provider: PipxProvider,
+    name: &str,
+    version: Option<&str>,
+    retries: u32,
+    timeout: Duration,
+) -> Result<()> {
+    let spec = spec_for(name, version);
+    let args: &[&str] = match provider {
+        PipxProvider::Pipx => &["install"],
+        PipxProvider::Uv => &["tool", "install"],
+    };
+    let limits = Limits {
+        timeout,
+        ..Limits::default()
+    };
+
+    subprocess::retry_with_backoff(retries, |_attempt| {
+        let mut command = Command::new(provider.binary());
+        command.args(args).arg(&spec);
+        let output = subprocess::run_captured(&mut command, &limits).with_context(|| {
+            format!(
+                "failed to run `{} {} {spec}`",
+                provider.binary(),
+                args.join(" ")
+            )
+        })?;
+        if !output.status.success() {
+            bail!(
+                "`{} {} {spec}` failed with {}: {}",
+                provider.binary(),
+                args.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    })
+}
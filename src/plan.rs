@@ -0,0 +1,922 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::agefile;
+use crate::cargo_pkg;
+use crate::cmd;
+use crate::facts;
+use crate::fileblock;
+use crate::gitrepo;
+use crate::hooks::HookCmd;
+use crate::manifest::Manifest;
+use crate::ownership;
+use crate::pipx;
+use crate::render;
+use crate::resource::{
+    AgeFileResource, CargoPackageResource, CmdResource, DirResource, FileBlockResource,
+    GitRepoResource, PipxPackageResource, Resource, State, TemplateResource,
+};
+use crate::secrets;
+
+/// Why an operation can't be applied without extra care.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Conflict {
+    /// The destination has the filesystem immutable attribute set (`chattr +i`).
+    Immutable,
+    /// The destination lives on a mount that was mounted read-only.
+    ReadOnlyMount,
+    /// The destination exists but isn't a git repository, so it can't be
+    /// managed as a `git_repo()` resource without clobbering it.
+    NotAGitRepo,
+    /// The path exists but isn't a directory, so it can't be managed as a
+    /// `dir()` resource without clobbering it.
+    NotADirectory,
+    /// `state = "absent"` was given, but the destination doesn't look like
+    /// something this resource created (a symlink pointing somewhere other
+    /// than `link.source`, or content that doesn't match what `template()`
+    /// would render), so removing it could destroy something unrelated.
+    NotOurs,
+}
+
+impl Conflict {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Conflict::Immutable => "destination is immutable (chattr +i)",
+            Conflict::ReadOnlyMount => "destination is on a read-only mount",
+            Conflict::NotAGitRepo => "destination exists but is not a git repository",
+            Conflict::NotADirectory => "destination exists but is not a directory",
+            Conflict::NotOurs => {
+                "destination exists but doesn't match what this resource would have created"
+            }
+        }
+    }
+
+    /// A concrete next step to clear this conflict, shown alongside
+    /// [`describe`](Self::describe) in `keron plan`'s "Conflicts" summary.
+    pub fn remediation(&self) -> &'static str {
+        match self {
+            Conflict::Immutable => "clear the immutable attribute (`chattr -i <path>`), then re-plan",
+            Conflict::ReadOnlyMount => "remount the destination read-write, then re-plan",
+            Conflict::NotAGitRepo => "remove or move aside the existing path so `git_repo()` can clone into it",
+            Conflict::NotADirectory => "remove or move aside the existing path so `dir()` can create a directory there",
+            Conflict::NotOurs => "inspect the destination by hand; `state = \"absent\"` refuses to remove anything it didn't create",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    /// Nothing needs to change.
+    Noop,
+    /// The destination will be created.
+    Create,
+    /// The destination exists but needs to be overwritten.
+    Update,
+    /// The destination is an existing regular file with the same content the
+    /// managed symlink would point at (or `link.source` doesn't exist yet),
+    /// so it will be replaced with the symlink as a non-destructive "adopt"
+    /// instead of an ordinary `Update`.
+    Adopt,
+    /// `state = "absent"` and the destination will be removed.
+    Remove,
+    /// The operation cannot proceed as-is.
+    Conflict(Conflict),
+    /// `--offline` skipped the query (a package listing, a remote git
+    /// lookup) that would normally decide this. The reason names what was
+    /// skipped and why.
+    Unknown(String),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlannedOperation {
+    /// A deterministic identifier derived from the declaring manifest's
+    /// path, the resource's [`Resource::kind`], and its
+    /// [`Resource::destination`] — stable across runs as long as those three
+    /// don't change, unlike a plan-order index, which shifts whenever a
+    /// manifest gains or loses an earlier resource. Lets external tooling
+    /// (a `plan-diff`, a dashboard) track one resource across two plans.
+    pub id: String,
+    pub description: String,
+    pub resource: Resource,
+    pub action: Action,
+    /// The manifest file that declared this operation's resource, so report
+    /// output can group operations by manifest (`keron plan --group-by
+    /// manifest`).
+    pub manifest_path: std::path::PathBuf,
+    /// The identifier given via `name = "..."` on this resource's
+    /// constructor, if any. Set by the caller after planning, since
+    /// [`plan_resource`] only sees the bare [`Resource`], not the manifest
+    /// metadata around it.
+    pub name: Option<String>,
+    /// The names given via `after = {...}` on this resource's constructor.
+    /// `apply()` skips an operation as soon as one of these fails or is
+    /// itself skipped, even under `--keep-going`, instead of letting an
+    /// independent-looking but actually-dependent operation run anyway.
+    pub after: Vec<String>,
+    /// The names given via `notify = {...}` on this resource's constructor:
+    /// `cmd()` resources, elsewhere in the same manifest, to run only if
+    /// this operation turns out not to be a no-op. See
+    /// [`apply_notify_overrides`].
+    pub notify: Vec<String>,
+    /// The 1-based line, in `manifest_path`, this resource's constructor was
+    /// called from, if mlua's debug info resolved it. Shown alongside
+    /// `manifest_path` wherever it's already surfaced (`--show-manifest`,
+    /// the conflict summary), and in `after`/`notify` resolution errors.
+    pub line: Option<u32>,
+    /// For a `Resource::Cmd`, what would actually run: the command string,
+    /// its resolved `cwd`, and its resolved `env` (redacted the same way
+    /// [`render::resolve_map`] redacts anything else, unless `plan
+    /// --resolve-secrets` was given). `None` for every other resource kind.
+    /// Surfaced by `keron plan --verbose` and in a plan file's JSON, so a
+    /// reviewer can see what would execute before approving it, rather than
+    /// just the bare command string `description` already shows.
+    pub resolved_invocation: Option<ResolvedInvocation>,
+}
+
+/// See [`PlannedOperation::resolved_invocation`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedInvocation {
+    pub command: String,
+    pub cwd: Option<PathBuf>,
+    pub env: BTreeMap<String, String>,
+}
+
+/// A manifest's `pre_cmd()`/`post_cmd()` hooks and `depends_on(...)`
+/// manifests, keyed by [`Manifest::path`] so `apply()` can find them once it
+/// reaches that manifest's group of operations.
+#[derive(Default, Clone)]
+pub struct ManifestHooks {
+    pub pre_cmds: Vec<HookCmd>,
+    pub post_cmds: Vec<HookCmd>,
+    /// Other manifests (by path) that must fully apply, without failing,
+    /// before this manifest's operations start. See `depends_on(...)`.
+    pub depends_on: Vec<PathBuf>,
+}
+
+/// Package listings gathered while planning `pipx_package()`/
+/// `cargo_package()` resources, reused by `apply()` so applying the plan
+/// doesn't re-query them.
+#[derive(Default)]
+pub struct PackageSnapshots {
+    pub pipx: pipx::Snapshot,
+    pub cargo: cargo_pkg::Snapshot,
+}
+
+pub struct Plan {
+    pub operations: Vec<PlannedOperation>,
+    pub package_snapshot: PackageSnapshots,
+    /// Secrets resolved while planning, reused by `apply()` so a secret
+    /// referenced by several resources is only fetched from its backend once
+    /// across the whole plan/apply run.
+    pub secret_cache: secrets::Cache,
+    /// Hooks declared by each manifest, keyed by its path.
+    pub manifest_hooks: BTreeMap<PathBuf, ManifestHooks>,
+    /// Unknown-option warnings collected across every manifest, in manifest
+    /// discovery order (already printed to stderr as each manifest
+    /// evaluated; kept here too so `keron plan` can show them alongside the
+    /// plan they affect).
+    pub warnings: Vec<String>,
+}
+
+impl Plan {
+    pub fn has_conflicts(&self) -> bool {
+        self.operations
+            .iter()
+            .any(|op| matches!(op.action, Action::Conflict(_)))
+    }
+
+    /// Whether applying this plan would do anything: any operation other
+    /// than a no-op, including conflicts and offline/unresolved unknowns,
+    /// counts as "not converged yet". Backs `keron plan --detailed-exitcode`.
+    pub fn has_changes(&self) -> bool {
+        self.operations
+            .iter()
+            .any(|op| !matches!(op.action, Action::Noop))
+    }
+}
+
+/// Options controlling how a plan is built.
+#[derive(Default)]
+pub struct BuildPlanOptions {
+    /// Resolve `secret_ref()` values for real instead of hashing/diffing a
+    /// redaction placeholder. Off by default so plain `keron plan` runs
+    /// never touch the secret backend.
+    pub resolve_secrets: bool,
+    /// Skip package provider queries and remote git lookups, reporting
+    /// affected resources as `Action::Unknown` instead of erroring out.
+    pub offline: bool,
+}
+
+pub fn build_plan_with(manifests: &[Manifest], options: &BuildPlanOptions) -> Result<Plan> {
+    let mut operations = Vec::new();
+    let package_snapshot = PackageSnapshots::default();
+    let secret_cache = secrets::Cache::default();
+    let mut manifest_hooks = BTreeMap::new();
+    let mut warnings = Vec::new();
+
+    for manifest in manifests {
+        warnings.extend(manifest.warnings.iter().cloned());
+        let mut manifest_operations = Vec::new();
+        for named in &manifest.resources {
+            let mut operation =
+                plan_resource(&named.resource, options, &package_snapshot, &secret_cache)
+                    .with_context(|| format!("while planning `{}`", manifest.path.display()))?;
+            operation.manifest_path = manifest.path.clone();
+            operation.id = operation_id(&manifest.path, &named.resource);
+            operation.name = named.name.clone();
+            operation.after = named.after.clone();
+            operation.notify = named.notify.clone();
+            operation.line = named.line;
+            manifest_operations.push(operation);
+        }
+        apply_notify_overrides(&mut manifest_operations);
+        operations.extend(manifest_operations);
+        manifest_hooks.insert(
+            manifest.path.clone(),
+            ManifestHooks {
+                pre_cmds: manifest.pre_cmds.clone(),
+                post_cmds: manifest.post_cmds.clone(),
+                depends_on: manifest.depends_on.clone(),
+            },
+        );
+    }
+
+    validate_no_conflicting_destinations(&operations)?;
+
+    Ok(Plan {
+        operations,
+        package_snapshot,
+        secret_cache,
+        manifest_hooks,
+        warnings,
+    })
+}
+
+/// Bails if two manifests both declare a resource that exclusively owns the
+/// same destination path (a `link()`/`template()`/`git_repo()`/
+/// `template_encrypted()`, whose destination can only ever be what one of
+/// them says it is), which would otherwise plan and apply both, with
+/// whichever ran last winning nondeterministically. `file_block()` is
+/// exempt since multiple manifests appending distinct marked blocks to the
+/// same file is the whole point of that resource, and `dir()`/`cmd()`/
+/// `pipx_package()` don't exclusively own a filesystem path the way these
+/// do.
+fn validate_no_conflicting_destinations(operations: &[PlannedOperation]) -> Result<()> {
+    let mut by_destination: BTreeMap<&Path, BTreeSet<&Path>> = BTreeMap::new();
+    for operation in operations {
+        if !owns_destination_exclusively(&operation.resource) {
+            continue;
+        }
+        by_destination
+            .entry(operation.resource.destination())
+            .or_default()
+            .insert(&operation.manifest_path);
+    }
+
+    for (destination, manifest_paths) in by_destination {
+        if manifest_paths.len() > 1 {
+            let manifests = manifest_paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            bail!(
+                "conflicting resources target `{}` from multiple manifests: {manifests}",
+                destination.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn owns_destination_exclusively(resource: &Resource) -> bool {
+    matches!(
+        resource,
+        Resource::Link(_) | Resource::Template(_) | Resource::GitRepo(_) | Resource::AgeFile(_)
+    )
+}
+
+/// Restricts `plan` to the operations matching `selector`, e.g. `name:zshrc`.
+/// `name:` is the only selector prefix understood so far.
+pub fn filter_only(plan: Plan, selector: &str) -> Result<Plan> {
+    let Some(name) = selector.strip_prefix("name:") else {
+        bail!("`--only {selector}` is not a recognized selector; expected `name:<resource-name>`");
+    };
+
+    Ok(Plan {
+        operations: plan
+            .operations
+            .into_iter()
+            .filter(|operation| operation.name.as_deref() == Some(name))
+            .collect(),
+        ..plan
+    })
+}
+
+/// Whether any resource has drifted, without building (or allocating) a full
+/// [`Plan`]: returns as soon as the first non-no-op action is found. Backs
+/// `keron check-drift`, which only needs a yes/no answer as fast as
+/// possible, not a description of what changed.
+pub fn has_any_change(manifests: &[Manifest], options: &BuildPlanOptions) -> Result<bool> {
+    let package_snapshot = PackageSnapshots::default();
+    let secret_cache = secrets::Cache::default();
+
+    for manifest in manifests {
+        for named in &manifest.resources {
+            let operation =
+                plan_resource(&named.resource, options, &package_snapshot, &secret_cache)
+                    .with_context(|| format!("while planning `{}`", manifest.path.display()))?;
+            if !matches!(operation.action, Action::Noop) {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+fn plan_resource(
+    resource: &Resource,
+    options: &BuildPlanOptions,
+    package_snapshot: &PackageSnapshots,
+    secret_cache: &secrets::Cache,
+) -> Result<PlannedOperation> {
+    let action = match resource {
+        Resource::Link(link) => plan_link(link)?,
+        Resource::Template(template) => plan_template(template, options, secret_cache)?,
+        Resource::GitRepo(git_repo) => plan_git_repo(git_repo, options)?,
+        Resource::FileBlock(file_block) => plan_file_block(file_block)?,
+        Resource::Cmd(cmd) => plan_cmd(cmd, options, secret_cache)?,
+        Resource::Dir(dir) => plan_dir(dir)?,
+        Resource::PipxPackage(package) => {
+            plan_pipx_package(package, options, &package_snapshot.pipx)?
+        }
+        Resource::CargoPackage(package) => {
+            plan_cargo_package(package, options, &package_snapshot.cargo)?
+        }
+        Resource::AgeFile(age_file) => plan_age_file(age_file, options)?,
+    };
+
+    let resolved_invocation = match resource {
+        Resource::Cmd(cmd) => Some(ResolvedInvocation {
+            command: cmd.command.clone(),
+            cwd: cmd.cwd.clone(),
+            env: render::resolve_map(&cmd.env, options.resolve_secrets, secret_cache)?,
+        }),
+        _ => None,
+    };
+
+    Ok(PlannedOperation {
+        id: String::new(),
+        description: resource.describe(),
+        resource: resource.clone(),
+        action,
+        manifest_path: std::path::PathBuf::new(),
+        name: None,
+        after: Vec::new(),
+        notify: Vec::new(),
+        line: None,
+        resolved_invocation,
+    })
+}
+
+/// Hex-encoded SHA-256 digest of `manifest_path`, `resource.kind()`, and
+/// `resource.destination()`, joined by a separator none of the three can
+/// naturally contain. Deterministic across runs: unlike a plan-order index,
+/// it doesn't shift just because an earlier resource was added, removed, or
+/// reordered.
+fn operation_id(manifest_path: &Path, resource: &Resource) -> String {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(manifest_path.to_string_lossy().as_bytes());
+    hasher.update(b"\0");
+    hasher.update(resource.kind().as_bytes());
+    hasher.update(b"\0");
+    hasher.update(resource.destination().to_string_lossy().as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// A `notify = {"name", ...}` opt (see `manifest::docs::RESERVED_OPTS`) marks
+/// another `cmd()` resource in `operations` to run only when this one
+/// actually changes, rather than on every apply — the way a `cmd()` with no
+/// `creates`/`unless`/`only_if` guard normally would. `order_by_dependencies`
+/// already placed every notify target after its notifier, so a single
+/// forward scan is enough to know which targets were triggered before
+/// overriding the rest to [`Action::Noop`].
+fn apply_notify_overrides(operations: &mut [PlannedOperation]) {
+    let targets: std::collections::HashSet<String> = operations
+        .iter()
+        .flat_map(|op| op.notify.iter().cloned())
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+
+    let mut triggered: std::collections::HashSet<String> = std::collections::HashSet::new();
+    for operation in operations.iter() {
+        if !matches!(operation.action, Action::Noop) {
+            triggered.extend(operation.notify.iter().cloned());
+        }
+    }
+
+    for operation in operations.iter_mut() {
+        if let Some(name) = &operation.name {
+            if targets.contains(name) && !triggered.contains(name) {
+                operation.action = Action::Noop;
+            }
+        }
+    }
+}
+
+fn plan_template(
+    template: &TemplateResource,
+    options: &BuildPlanOptions,
+    secret_cache: &secrets::Cache,
+) -> Result<Action> {
+    let destination = &template.destination;
+
+    if let Some(conflict) = classify_conflict(destination)? {
+        return Ok(Action::Conflict(conflict));
+    }
+
+    // `state = "absent"` still needs to render `source` to know whether
+    // `destination`'s content matches what this resource would have written
+    // there, so `source` has to stay in the manifest until the resource is
+    // dropped entirely, even though nothing is written from it anymore.
+    let source = std::fs::read_to_string(&template.source).with_context(|| {
+        format!(
+            "failed to read template source `{}`",
+            template.source.display()
+        )
+    })?;
+    let vars = render::with_existing_content(&template.vars, destination);
+    let vars = render::with_facts(&vars, &facts::Facts::gather());
+    let rendered = render::render(&source, &vars, options.resolve_secrets, secret_cache)?;
+    let rendered = match template.newline {
+        Some(newline) => render::normalize_newlines(&rendered, newline),
+        None => rendered,
+    };
+
+    if template.state == State::Absent {
+        if !destination.exists() {
+            return Ok(Action::Noop);
+        }
+        let existing = std::fs::read_to_string(destination)
+            .with_context(|| format!("failed to read `{}`", destination.display()))?;
+        return if existing == rendered {
+            Ok(Action::Remove)
+        } else {
+            Ok(Action::Conflict(Conflict::NotOurs))
+        };
+    }
+
+    if !destination.exists() {
+        return Ok(Action::Create);
+    }
+
+    let existing = std::fs::read_to_string(destination)
+        .with_context(|| format!("failed to read `{}`", destination.display()))?;
+
+    if existing == rendered
+        && ownership::matches(
+            destination,
+            template.owner.as_deref(),
+            template.group.as_deref(),
+        )?
+    {
+        Ok(Action::Noop)
+    } else {
+        Ok(Action::Update)
+    }
+}
+
+fn plan_link(link: &crate::resource::LinkResource) -> Result<Action> {
+    let destination = &link.destination;
+
+    if let Some(conflict) = classify_conflict(destination)? {
+        return Ok(Action::Conflict(conflict));
+    }
+
+    if link.state == State::Absent {
+        if !destination.exists() && !destination.is_symlink() {
+            return Ok(Action::Noop);
+        }
+        return if destination.is_symlink()
+            && std::fs::read_link(destination).ok().as_deref() == Some(link.source.as_path())
+        {
+            Ok(Action::Remove)
+        } else {
+            Ok(Action::Conflict(Conflict::NotOurs))
+        };
+    }
+
+    if destination.is_symlink() {
+        if std::fs::read_link(destination).ok().as_deref() == Some(link.source.as_path())
+            && ownership::matches(destination, link.owner.as_deref(), link.group.as_deref())?
+        {
+            return Ok(Action::Noop);
+        }
+        return Ok(Action::Update);
+    }
+
+    if destination.exists() {
+        if link.adopt && (!link.source.exists() || files_match(&link.source, destination)?) {
+            Ok(Action::Adopt)
+        } else {
+            Ok(Action::Update)
+        }
+    } else {
+        Ok(Action::Create)
+    }
+}
+
+/// Whether `a` and `b` are both regular files with identical content.
+fn files_match(a: &Path, b: &Path) -> Result<bool> {
+    let a = std::fs::read(a).with_context(|| format!("failed to read `{}`", a.display()))?;
+    let b = std::fs::read(b).with_context(|| format!("failed to read `{}`", b.display()))?;
+    Ok(a == b)
+}
+
+fn plan_git_repo(git_repo: &GitRepoResource, options: &BuildPlanOptions) -> Result<Action> {
+    let destination = &git_repo.destination;
+
+    if let Some(conflict) = classify_conflict(destination)? {
+        return Ok(Action::Conflict(conflict));
+    }
+
+    let Some(local_commit) = gitrepo::current_commit(destination) else {
+        if destination.exists() {
+            return Ok(Action::Conflict(Conflict::NotAGitRepo));
+        }
+        if options.offline {
+            return Ok(Action::Unknown(format!(
+                "--offline: can't clone `{}` to check it",
+                git_repo.url
+            )));
+        }
+        return Ok(Action::Create);
+    };
+
+    if options.offline {
+        return Ok(Action::Unknown(format!(
+            "--offline: can't query `{}` to compare against the local commit",
+            git_repo.url
+        )));
+    }
+
+    let remote_commit = gitrepo::remote_commit(&git_repo.url, git_repo.reference.as_deref())?;
+    if local_commit == remote_commit {
+        Ok(Action::Noop)
+    } else {
+        Ok(Action::Update)
+    }
+}
+
+fn plan_file_block(file_block: &FileBlockResource) -> Result<Action> {
+    let destination = &file_block.destination;
+
+    if let Some(conflict) = classify_conflict(destination)? {
+        return Ok(Action::Conflict(conflict));
+    }
+
+    if !destination.exists() {
+        return Ok(Action::Create);
+    }
+
+    let existing = std::fs::read_to_string(destination)
+        .with_context(|| format!("failed to read `{}`", destination.display()))?;
+    let block = fileblock::render_block(&file_block.content, &file_block.marker);
+    let merged = fileblock::merge_block(&existing, &block, &file_block.marker);
+
+    if merged == existing {
+        Ok(Action::Noop)
+    } else {
+        Ok(Action::Update)
+    }
+}
+
+fn plan_cmd(
+    resource: &CmdResource,
+    options: &BuildPlanOptions,
+    secret_cache: &secrets::Cache,
+) -> Result<Action> {
+    if let Some(creates) = &resource.creates {
+        if !creates.exists() {
+            return Ok(Action::Create);
+        }
+
+        let Some(expected_hash) = &resource.creates_hash else {
+            return Ok(Action::Noop);
+        };
+
+        let actual_hash = cmd::file_hash(creates)?;
+        return Ok(if &actual_hash == expected_hash {
+            Action::Noop
+        } else {
+            Action::Update
+        });
+    }
+
+    if let Some(unless) = &resource.unless {
+        let env = render::resolve_map(&resource.env, options.resolve_secrets, secret_cache)?;
+        return Ok(
+            if cmd::guard_succeeds(unless, &env, resource.cwd.as_deref())? {
+                Action::Noop
+            } else {
+                Action::Create
+            },
+        );
+    }
+
+    if let Some(only_if) = &resource.only_if {
+        let env = render::resolve_map(&resource.env, options.resolve_secrets, secret_cache)?;
+        return Ok(
+            if cmd::guard_succeeds(only_if, &env, resource.cwd.as_deref())? {
+                Action::Create
+            } else {
+                Action::Noop
+            },
+        );
+    }
+
+    // No idempotence marker: keron can't tell whether the command has
+    // already run, so it always reports the resource as pending.
+    Ok(Action::Create)
+}
+
+fn plan_dir(resource: &DirResource) -> Result<Action> {
+    let path = &resource.path;
+
+    if let Some(conflict) = classify_conflict(path)? {
+        return Ok(Action::Conflict(conflict));
+    }
+
+    if !path.exists() {
+        return Ok(Action::Create);
+    }
+    if !path.is_dir() {
+        return Ok(Action::Conflict(Conflict::NotADirectory));
+    }
+
+    if let Some(mode) = resource.mode {
+        let metadata = std::fs::metadata(path)
+            .with_context(|| format!("failed to read metadata for `{}`", path.display()))?;
+        if metadata.permissions().mode() & 0o777 != mode {
+            return Ok(Action::Update);
+        }
+    }
+
+    Ok(Action::Noop)
+}
+
+/// A package with no `version` pin is considered installed as soon as it
+/// shows up in the listing, regardless of which version that happens to be.
+/// An exact pin (`version = "1.2.3"`) additionally needs the installed
+/// version to match. A constrained pin (`version = ">=1.2"`) can't be
+/// verified against what's installed — pipx/uv have no such query — so
+/// it's reported as satisfied whenever the package is present at all,
+/// with a hint printed so that limitation isn't silent.
+fn plan_pipx_package(
+    resource: &PipxPackageResource,
+    options: &BuildPlanOptions,
+    package_snapshot: &pipx::Snapshot,
+) -> Result<Action> {
+    if options.offline {
+        return Ok(Action::Unknown(format!(
+            "--offline: can't query {} to check `{}`",
+            resource.provider.binary(),
+            resource.name
+        )));
+    }
+
+    let installed = package_snapshot.installed(resource.provider)?;
+
+    let Some(installed_version) = installed.get(&resource.name) else {
+        return Ok(Action::Create);
+    };
+
+    match pipx::parse_version_pin(resource.version.as_deref()) {
+        pipx::VersionPin::None => Ok(Action::Noop),
+        pipx::VersionPin::Exact(version) if version == installed_version => Ok(Action::Noop),
+        pipx::VersionPin::Exact(_) => Ok(Action::Update),
+        pipx::VersionPin::Constrained(constraint) => {
+            eprintln!(
+                "pipx_package `{}`: {} can't verify the constraint `{constraint}` against installed `{installed_version}`; treating as satisfied",
+                resource.name,
+                resource.provider.binary(),
+            );
+            Ok(Action::Noop)
+        }
+    }
+}
+
+/// Like [`plan_pipx_package`], but cargo only ever reports an exact
+/// installed version (no constraint operators), so there's no "unverifiable"
+/// case to fall back on. A `git`/`features` change to an already-installed
+/// package with no `version` bump is invisible to `cargo install --list` and
+/// is reported as a no-op — the same limitation `plan_pipx_package` has for
+/// a constrained pin, just without a way to hint at it here since nothing
+/// was actually left unverified from cargo's own point of view.
+fn plan_cargo_package(
+    resource: &CargoPackageResource,
+    options: &BuildPlanOptions,
+    cargo_snapshot: &cargo_pkg::Snapshot,
+) -> Result<Action> {
+    if options.offline {
+        return Ok(Action::Unknown(format!(
+            "--offline: can't query cargo to check `{}`",
+            resource.name
+        )));
+    }
+
+    let installed = cargo_snapshot.installed()?;
+
+    let Some(installed_version) = installed.get(&resource.name) else {
+        return Ok(Action::Create);
+    };
+
+    match &resource.version {
+        None => Ok(Action::Noop),
+        Some(version) if version == installed_version => Ok(Action::Noop),
+        Some(_) => Ok(Action::Update),
+    }
+}
+
+/// Decrypting requires reading the identity file and running the age
+/// primitives, so like `secret_ref()`, it's only done when the caller opted
+/// into touching the secret material (`--resolve-secrets`); otherwise the
+/// plan reports the file's state as unknown rather than guessing.
+fn plan_age_file(resource: &AgeFileResource, options: &BuildPlanOptions) -> Result<Action> {
+    let destination = &resource.destination;
+
+    if let Some(conflict) = classify_conflict(destination)? {
+        return Ok(Action::Conflict(conflict));
+    }
+
+    if !options.resolve_secrets {
+        return Ok(Action::Unknown(
+            "pass --resolve-secrets to decrypt and diff this file".to_string(),
+        ));
+    }
+
+    let plaintext = agefile::decrypt(&resource.source, &resource.identity)?;
+
+    if !destination.exists() {
+        return Ok(Action::Create);
+    }
+
+    let existing = std::fs::read(destination)
+        .with_context(|| format!("failed to read `{}`", destination.display()))?;
+
+    Ok(if existing == plaintext {
+        Action::Noop
+    } else {
+        Action::Update
+    })
+}
+
+/// Detects conditions that would make writing to `destination` fail or
+/// behave surprisingly, so they can be surfaced as a distinct plan-time
+/// conflict rather than an opaque I/O error during apply.
+fn classify_conflict(destination: &Path) -> Result<Option<Conflict>> {
+    if destination.exists() && is_immutable(destination) {
+        return Ok(Some(Conflict::Immutable));
+    }
+
+    if is_on_readonly_mount(destination)? {
+        return Ok(Some(Conflict::ReadOnlyMount));
+    }
+
+    Ok(None)
+}
+
+/// Best-effort check for the ext2/3/4 `chattr +i` immutable attribute via
+/// `lsattr`. Returns `false` (rather than erroring) when `lsattr` isn't
+/// available, since most destinations simply aren't on such a filesystem.
+fn is_immutable(path: &Path) -> bool {
+    let Ok(output) = Command::new("lsattr").arg("-d").arg(path).output() else {
+        return false;
+    };
+    if !output.status.success() {
+        return false;
+    }
+    let Ok(stdout) = String::from_utf8(output.stdout) else {
+        return false;
+    };
+    stdout
+        .split_whitespace()
+        .next()
+        .is_some_and(|attrs| attrs.contains('i'))
+}
+
+/// Best-effort check of whether `path`'s mount point is mounted read-only,
+/// by inspecting `/proc/mounts`. Returns `false` on platforms without it.
+fn is_on_readonly_mount(path: &Path) -> Result<bool> {
+    let Ok(mounts) = std::fs::read_to_string("/proc/mounts") else {
+        return Ok(false);
+    };
+
+    let target = path.parent().unwrap_or(path);
+    let target = target
+        .canonicalize()
+        .unwrap_or_else(|_| target.to_path_buf());
+
+    let mut best_match: Option<(&Path, bool)> = None;
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(_device), Some(mount_point), Some(_fstype), Some(options)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let mount_point = Path::new(mount_point);
+        if target.starts_with(mount_point) {
+            let is_better = best_match
+                .map(|(current, _)| mount_point.as_os_str().len() > current.as_os_str().len())
+                .unwrap_or(true);
+            if is_better {
+                let read_only = options.split(',').any(|opt| opt == "ro");
+                best_match = Some((mount_point, read_only));
+            }
+        }
+    }
+
+    Ok(best_match.is_some_and(|(_, read_only)| read_only))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_no_conflicting_destinations;
+    use crate::plan::{Action, PlannedOperation};
+    use crate::resource::{LinkResource, Resource, State};
+
+    fn link_operation(manifest_path: &str, destination: &str) -> PlannedOperation {
+        PlannedOperation {
+            id: format!("{manifest_path}:{destination}"),
+            description: String::new(),
+            resource: Resource::Link(LinkResource {
+                source: "source".into(),
+                destination: destination.into(),
+                windows_fallback: None,
+                adopt: false,
+                owner: None,
+                group: None,
+                elevate: false,
+                state: State::Present,
+            }),
+            action: Action::Noop,
+            manifest_path: manifest_path.into(),
+            name: None,
+            after: Vec::new(),
+            notify: Vec::new(),
+            line: None,
+            resolved_invocation: None,
+        }
+    }
+
+    #[test]
+    fn same_destination_from_two_manifests_conflicts() {
+        let operations = vec![
+            link_operation("a.lua", "/home/user/.zshrc"),
+            link_operation("b.lua", "/home/user/.zshrc"),
+        ];
+
+        let err = validate_no_conflicting_destinations(&operations).unwrap_err();
+        assert!(err.to_string().contains("multiple manifests"));
+        assert!(err.to_string().contains(".zshrc"));
+    }
+
+    #[test]
+    fn same_destination_from_one_manifest_is_not_a_conflict() {
+        // Not realistic (a manifest naming the same destination twice is its
+        // own bug), but `validate_no_conflicting_destinations` only exists to
+        // catch it happening *across* manifests, so a single manifest
+        // shouldn't trip it.
+        let operations = vec![
+            link_operation("a.lua", "/home/user/.zshrc"),
+            link_operation("a.lua", "/home/user/.zshrc"),
+        ];
+
+        assert!(validate_no_conflicting_destinations(&operations).is_ok());
+    }
+
+    #[test]
+    fn different_destinations_never_conflict() {
+        let operations = vec![
+            link_operation("a.lua", "/home/user/.zshrc"),
+            link_operation("b.lua", "/home/user/.vimrc"),
+        ];
+
+        assert!(validate_no_conflicting_destinations(&operations).is_ok());
+    }
+}
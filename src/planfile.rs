@@ -0,0 +1,201 @@
+//! On-disk format for `keron plan -o <file>` / `keron apply --plan-file
+//! <file> --execute`: a reviewed plan gets generated once (e.g. in CI) and
+//! applied later without re-deciding what to do, so what an operator
+//! approved is exactly what runs.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::manifest::Manifest;
+use crate::plan::{ManifestHooks, PackageSnapshots, Plan, PlannedOperation};
+use crate::secrets;
+
+/// Bumped whenever [`PlanFile`]'s shape changes incompatibly, so an old
+/// `keron apply --plan-file` build fails loudly on a plan generated by a
+/// newer one (or vice versa) instead of misinterpreting the JSON.
+const PLAN_FILE_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct PlanFile {
+    version: u32,
+    /// Hash of every discovered manifest's path and contents, in discovery
+    /// order, so `keron apply --plan-file` can refuse to execute a plan
+    /// against a source tree that changed after the plan was generated.
+    manifest_hash: String,
+    operations: Vec<PlannedOperation>,
+}
+
+/// Writes `plan` to `path` for a later `keron apply --plan-file`.
+pub fn write(path: &Path, manifests: &[Manifest], plan: &Plan) -> Result<()> {
+    let file = PlanFile {
+        version: PLAN_FILE_VERSION,
+        manifest_hash: hash_manifests(manifests),
+        operations: plan.operations.clone(),
+    };
+    let json = serde_json::to_string_pretty(&file).context("failed to serialize plan")?;
+    std::fs::write(path, json).with_context(|| format!("failed to write `{}`", path.display()))
+}
+
+/// Loads a plan file and checks it against `manifests` (the manifests
+/// discovered right now), bailing if either the format version or the
+/// manifest contents have drifted since the plan was generated.
+pub fn read_and_verify(path: &Path, manifests: &[Manifest]) -> Result<Plan> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    let file: PlanFile = serde_json::from_str(&json)
+        .with_context(|| format!("failed to parse plan file `{}`", path.display()))?;
+
+    if file.version != PLAN_FILE_VERSION {
+        bail!(
+            "`{}` was generated by plan-file format v{}, but this build only understands v{PLAN_FILE_VERSION}; regenerate the plan",
+            path.display(),
+            file.version,
+        );
+    }
+
+    if file.manifest_hash != hash_manifests(manifests) {
+        bail!(
+            "`{}` no longer matches the manifests it was generated from; regenerate the plan with `keron plan -o` before applying it",
+            path.display(),
+        );
+    }
+
+    Ok(Plan {
+        operations: file.operations,
+        // Neither cache is part of the plan file: applying still queries
+        // package listings and resolves secrets fresh, same as a normal
+        // `keron apply` would.
+        package_snapshot: PackageSnapshots::default(),
+        secret_cache: secrets::Cache::default(),
+        // Not part of the plan file either, but cheap to re-derive from the
+        // manifests, which are re-read anyway to verify `manifest_hash`.
+        manifest_hooks: manifests
+            .iter()
+            .map(|manifest| {
+                (
+                    manifest.path.clone(),
+                    ManifestHooks {
+                        pre_cmds: manifest.pre_cmds.clone(),
+                        post_cmds: manifest.post_cmds.clone(),
+                        depends_on: manifest.depends_on.clone(),
+                    },
+                )
+            })
+            .collect(),
+        // Likewise re-derived rather than round-tripped through the plan
+        // file: an unknown-option warning is about the manifest, not the
+        // plan, so it should reflect whatever the manifests say right now.
+        warnings: manifests
+            .iter()
+            .flat_map(|manifest| manifest.warnings.iter().cloned())
+            .collect(),
+    })
+}
+
+/// Reads a plan file's operations without checking `manifest_hash` against
+/// any manifests, for tooling (`keron plan-diff`) that only wants to compare
+/// two plans' contents rather than re-verify either against a source tree.
+pub fn read_operations(path: &Path) -> Result<Vec<PlannedOperation>> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    let file: PlanFile = serde_json::from_str(&json)
+        .with_context(|| format!("failed to parse plan file `{}`", path.display()))?;
+    Ok(file.operations)
+}
+
+fn hash_manifests(manifests: &[Manifest]) -> String {
+    let mut hasher = Sha256::new();
+    for manifest in manifests {
+        hasher.update(manifest.path.to_string_lossy().as_bytes());
+        if let Ok(contents) = std::fs::read(&manifest.path) {
+            hasher.update(&contents);
+        }
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest;
+
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("keron-planfile-test-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn empty_plan() -> Plan {
+        Plan {
+            operations: Vec::new(),
+            package_snapshot: PackageSnapshots::default(),
+            secret_cache: secrets::Cache::default(),
+            manifest_hooks: Default::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn read_and_verify_rejects_a_mismatched_format_version() {
+        let dir = scratch_dir("version");
+        std::fs::write(dir.join("a.lua"), "cmd(\"true\")").unwrap();
+        let manifests = manifest::discover(&dir).unwrap();
+        let plan_path = dir.join("plan.json");
+        write(&plan_path, &manifests, &empty_plan()).unwrap();
+
+        let mut file: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&plan_path).unwrap()).unwrap();
+        file["version"] = serde_json::json!(PLAN_FILE_VERSION + 1);
+        std::fs::write(&plan_path, serde_json::to_string(&file).unwrap()).unwrap();
+
+        let error = match read_and_verify(&plan_path, &manifests) {
+            Ok(_) => panic!("expected a version mismatch error"),
+            Err(err) => err.to_string(),
+        };
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(error.contains("only understands v"));
+    }
+
+    #[test]
+    fn read_and_verify_rejects_manifests_that_changed_since_the_plan_was_written() {
+        let dir = scratch_dir("drift");
+        let manifest_path = dir.join("a.lua");
+        std::fs::write(&manifest_path, "cmd(\"true\")").unwrap();
+        let manifests = manifest::discover(&dir).unwrap();
+        let plan_path = dir.join("plan.json");
+        write(&plan_path, &manifests, &empty_plan()).unwrap();
+
+        std::fs::write(&manifest_path, "cmd(\"false\")").unwrap();
+
+        let error = match read_and_verify(&plan_path, &manifests) {
+            Ok(_) => panic!("expected a manifest-drift error"),
+            Err(err) => err.to_string(),
+        };
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(error.contains("no longer matches"));
+    }
+
+    #[test]
+    fn read_and_verify_passes_through_a_matching_plan() {
+        let dir = scratch_dir("match");
+        std::fs::write(dir.join("a.lua"), "cmd(\"true\")").unwrap();
+        let manifests = manifest::discover(&dir).unwrap();
+        let plan_path = dir.join("plan.json");
+        write(&plan_path, &manifests, &empty_plan()).unwrap();
+
+        let result = read_and_verify(&plan_path, &manifests);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_ok());
+    }
+}
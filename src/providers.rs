@@ -0,0 +1,247 @@
+//! Detection of package managers on the host. Most of them (`brew`, `apt`,
+//! ...) are only ever driven indirectly through a manifest's own `cmd()`,
+//! and knowing which are actually on `$PATH` (and where they'd drop things)
+//! is what makes a rejected `cmd()` guard like `unless "brew list foo"`
+//! debuggable: was `brew` even found? `pipx`/`cargo` are the exception —
+//! `pipx_package()`/`cargo_package()` install through them directly, so
+//! their registrations here double as the detection `keron providers` uses.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::elevate;
+use crate::subprocess::{self, Limits};
+
+/// A package manager keron knows how to look for.
+struct KnownProvider {
+    name: &'static str,
+    binary: &'static str,
+    version_args: &'static [&'static str],
+    default_install_dir: fn() -> Option<PathBuf>,
+}
+
+const KNOWN_PROVIDERS: &[KnownProvider] = &[
+    KnownProvider {
+        name: "brew",
+        binary: "brew",
+        version_args: &["--version"],
+        default_install_dir: || Some(PathBuf::from("/opt/homebrew/Cellar")),
+    },
+    // Casks aren't formulae: `brew install firefox` fails for a
+    // GUI/cask-only package, and the two kinds live in separate directory
+    // trees under the same `brew` binary. Reported as a second provider
+    // (rather than a field on the formula one) so `keron providers` shows
+    // both install roots and a `cmd()` guard like
+    // `unless "brew list --cask firefox"` is debuggable the same way a
+    // formula guard is.
+    KnownProvider {
+        name: "brew-cask",
+        binary: "brew",
+        version_args: &["--version"],
+        default_install_dir: || Some(PathBuf::from("/opt/homebrew/Caskroom")),
+    },
+    KnownProvider {
+        name: "apt",
+        binary: "apt",
+        version_args: &["--version"],
+        default_install_dir: || Some(PathBuf::from("/usr")),
+    },
+    KnownProvider {
+        name: "dnf",
+        binary: "dnf",
+        version_args: &["--version"],
+        default_install_dir: || Some(PathBuf::from("/usr")),
+    },
+    KnownProvider {
+        name: "pacman",
+        binary: "pacman",
+        version_args: &["--version"],
+        default_install_dir: || Some(PathBuf::from("/usr")),
+    },
+    KnownProvider {
+        name: "apk",
+        binary: "apk",
+        version_args: &["--version"],
+        default_install_dir: || Some(PathBuf::from("/usr")),
+    },
+    KnownProvider {
+        name: "pipx",
+        binary: "pipx",
+        version_args: &["--version"],
+        default_install_dir: || home_subdir(".local/pipx"),
+    },
+    KnownProvider {
+        name: "uv",
+        binary: "uv",
+        version_args: &["--version"],
+        default_install_dir: || home_subdir(".local/share/uv/tools"),
+    },
+    KnownProvider {
+        name: "npm",
+        binary: "npm",
+        version_args: &["--version"],
+        default_install_dir: || home_subdir(".npm-global"),
+    },
+    KnownProvider {
+        name: "cargo",
+        binary: "cargo",
+        version_args: &["--version"],
+        default_install_dir: || home_subdir(".cargo/bin"),
+    },
+    KnownProvider {
+        name: "winget",
+        binary: "winget",
+        version_args: &["--version"],
+        // Winget installs land under `%LOCALAPPDATA%`, which nothing else
+        // in this list has needed to locate — `home_subdir` builds off
+        // `xdg::home_dir()`, not `%LOCALAPPDATA%`, so a guessed path here
+        // would likely just be wrong. `None` is honest about not knowing.
+        default_install_dir: || None,
+    },
+];
+
+fn home_subdir(suffix: &str) -> Option<PathBuf> {
+    Some(crate::xdg::home_dir().join(suffix))
+}
+
+/// Detection outcome for a single provider (built-in or user-declared), in
+/// the shape reported by `keron providers`.
+#[derive(Debug, Serialize)]
+pub struct ProviderStatus {
+    pub name: String,
+    pub binary: Option<String>,
+    pub detected: bool,
+    pub path: Option<PathBuf>,
+    pub version: Option<String>,
+    pub default_install_dir: Option<PathBuf>,
+    /// `detect`/`list`/`install`/`remove` commands, for manifest-declared
+    /// providers only — useful for debugging what a `register_provider()`
+    /// entry would actually run.
+    pub detect: Option<String>,
+    pub list: Option<String>,
+    pub install: Option<String>,
+    pub remove: Option<String>,
+}
+
+/// A provider declared in a manifest via `register_provider()`, for package
+/// managers keron has no built-in knowledge of (`nix profile`, `flatpak`,
+/// ...). `detect` is run as a shell command and treated the same way as a
+/// `cmd()` `unless`/`only_if` guard: exit zero means present. `list`,
+/// `install`, and `remove` aren't run by `keron providers` itself — they're
+/// kept so other resources can look the provider up by name and shell out
+/// through them.
+#[derive(Debug, Clone)]
+pub struct CustomProvider {
+    pub name: String,
+    pub detect: String,
+    pub list: Option<String>,
+    pub install: Option<String>,
+    pub remove: Option<String>,
+}
+
+/// Detects every known provider on `$PATH`, in the fixed order they're
+/// declared above.
+pub fn detect_all() -> Vec<ProviderStatus> {
+    KNOWN_PROVIDERS.iter().map(detect_one).collect()
+}
+
+fn detect_one(provider: &KnownProvider) -> ProviderStatus {
+    let path = find_on_path(provider.binary);
+    match &path {
+        Some(path) => {
+            tracing::debug!(provider = provider.name, path = %path.display(), "provider found on $PATH")
+        }
+        None => tracing::debug!(
+            provider = provider.name,
+            binary = provider.binary,
+            "provider not found on $PATH"
+        ),
+    }
+    let version = path
+        .as_ref()
+        .and_then(|path| read_version(path, provider.version_args));
+
+    ProviderStatus {
+        name: provider.name.to_string(),
+        binary: Some(provider.binary.to_string()),
+        detected: path.is_some(),
+        path,
+        version,
+        default_install_dir: (provider.default_install_dir)(),
+        detect: None,
+        list: None,
+        install: None,
+        remove: None,
+    }
+}
+
+/// Detects a manifest-declared provider by running its `detect` command,
+/// the same way a `cmd()` guard is run.
+pub fn detect_custom(provider: &CustomProvider) -> ProviderStatus {
+    let detected =
+        crate::cmd::guard_succeeds(&provider.detect, &BTreeMap::new(), None).unwrap_or(false);
+    tracing::debug!(
+        provider = provider.name,
+        detect = provider.detect,
+        detected,
+        "custom provider detection"
+    );
+
+    ProviderStatus {
+        name: provider.name.clone(),
+        binary: None,
+        detected,
+        path: None,
+        version: None,
+        default_install_dir: None,
+        detect: Some(provider.detect.clone()),
+        list: provider.list.clone(),
+        install: provider.install.clone(),
+        remove: provider.remove.clone(),
+    }
+}
+
+/// Runs `apt-get update` under the configured elevation launcher — the only
+/// package-manager operation in this tree that needs root; pipx/uv installs
+/// and every provider's own detection run as the invoking user. Centralized
+/// here rather than left as an ad hoc `Command` in `commands::apply`, so a
+/// provider-triggered privileged call goes through the same
+/// `--elevation`/`--assume-no-elevation`-aware path as everything else that
+/// elevates, instead of needing all of keron run under sudo to reach it.
+pub fn refresh_apt(elevation: elevate::ElevationStrategy) -> Result<(), elevate::ElevationError> {
+    elevate::run_privileged("apt-get", &["update"], elevation)
+}
+
+/// Hand-rolled `which`: keron shells out to enough external tools already
+/// without pulling in a crate just to walk `$PATH`.
+fn find_on_path(binary: &str) -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    std::env::split_paths(&path_var).find_map(|dir| {
+        let candidate = dir.join(binary);
+        candidate.is_file().then_some(candidate)
+    })
+}
+
+/// Runs `path <version_args>` and returns the first line of its output,
+/// trimmed. Best-effort: a provider that's on `$PATH` but fails to report a
+/// version still counts as detected, just without one.
+fn read_version(path: &PathBuf, version_args: &[&str]) -> Option<String> {
+    let mut command = Command::new(path);
+    command.args(version_args);
+    let limits = Limits {
+        timeout: Duration::from_secs(5),
+        ..Limits::default()
+    };
+    let output = subprocess::run_captured(&mut command, &limits).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(str::to_string)
+}
@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::facts::Facts;
+use crate::resource::{Newline, TemplateValue};
+use crate::secrets;
+
+const EACH_CLOSE_TAG: &str = "{{/each}}";
+
+/// Reserved variable name exposing `destination`'s current content to a
+/// template, so a manifest can merge machine-local sections of an existing
+/// file into what it renders (e.g. `{{ existing }}` copied verbatim into a
+/// generated block). Empty when `destination` doesn't exist yet or isn't
+/// valid UTF-8, so a first-time render still succeeds.
+const EXISTING_VAR: &str = "existing";
+
+/// Returns `vars` with [`EXISTING_VAR`] set to `destination`'s current
+/// content, overriding any variable of the same name the manifest declared,
+/// the same way `#each` reserves `this` inside its loop body. Reading
+/// `destination` fresh on every call keeps plan-time diffing and apply
+/// deterministic: both see whatever is on disk at the moment they render.
+pub fn with_existing_content(
+    vars: &BTreeMap<String, TemplateValue>,
+    destination: &Path,
+) -> BTreeMap<String, TemplateValue> {
+    let existing = std::fs::read_to_string(destination).unwrap_or_default();
+    let mut vars = vars.clone();
+    vars.insert(EXISTING_VAR.to_string(), TemplateValue::Str(existing));
+    vars
+}
+
+/// Reserved variable name exposing host facts (os, arch, hostname, ...) to
+/// a template, mirroring how `facts()` exposes the same values to Lua. See
+/// [`Facts`].
+const FACTS_VAR: &str = "facts";
+
+/// Returns `vars` with [`FACTS_VAR`] set to `facts`, overriding any variable
+/// of the same name the manifest declared, the same way [`with_existing_content`]
+/// reserves `existing`.
+pub fn with_facts(
+    vars: &BTreeMap<String, TemplateValue>,
+    facts: &Facts,
+) -> BTreeMap<String, TemplateValue> {
+    let mut vars = vars.clone();
+    vars.insert(FACTS_VAR.to_string(), facts.as_template_value());
+    vars
+}
+
+/// Renders `{{ name }}` placeholders (dotted, e.g. `{{ host.user }}`, for
+/// fields of a `Table` variable) and `{{#each name}}...{{/each}}` loops
+/// (over a `List` variable, with the current item bound to `this` inside the
+/// loop body) in `content` against `vars`.
+///
+/// When `resolve_secrets` is `false`, secret-backed variables (any `env()`
+/// value still marked `sensitive`, and any plain-string var named in a
+/// `template()`'s `sensitive_vars`) are rendered as a fixed redaction
+/// placeholder instead of being resolved, so plan-time diffing never has to
+/// touch the secret backend or show a credential on screen. `secret_cache`
+/// memoizes resolved values across calls within the same run, so a secret
+/// used in several templates is only fetched once.
+pub fn render(
+    content: &str,
+    vars: &BTreeMap<String, TemplateValue>,
+    resolve_secrets: bool,
+    secret_cache: &secrets::Cache,
+) -> Result<String> {
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        let Some(end) = after_open.find("}}") else {
+            bail!("unterminated `{{{{` placeholder in template");
+        };
+        let tag = after_open[..end].trim();
+        rest = &after_open[end + 2..];
+
+        if let Some(list_path) = tag.strip_prefix("#each ") {
+            let list_path = list_path.trim();
+            let Some(close_start) = rest.find(EACH_CLOSE_TAG) else {
+                bail!("`{{{{#each {list_path}}}}}` has no matching `{{{{/each}}}}`");
+            };
+            let body = &rest[..close_start];
+            rest = &rest[close_start + EACH_CLOSE_TAG.len()..];
+
+            for item in resolve_list(vars, list_path)? {
+                let mut scope = vars.clone();
+                scope.insert("this".to_string(), item.clone());
+                output.push_str(&render(body, &scope, resolve_secrets, secret_cache)?);
+            }
+            continue;
+        }
+
+        output.push_str(&resolve_path(vars, tag, resolve_secrets, secret_cache)?);
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+/// Normalizes every line ending in `content` to the style `newline` asks
+/// for, so a rendered template's line endings don't depend on which OS
+/// rendered it. `content` is first collapsed to bare `\n` regardless of
+/// what it already contains, so this is idempotent no matter the input.
+pub fn normalize_newlines(content: &str, newline: Newline) -> String {
+    let lf = content.replace("\r\n", "\n");
+    match newline {
+        Newline::Lf => lf,
+        Newline::Crlf => lf.replace('\n', "\r\n"),
+        Newline::Native => {
+            if cfg!(windows) {
+                lf.replace('\n', "\r\n")
+            } else {
+                lf
+            }
+        }
+    }
+}
+
+/// Resolves every value in `vars` to a plain string, e.g. for `cmd()`'s
+/// `env` option. No dotted paths or `#each` loops here: an env var is always
+/// flat. Same secret-redaction rules as [`render`].
+pub fn resolve_map(
+    vars: &BTreeMap<String, TemplateValue>,
+    resolve_secrets: bool,
+    secret_cache: &secrets::Cache,
+) -> Result<BTreeMap<String, String>> {
+    vars.iter()
+        .map(|(name, value)| {
+            Ok((
+                name.clone(),
+                resolve_leaf(value, resolve_secrets, secret_cache)?,
+            ))
+        })
+        .collect()
+}
+
+/// Resolves a dotted path like `host.user` or `hosts.0.user` against `vars`
+/// down to a plain string.
+fn resolve_path(
+    vars: &BTreeMap<String, TemplateValue>,
+    path: &str,
+    resolve_secrets: bool,
+    secret_cache: &secrets::Cache,
+) -> Result<String> {
+    resolve_leaf(lookup(vars, path)?, resolve_secrets, secret_cache)
+}
+
+fn resolve_list<'a>(
+    vars: &'a BTreeMap<String, TemplateValue>,
+    path: &str,
+) -> Result<&'a [TemplateValue]> {
+    match lookup(vars, path)? {
+        TemplateValue::List(items) => Ok(items),
+        _ => bail!("`{{{{#each {path}}}}}` requires a list variable, but `{path}` isn't one"),
+    }
+}
+
+fn lookup<'a>(vars: &'a BTreeMap<String, TemplateValue>, path: &str) -> Result<&'a TemplateValue> {
+    let mut segments = path.split('.');
+    let root = segments.next().unwrap_or(path);
+    let mut value = vars
+        .get(root)
+        .ok_or_else(|| anyhow::anyhow!("template variable `{root}` is not defined"))?;
+
+    for segment in segments {
+        value = descend(value, segment).with_context(|| format!("template variable `{path}`"))?;
+    }
+    Ok(value)
+}
+
+fn descend<'a>(value: &'a TemplateValue, segment: &str) -> Result<&'a TemplateValue> {
+    match value {
+        TemplateValue::Table(fields) => fields
+            .get(segment)
+            .ok_or_else(|| anyhow::anyhow!("has no field named `{segment}`")),
+        TemplateValue::List(items) => {
+            let index: usize = segment
+                .parse()
+                .map_err(|_| anyhow::anyhow!("`{segment}` isn't a list index"))?;
+            items.get(index).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "index {index} is out of range (list has {} item(s))",
+                    items.len()
+                )
+            })
+        }
+        TemplateValue::Str(_)
+        | TemplateValue::SensitiveStr(_)
+        | TemplateValue::Secret(_)
+        | TemplateValue::EnvVar(_) => {
+            bail!("can't look up `{segment}` on a plain value")
+        }
+    }
+}
+
+fn resolve_leaf(
+    value: &TemplateValue,
+    resolve_secrets: bool,
+    secret_cache: &secrets::Cache,
+) -> Result<String> {
+    match value {
+        TemplateValue::Str(s) => Ok(s.clone()),
+        TemplateValue::SensitiveStr(s) => {
+            if resolve_secrets {
+                Ok(s.clone())
+            } else {
+                Ok(secrets::REDACTED_PLACEHOLDER.to_string())
+            }
+        }
+        TemplateValue::Secret(secret_ref) => {
+            if resolve_secrets {
+                secret_cache.resolve(&secret_ref.uri)
+            } else {
+                Ok(secrets::REDACTED_PLACEHOLDER.to_string())
+            }
+        }
+        TemplateValue::EnvVar(env_var) => {
+            let value = std::env::var(&env_var.name)
+                .with_context(|| format!("environment variable `{}` is not set", env_var.name))?;
+            if env_var.sensitive && !resolve_secrets {
+                Ok(secrets::REDACTED_PLACEHOLDER.to_string())
+            } else {
+                Ok(value)
+            }
+        }
+        TemplateValue::List(_) => {
+            bail!("can't render a list directly; loop over it with `{{#each name}}...{{/each}}`")
+        }
+        TemplateValue::Table(_) => {
+            bail!("can't render a table directly; access a field with `name.field`")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::render;
+    use crate::resource::TemplateValue;
+    use crate::secrets;
+
+    fn str(value: &str) -> TemplateValue {
+        TemplateValue::Str(value.to_string())
+    }
+
+    #[test]
+    fn resolves_a_dotted_path_through_nested_tables() {
+        let mut inner = BTreeMap::new();
+        inner.insert("user".to_string(), str("aki"));
+        let mut vars = BTreeMap::new();
+        vars.insert("host".to_string(), TemplateValue::Table(inner));
+
+        let out = render(
+            "hello {{ host.user }}",
+            &vars,
+            true,
+            &secrets::Cache::default(),
+        )
+        .unwrap();
+        assert_eq!(out, "hello aki");
+    }
+
+    #[test]
+    fn each_binds_this_and_restores_the_outer_scope_after_the_loop() {
+        let mut vars = BTreeMap::new();
+        vars.insert("this".to_string(), str("outer"));
+        vars.insert(
+            "items".to_string(),
+            TemplateValue::List(vec![str("a"), str("b")]),
+        );
+
+        let out = render(
+            "before {{#each items}}[{{ this }}]{{/each}} after {{ this }}",
+            &vars,
+            true,
+            &secrets::Cache::default(),
+        )
+        .unwrap();
+        assert_eq!(out, "before [a][b] after outer");
+    }
+
+    #[test]
+    fn each_over_a_list_of_tables_resolves_dotted_fields_on_this() {
+        let mut item = BTreeMap::new();
+        item.insert("name".to_string(), str("keron"));
+        let mut vars = BTreeMap::new();
+        vars.insert(
+            "pkgs".to_string(),
+            TemplateValue::List(vec![TemplateValue::Table(item)]),
+        );
+
+        let out = render(
+            "{{#each pkgs}}{{ this.name }}{{/each}}",
+            &vars,
+            true,
+            &secrets::Cache::default(),
+        )
+        .unwrap();
+        assert_eq!(out, "keron");
+    }
+
+    #[test]
+    fn each_over_a_non_list_variable_errors() {
+        let mut vars = BTreeMap::new();
+        vars.insert("pkgs".to_string(), str("not-a-list"));
+
+        let err = render(
+            "{{#each pkgs}}{{ this }}{{/each}}",
+            &vars,
+            true,
+            &secrets::Cache::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("requires a list variable"));
+    }
+
+    #[test]
+    fn unterminated_each_errors_instead_of_looping_forever() {
+        let vars = BTreeMap::new();
+        let err = render(
+            "{{#each pkgs}}oops",
+            &vars,
+            true,
+            &secrets::Cache::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("no matching"));
+    }
+
+    #[test]
+    fn unresolved_secret_is_redacted_but_still_renders() {
+        let mut vars = BTreeMap::new();
+        vars.insert(
+            "password".to_string(),
+            TemplateValue::SensitiveStr("hunter2".to_string()),
+        );
+
+        let out = render(
+            "pw={{ password }}",
+            &vars,
+            false,
+            &secrets::Cache::default(),
+        )
+        .unwrap();
+        assert_eq!(out, format!("pw={}", secrets::REDACTED_PLACEHOLDER));
+    }
+}
@@ -0,0 +1,58 @@
+//! Shared output formatting for `keron plan` / `keron apply`: consistent
+//! `~` shortening of paths under `$HOME`, with an `--absolute-paths` opt-out.
+
+use std::io::IsTerminal;
+
+use crate::xdg;
+
+/// Replaces the user's home directory prefix in `text` with `~`, unless
+/// `absolute_paths` is set. Uses the invoking user's home when keron is run
+/// under `sudo`, matching [`xdg::cache_dir`]'s resolution.
+pub fn shorten_paths(text: &str, absolute_paths: bool) -> String {
+    if absolute_paths {
+        return text.to_string();
+    }
+
+    let home = xdg::home_dir();
+    let home = home.to_string_lossy();
+    if home.is_empty() || home == "." {
+        return text.to_string();
+    }
+
+    text.replace(home.as_ref(), "~")
+}
+
+/// Wraps `text` in ANSI "dim" styling when [`should_color`] says to, for
+/// secondary detail (e.g. an operation's owning manifest) that shouldn't
+/// compete with the primary content around it.
+pub fn dim(text: &str) -> String {
+    if should_color() {
+        format!("\x1b[2m{text}\x1b[0m")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Whether ANSI styling should be used, honoring the `NO_COLOR`
+/// (<https://no-color.org>) and `CLICOLOR`/`CLICOLOR_FORCE`
+/// (<https://bixense.com/clicolors>) conventions on top of the usual TTY
+/// check: `NO_COLOR` always disables color when set (to any value, even
+/// empty); `CLICOLOR_FORCE` (to anything but `0`) forces it on even off a
+/// TTY; `CLICOLOR=0` forces it off even on one. Falls back to whether
+/// stdout is a terminal when none of those are set.
+pub fn should_color() -> bool {
+    if std::env::var_os("NO_COLOR").is_some() {
+        return false;
+    }
+    if let Some(value) = std::env::var_os("CLICOLOR_FORCE") {
+        if value != "0" {
+            return true;
+        }
+    }
+    if let Some(value) = std::env::var_os("CLICOLOR") {
+        if value == "0" {
+            return false;
+        }
+    }
+    std::io::stdout().is_terminal()
+}
@@ -0,0 +1,411 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// A single managed unit declared by a manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Resource {
+    Link(LinkResource),
+    Template(TemplateResource),
+    GitRepo(GitRepoResource),
+    FileBlock(FileBlockResource),
+    Cmd(CmdResource),
+    Dir(DirResource),
+    PipxPackage(PipxPackageResource),
+    CargoPackage(CargoPackageResource),
+    AgeFile(AgeFileResource),
+}
+
+impl Resource {
+    pub fn describe(&self) -> String {
+        match self {
+            Resource::Link(link) => match link.windows_fallback {
+                Some(fallback) => format!(
+                    "link {} -> {} (windows fallback: {})",
+                    link.destination.display(),
+                    link.source.display(),
+                    fallback.label()
+                ),
+                None => format!(
+                    "link {} -> {}",
+                    link.destination.display(),
+                    link.source.display()
+                ),
+            },
+            Resource::Template(template) => {
+                format!(
+                    "template {} -> {}",
+                    template.destination.display(),
+                    template.source.display()
+                )
+            }
+            Resource::GitRepo(git_repo) => {
+                format!(
+                    "git_repo {} -> {}",
+                    git_repo.destination.display(),
+                    git_repo.url
+                )
+            }
+            Resource::FileBlock(file_block) => {
+                format!(
+                    "file_block {} ({})",
+                    file_block.destination.display(),
+                    file_block.marker
+                )
+            }
+            Resource::Cmd(cmd) => match &cmd.creates {
+                Some(creates) => format!("cmd `{}` (creates {})", cmd.command, creates.display()),
+                None => format!("cmd `{}`", cmd.command),
+            },
+            Resource::Dir(dir) => format!("dir {}", dir.path.display()),
+            Resource::PipxPackage(package) => match &package.version {
+                Some(version) if version.starts_with(|c: char| "=!<>~".contains(c)) => format!(
+                    "pipx_package {}{} via {}",
+                    package.name,
+                    version,
+                    package.provider.binary()
+                ),
+                Some(version) => format!(
+                    "pipx_package {}=={} via {}",
+                    package.name,
+                    version,
+                    package.provider.binary()
+                ),
+                None => format!(
+                    "pipx_package {} via {}",
+                    package.name,
+                    package.provider.binary()
+                ),
+            },
+            Resource::CargoPackage(package) => {
+                let locked = if package.locked { " (locked)" } else { "" };
+                match (&package.git, &package.version) {
+                    (Some(git), _) => {
+                        format!("cargo_package {} via git {git}{locked}", package.name)
+                    }
+                    (None, Some(version)) => {
+                        format!("cargo_package {}@{version}{locked}", package.name)
+                    }
+                    (None, None) => format!("cargo_package {}{locked}", package.name),
+                }
+            }
+            Resource::AgeFile(age_file) => format!(
+                "template_encrypted {} -> {}",
+                age_file.destination.display(),
+                age_file.source.display()
+            ),
+        }
+    }
+
+    /// A short, stable name for this resource's constructor, used (together
+    /// with the declaring manifest's path and [`destination`](Self::destination))
+    /// to derive [`crate::plan::PlannedOperation::id`], a resource identifier
+    /// that survives a manifest being reordered or having unrelated
+    /// resources added around it.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Resource::Link(_) => "link",
+            Resource::Template(_) => "template",
+            Resource::GitRepo(_) => "git_repo",
+            Resource::FileBlock(_) => "file_block",
+            Resource::Cmd(_) => "cmd",
+            Resource::Dir(_) => "dir",
+            Resource::PipxPackage(_) => "pipx_package",
+            Resource::CargoPackage(_) => "cargo_package",
+            Resource::AgeFile(_) => "template_encrypted",
+        }
+    }
+
+    pub fn destination(&self) -> &std::path::Path {
+        match self {
+            Resource::Link(link) => &link.destination,
+            Resource::Template(template) => &template.destination,
+            Resource::GitRepo(git_repo) => &git_repo.destination,
+            Resource::FileBlock(file_block) => &file_block.destination,
+            Resource::Cmd(cmd) => cmd
+                .creates
+                .as_deref()
+                .unwrap_or_else(|| std::path::Path::new("")),
+            Resource::Dir(dir) => &dir.path,
+            // Not filesystem-path-addressable; nothing consults this for a
+            // `PipxPackage`/`CargoPackage`, same as a `Cmd` without `creates`.
+            Resource::PipxPackage(_) => std::path::Path::new(""),
+            Resource::CargoPackage(_) => std::path::Path::new(""),
+            Resource::AgeFile(age_file) => &age_file.destination,
+        }
+    }
+}
+
+/// Symlinks `destination` to `source`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkResource {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// What to do if a real symlink can't be created, e.g. on Windows
+    /// without Developer Mode enabled. `None` means fail loudly instead of
+    /// silently applying a different kind of link than the manifest asked
+    /// for.
+    pub windows_fallback: Option<LinkFallback>,
+    /// When `destination` is already a plain file with the same content
+    /// `source` would have (or `source` doesn't exist yet), replace it with
+    /// the managed symlink as an "adopt" rather than an ordinary `Update`,
+    /// without requiring `--backup-dir`/`--use-trash` to feel safe about it.
+    /// If `source` doesn't exist yet, its content is seeded from
+    /// `destination` first.
+    pub adopt: bool,
+    /// Desired owning user/group for `destination`, applied via `chown`
+    /// after the symlink itself is up to date. `None` leaves that side of
+    /// ownership alone, beyond the invoking-uid reclaim `sudo::
+    /// chown_to_invoker` already does.
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Run the `owner`/`group` chown with the detected privilege escalation
+    /// helper. Required when `owner`/`group` names anyone other than the
+    /// current user, since only root can give a file away.
+    pub elevate: bool,
+    /// `absent` plans and applies removal of a previously managed symlink
+    /// instead of creating/updating one. See [`State`].
+    pub state: State,
+}
+
+/// Whether a `link()`/`template()` resource should exist (the default) or be
+/// removed if it's still there. Kept per-resource rather than as a top-level
+/// `remove()` constructor so the same `name = "..."`/`after = {...}` opts and
+/// the same destination stay meaningful across a manifest edit that flips a
+/// dotfile from managed to unmanaged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum State {
+    Present,
+    Absent,
+}
+
+/// A degraded stand-in for a symlink `link()` may fall back to via
+/// `windows_fallback` when the platform refuses to create a real one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkFallback {
+    /// A directory junction. Doesn't require the elevated privilege plain
+    /// symlinks need on Windows, but only applies to directories.
+    Junction,
+    /// A hard link. Only applies to files, and only within the same volume.
+    Hardlink,
+    /// A plain copy of `source`'s contents at apply time. Always available,
+    /// but the destination silently stops tracking `source` afterward.
+    Copy,
+}
+
+impl LinkFallback {
+    pub fn label(self) -> &'static str {
+        match self {
+            LinkFallback::Junction => "junction",
+            LinkFallback::Hardlink => "hardlink",
+            LinkFallback::Copy => "copy",
+        }
+    }
+}
+
+/// Renders `source` (with `{{ variable }}` placeholders) into `destination`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateResource {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub vars: BTreeMap<String, TemplateValue>,
+    /// Line ending to normalize the rendered output to. `None` leaves
+    /// whatever the source file and `{{ }}` substitutions happen to produce
+    /// untouched, matching the pre-existing behavior.
+    pub newline: Option<Newline>,
+    /// Desired owning user/group for `destination`, applied via `chown`
+    /// after the rendered content is up to date. See [`LinkResource::owner`].
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Run the `owner`/`group` chown with the detected privilege escalation
+    /// helper. See [`LinkResource::elevate`].
+    pub elevate: bool,
+    /// `absent` plans and applies removal of a previously rendered file
+    /// instead of rendering/updating one. See [`State`].
+    pub state: State,
+}
+
+/// A line-ending style `template()` can normalize its rendered output to,
+/// so a template shared between hosts doesn't flap between LF and CRLF on
+/// every `keron plan` depending on which OS last rendered it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Newline {
+    Crlf,
+    Lf,
+    /// `\r\n` on Windows, `\n` everywhere else.
+    Native,
+}
+
+/// A value bound to a template variable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TemplateValue {
+    Str(String),
+    /// A plain string explicitly named in `sensitive_vars = {...}` on the
+    /// resource's constructor, e.g. a token embedded directly in a manifest
+    /// rather than behind `secret_ref()`. Unlike `Secret`, the value is
+    /// already known without touching any backend, but it's still treated
+    /// as sensitive for change-detection: rendered as the redaction
+    /// placeholder unless `--resolve-secrets`, the same as `Secret`.
+    SensitiveStr(String),
+    /// A lazy handle to a secret, created via `secret_ref(uri)`. Only
+    /// resolved at apply time (or with `--resolve-secrets` at plan time),
+    /// so dry-runs never touch the secret backend.
+    Secret(SecretRef),
+    /// A value read from the host process's environment, created via
+    /// `env(name)`. Unlike `secret_ref()` there's no backend to avoid
+    /// hitting, so it's still looked up during a plain `keron plan`; `sensitive`
+    /// only controls whether it's shown in a plan-time diff and added to
+    /// `cmd()`'s output redaction list.
+    EnvVar(EnvVarValue),
+    /// A Lua array table, e.g. a list of SSH hosts to loop over with
+    /// `{{#each name}}...{{/each}}`. Kept as a `Vec` (not a set) since
+    /// declaration order is almost always the order the manifest author
+    /// wants it rendered in.
+    List(Vec<TemplateValue>),
+    /// A Lua table with string keys, accessed field-by-field as
+    /// `{{ name.field }}`. A `BTreeMap` rather than a `HashMap` so nested
+    /// vars never make plan output non-deterministic between runs.
+    Table(BTreeMap<String, TemplateValue>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretRef {
+    pub uri: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvVarValue {
+    pub name: String,
+    /// Whether to treat the value as sensitive, i.e. redact it from a
+    /// plan-time diff and from captured `cmd()` output. Defaults to `true`
+    /// via `env(name, { sensitive = false })`, since host environment
+    /// variables (`AWS_SECRET_ACCESS_KEY`, `GITHUB_TOKEN`, ...) commonly
+    /// carry credentials.
+    pub sensitive: bool,
+}
+
+/// Clones (or keeps up to date) a git repository at `destination`, e.g. for
+/// plugin managers like oh-my-zsh or tpm.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitRepoResource {
+    pub url: String,
+    pub destination: PathBuf,
+    pub reference: Option<String>,
+    pub depth: Option<u32>,
+}
+
+/// Inserts or replaces a marked block inside a file the manifest doesn't
+/// otherwise own, keeping the rest of the file untouched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileBlockResource {
+    pub destination: PathBuf,
+    pub content: String,
+    pub marker: String,
+}
+
+/// Runs a shell command. Without an idempotency condition, keron has no way
+/// to tell whether the command already ran, so it always reports as
+/// pending. `creates` (optionally checked against `creates_hash`) treats the
+/// command as a no-op once that path exists; `unless`/`only_if` instead run
+/// a guard command and inspect its exit status. Checked in that order.
+/// `env` and `cwd` apply to the command itself and, where relevant, to its
+/// `unless`/`only_if` guards. `retries` and `timeout` apply only to the
+/// command's own execution: on a flaky network, a package install shouldn't
+/// have to be re-run from scratch just because it timed out once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CmdResource {
+    pub command: String,
+    pub creates: Option<PathBuf>,
+    pub creates_hash: Option<String>,
+    pub unless: Option<String>,
+    pub only_if: Option<String>,
+    pub env: BTreeMap<String, TemplateValue>,
+    pub cwd: Option<PathBuf>,
+    pub retries: u32,
+    pub timeout: Duration,
+}
+
+/// Guarantees a directory exists with a given `mode`, independent of the
+/// `mkdirs` side effect other resources apply to their own parent
+/// directories.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirResource {
+    pub path: PathBuf,
+    pub mode: Option<u32>,
+    pub mkdirs: bool,
+    pub elevate: bool,
+}
+
+/// Which underlying tool installs and lists `PipxPackageResource` packages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PipxProvider {
+    Pipx,
+    Uv,
+}
+
+impl PipxProvider {
+    pub fn binary(self) -> &'static str {
+        match self {
+            PipxProvider::Pipx => "pipx",
+            PipxProvider::Uv => "uv",
+        }
+    }
+}
+
+/// Installs a Python CLI tool (ruff, poetry, httpie, ...) into its own
+/// isolated environment via `pipx` or `uv tool`. Idempotency is checked by
+/// parsing the provider's own package listing rather than a `creates`/
+/// `unless` guard, since keron has no generic way to ask "is this already
+/// installed" for an arbitrary tool name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipxPackageResource {
+    pub name: String,
+    pub version: Option<String>,
+    pub provider: PipxProvider,
+    /// How many times to retry the install (with exponential backoff) if it
+    /// fails, same as `cmd()`'s `retries` — a flaky network shouldn't fail
+    /// the whole apply over a single dropped connection.
+    pub retries: u32,
+    /// Wall-clock budget for a single install attempt, same as `cmd()`'s
+    /// `timeout`.
+    pub timeout: Duration,
+}
+
+/// Installs a Rust binary via `cargo install`. Idempotency is checked by
+/// parsing `cargo install --list` the same way `PipxPackageResource` parses
+/// its provider's own listing; a `git` install already present is
+/// considered up to date once it shows up there at all, since `cargo
+/// install --list` reports the version it built, not whether upstream has
+/// since moved past the commit that was checked out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CargoPackageResource {
+    pub name: String,
+    pub version: Option<String>,
+    /// `cargo install --locked` — use the crate's checked-in `Cargo.lock`
+    /// instead of re-resolving dependencies.
+    pub locked: bool,
+    /// `cargo install --git <url>` instead of installing from crates.io.
+    pub git: Option<String>,
+    /// `cargo install --features <a,b,c>`.
+    pub features: Vec<String>,
+    /// How many times to retry the install (with exponential backoff) if it
+    /// fails, same as `cmd()`'s `retries` — a `cargo install` of anything
+    /// non-trivial can outlast a single flaky network blip.
+    pub retries: u32,
+    /// Wall-clock budget for a single install attempt, same as `cmd()`'s
+    /// `timeout`.
+    pub timeout: Duration,
+}
+
+/// Decrypts an age-encrypted `source` file into plaintext at `destination`,
+/// e.g. for a whole secrets file (`ssh config`, an API token dump) that
+/// should live in the dotfiles repo encrypted rather than being templated
+/// value-by-value with `secret_ref()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgeFileResource {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub identity: PathBuf,
+}
@@ -0,0 +1,211 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+
+use crate::subprocess::{self, Limits};
+
+/// Placeholder text substituted for a secret when it must not actually be
+/// resolved (plain dry-run planning), so change-detection still works
+/// without ever touching the secret backend.
+pub const REDACTED_PLACEHOLDER: &str = "<secret:redacted>";
+
+/// Memoizes resolved secret values for the lifetime of one plan/apply run,
+/// keyed by URI, so a `secret_ref()` used across several templates only
+/// prompts the backend (1Password, ...) once instead of once per use.
+#[derive(Default)]
+pub struct Cache(RefCell<HashMap<String, String>>);
+
+impl Cache {
+    /// Resolves `uri`, reusing a value already resolved earlier in this run.
+    pub fn resolve(&self, uri: &str) -> Result<String> {
+        if let Some(cached) = self.0.borrow().get(uri) {
+            tracing::debug!(
+                uri,
+                "secret already resolved this run, reusing cached value"
+            );
+            return Ok(cached.clone());
+        }
+
+        tracing::debug!(uri, "resolving secret_ref()");
+        let value = resolve(uri)?;
+        self.0.borrow_mut().insert(uri.to_string(), value.clone());
+        Ok(value)
+    }
+}
+
+/// Resolves a secret reference URI against its backend.
+///
+/// Supports `op://` (1Password CLI) and `keyring://service/account` (the OS
+/// keychain: Keychain Services on macOS, Secret Service on Linux,
+/// Credential Manager on Windows).
+pub fn resolve(uri: &str) -> Result<String> {
+    if let Some(rest) = uri.strip_prefix("op://") {
+        return resolve_1password(rest);
+    }
+    if let Some(rest) = uri.strip_prefix("keyring://") {
+        return resolve_keyring(rest);
+    }
+    bail!("unsupported secret reference `{uri}` (only op:// and keyring:// are supported)");
+}
+
+/// Replaces every occurrence of a resolved secret value in `text` with the
+/// redaction placeholder, e.g. before folding captured command output into
+/// an error message or report.
+pub fn redact(text: &str, secret_values: &[String]) -> String {
+    let mut redacted = text.to_string();
+    for value in secret_values {
+        if !value.is_empty() {
+            redacted = redacted.replace(value.as_str(), REDACTED_PLACEHOLDER);
+        }
+    }
+    redacted
+}
+
+/// User-configured regex patterns, plus how thoroughly a match should be
+/// hidden, from the `redact_patterns`/`redact_partial` keys in keron's
+/// global config. Layered on top of [`redact`] for text that merely *looks*
+/// like a secret (a token pasted into a `cmd()`'s stdout) that keron never
+/// resolved itself, so has no exact value to match against.
+#[derive(Default)]
+pub struct RedactionRules {
+    patterns: Vec<Regex>,
+    partial: bool,
+}
+
+impl RedactionRules {
+    /// Loads `redact_patterns`/`redact_partial` from keron's global config
+    /// file. An absent file, or one without either key, yields no patterns —
+    /// behavior identical to before these existed.
+    pub fn load() -> Result<Self> {
+        let config = crate::source::global_config()?;
+        let patterns = config
+            .redact_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .with_context(|| format!("invalid `redact_patterns` entry `{pattern}`"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            patterns,
+            partial: config.redact_partial,
+        })
+    }
+}
+
+/// Runs `rules.patterns` over `text`, masking each match fully or partially
+/// (`gh****23`) per `rules.partial`. Meant to run after [`redact`], to catch
+/// secret-shaped substrings it didn't already know to look for.
+pub fn redact_patterns(text: &str, rules: &RedactionRules) -> String {
+    let mut redacted = text.to_string();
+    for pattern in &rules.patterns {
+        redacted = pattern
+            .replace_all(&redacted, |caps: &regex::Captures| {
+                mask(&caps[0], rules.partial)
+            })
+            .into_owned();
+    }
+    redacted
+}
+
+/// Masks `value` fully, or partially (first/last two characters kept, the
+/// rest replaced with `*`) so a redacted report stays debuggable without
+/// leaking the value itself. Falls back to full masking when `value` is too
+/// short to leave anything meaningfully hidden.
+fn mask(value: &str, partial: bool) -> String {
+    if !partial {
+        return REDACTED_PLACEHOLDER.to_string();
+    }
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 4 {
+        return REDACTED_PLACEHOLDER.to_string();
+    }
+    let first: String = chars[..2].iter().collect();
+    let last: String = chars[chars.len() - 2..].iter().collect();
+    format!("{first}****{last}")
+}
+
+fn resolve_1password(reference: &str) -> Result<String> {
+    let mut command = Command::new("op");
+    command.arg("read").arg(format!("op://{reference}"));
+
+    let output = subprocess::run_captured(&mut command, &Limits::default())
+        .context("failed to run `op read`; is the 1Password CLI installed and signed in?")?;
+
+    if !output.status.success() {
+        bail!(
+            "`op read op://{reference}` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)?
+        .trim_end_matches('\n')
+        .to_string())
+}
+
+/// Resolves `service/account` from the OS keychain, without shelling out to
+/// any external CLI.
+fn resolve_keyring(reference: &str) -> Result<String> {
+    let (service, account) = reference.split_once('/').ok_or_else(|| {
+        anyhow::anyhow!(
+            "invalid `keyring://` reference `{reference}` (expected `keyring://service/account`)"
+        )
+    })?;
+
+    let entry = keyring::Entry::new(service, account)
+        .with_context(|| format!("failed to open keychain entry `{service}/{account}`"))?;
+
+    entry
+        .get_password()
+        .with_context(|| format!("failed to read keychain entry `{service}/{account}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::{mask, redact, redact_patterns, RedactionRules, REDACTED_PLACEHOLDER};
+
+    fn rules(patterns: &[&str], partial: bool) -> RedactionRules {
+        RedactionRules {
+            patterns: patterns.iter().map(|p| Regex::new(p).unwrap()).collect(),
+            partial,
+        }
+    }
+
+    #[test]
+    fn redact_replaces_every_occurrence_of_a_known_value() {
+        let out = redact("token=abc123 abc123 done", &["abc123".to_string()]);
+        assert_eq!(out, format!("token={p} {p} done", p = REDACTED_PLACEHOLDER));
+    }
+
+    #[test]
+    fn redact_ignores_empty_values() {
+        let out = redact("nothing to hide here", &["".to_string()]);
+        assert_eq!(out, "nothing to hide here");
+    }
+
+    #[test]
+    fn redact_patterns_fully_masks_matches_by_default() {
+        let rules = rules(&["gh[a-z0-9]+"], false);
+        let out = redact_patterns("key: ghp1234567890", &rules);
+        assert_eq!(out, format!("key: {REDACTED_PLACEHOLDER}"));
+    }
+
+    #[test]
+    fn redact_patterns_partially_masks_when_configured() {
+        let rules = rules(&["gh[a-z0-9]+"], true);
+        let out = redact_patterns("key: ghp1234567890", &rules);
+        assert_eq!(out, "key: gh****90");
+    }
+
+    #[test]
+    fn mask_falls_back_to_full_masking_for_short_values() {
+        assert_eq!(mask("abcd", true), REDACTED_PLACEHOLDER);
+        assert_eq!(mask("abcde", true), "ab****de");
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::subprocess::{self, Limits};
+use crate::xdg;
+
+/// keron's own global config file, distinct from a discovered tree's
+/// `keron.toml` (see [`crate::manifest`]'s `CONFIG_FILE_NAME`), which only
+/// lists that tree's manifests.
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+#[derive(Deserialize, Default)]
+pub(crate) struct GlobalConfig {
+    /// Default source (local path or git URL) to use when none is given on
+    /// the command line.
+    source: Option<String>,
+    /// Regex patterns matched against captured `cmd()` output and folded
+    /// into `secrets::RedactionRules`, for scrubbing secret-shaped text
+    /// keron never resolved itself so has no exact value to redact.
+    #[serde(default)]
+    pub(crate) redact_patterns: Vec<String>,
+    /// Keep the first and last two characters of a `redact_patterns` match
+    /// visible (`gh****23`) instead of fully hiding it.
+    #[serde(default)]
+    pub(crate) redact_partial: bool,
+}
+
+fn config_path() -> PathBuf {
+    xdg::config_dir().join("keron").join(CONFIG_FILE_NAME)
+}
+
+/// Reads and parses keron's own global config file, or its defaults if the
+/// file doesn't exist.
+pub(crate) fn global_config() -> Result<GlobalConfig> {
+    let config_path = config_path();
+    if !config_path.is_file() {
+        return Ok(GlobalConfig::default());
+    }
+    let contents = std::fs::read_to_string(&config_path)
+        .with_context(|| format!("failed to read `{}`", config_path.display()))?;
+    toml::from_str(&contents)
+        .with_context(|| format!("failed to parse `{}`", config_path.display()))
+}
+
+/// Resolves a source for a command that omitted one: the `source` key in
+/// keron's own config file, then a manifest tree detected in the current
+/// directory, then the conventional `~/.dotfiles`. Bails listing everything
+/// tried if none match.
+pub fn default_source() -> Result<String> {
+    if let Some(source) = global_config()?.source {
+        return Ok(source);
+    }
+
+    let cwd = std::env::current_dir().context("failed to read the current directory")?;
+    if looks_like_manifest_tree(&cwd) {
+        return Ok(cwd.to_string_lossy().into_owned());
+    }
+
+    let dotfiles = xdg::home_dir().join(".dotfiles");
+    if looks_like_manifest_tree(&dotfiles) {
+        return Ok(dotfiles.to_string_lossy().into_owned());
+    }
+
+    bail!(
+        "no source given and none could be found automatically; tried the `source` key in `{}`, the current directory (`{}`), and `{}` — pass one explicitly, e.g. `keron apply ~/dotfiles`",
+        config_path().display(),
+        cwd.display(),
+        dotfiles.display(),
+    );
+}
+
+/// A quick, non-exhaustive check for whether `path` looks like a keron
+/// manifest tree: either it declares its manifests explicitly, or it has at
+/// least one `*.lua` file directly inside it. Doesn't walk subdirectories,
+/// since that's `manifest::discover`'s job once a source is actually chosen.
+fn looks_like_manifest_tree(path: &Path) -> bool {
+    if !path.is_dir() {
+        return false;
+    }
+    if path.join("keron.toml").is_file() || path.join("globals.lua").is_file() {
+        return true;
+    }
+    std::fs::read_dir(path).is_ok_and(|entries| {
+        entries
+            .filter_map(std::result::Result::ok)
+            .any(|entry| entry.path().extension().is_some_and(|ext| ext == "lua"))
+    })
+}
+
+/// Clones and fetches get more slack than a quick metadata query, since
+/// they're bounded by network/repo size rather than a stuck process.
+const CLONE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A dotfiles source, as given on the command line.
+pub enum Source {
+    /// A git repository (remote or local), checked out into a cached
+    /// working directory.
+    Git {
+        url: String,
+        reference: Option<String>,
+    },
+    /// A path that already exists on disk, used in place.
+    Local { path: PathBuf },
+}
+
+impl Source {
+    pub fn parse(input: &str) -> Self {
+        if let Some(rest) = input.strip_prefix("git+file://") {
+            let (path, reference) = split_reference(rest);
+            return Source::Git {
+                url: format!("file://{path}"),
+                reference,
+            };
+        }
+
+        if input.starts_with("http://")
+            || input.starts_with("https://")
+            || input.starts_with("git@")
+            || input.starts_with("ssh://")
+        {
+            let (url, reference) = split_reference(input);
+            return Source::Git {
+                url: url.to_string(),
+                reference,
+            };
+        }
+
+        Source::Local {
+            path: PathBuf::from(input),
+        }
+    }
+}
+
+/// Splits a trailing `#<reference>` fragment (branch, tag, or commit) off a
+/// URL, as used by `git+file:///path#branch`.
+fn split_reference(input: &str) -> (&str, Option<String>) {
+    match input.split_once('#') {
+        Some((url, reference)) => (url, Some(reference.to_string())),
+        None => (input, None),
+    }
+}
+
+/// Options controlling how a `Source` is resolved.
+#[derive(Default)]
+pub struct ResolveOptions {
+    /// Force a fresh clone instead of reusing the on-disk cache.
+    pub refresh: bool,
+    /// Refuse remote sources instead of hitting the network for them.
+    pub offline: bool,
+}
+
+/// A source that has been made available on the local filesystem, together
+/// with the commit it resolved to (if it came from git).
+pub struct ResolvedSource {
+    pub path: PathBuf,
+    pub commit: Option<String>,
+}
+
+impl ResolvedSource {
+    pub fn root(&self) -> &Path {
+        &self.path
+    }
+}
+
+pub fn resolve_with(source: &Source, options: &ResolveOptions) -> Result<ResolvedSource> {
+    match source {
+        Source::Local { path } => {
+            if !path.exists() {
+                bail!("source path `{}` does not exist", path.display());
+            }
+            Ok(ResolvedSource {
+                path: path.clone(),
+                commit: None,
+            })
+        }
+        Source::Git { url, reference } => {
+            if options.offline {
+                bail!("source `{url}` is a remote git source, which --offline refuses to fetch");
+            }
+
+            let cache_path = cache_path_for(url);
+            clone_or_update(url, reference.as_deref(), &cache_path, options.refresh)?;
+
+            let commit = resolve_head_commit(&cache_path)?;
+
+            Ok(ResolvedSource {
+                path: cache_path,
+                commit: Some(commit),
+            })
+        }
+    }
+}
+
+/// Deterministic, collision-resistant-enough cache directory for a given
+/// source URL: `$XDG_CACHE_HOME/keron/sources/<hash>`.
+fn cache_path_for(url: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    let hash = hasher.finish();
+    xdg::cache_dir()
+        .join("keron")
+        .join("sources")
+        .join(format!("{hash:016x}"))
+}
+
+fn clone_or_update(
+    url: &str,
+    reference: Option<&str>,
+    cache_path: &Path,
+    refresh: bool,
+) -> Result<()> {
+    if refresh && cache_path.exists() {
+        tracing::debug!(url, cache = %cache_path.display(), "removing stale source cache for --refresh");
+        std::fs::remove_dir_all(cache_path)
+            .with_context(|| format!("failed to remove stale cache `{}`", cache_path.display()))?;
+    }
+
+    if cache_path.exists() {
+        tracing::debug!(url, reference, cache = %cache_path.display(), "reusing cached source, fetching and checking out");
+        fetch_and_checkout(cache_path, reference)
+    } else {
+        tracing::debug!(url, reference, cache = %cache_path.display(), "no cached source, cloning fresh");
+        if let Some(parent) = cache_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create `{}`", parent.display()))?;
+        }
+        clone_shallow(url, reference, cache_path)
+    }
+}
+
+fn clone_shallow(url: &str, reference: Option<&str>, cache_path: &Path) -> Result<()> {
+    let mut command = Command::new("git");
+    command.arg("clone").arg("--quiet").arg("--depth").arg("1");
+    if let Some(reference) = reference {
+        command.arg("--branch").arg(reference);
+    }
+    command.arg(url).arg(cache_path);
+
+    run_git(command, &format!("git clone {url}"))
+}
+
+fn fetch_and_checkout(cache_path: &Path, reference: Option<&str>) -> Result<()> {
+    let fetch_ref = reference.unwrap_or("HEAD");
+
+    let mut fetch = Command::new("git");
+    fetch
+        .arg("-C")
+        .arg(cache_path)
+        .arg("fetch")
+        .arg("--quiet")
+        .arg("--depth")
+        .arg("1")
+        .arg("origin")
+        .arg(fetch_ref);
+    run_git(fetch, &format!("git fetch --depth 1 origin {fetch_ref}"))?;
+
+    let mut checkout = Command::new("git");
+    checkout
+        .arg("-C")
+        .arg(cache_path)
+        .arg("checkout")
+        .arg("--quiet")
+        .arg("--detach")
+        .arg("FETCH_HEAD");
+    run_git(checkout, "git checkout FETCH_HEAD")
+}
+
+/// Runs a git subcommand, forwarding its output, and turns a non-zero exit
+/// into a clear error — recognizing SSH/HTTPS authentication failures
+/// specially so private-repo users know to check their agent/credentials
+/// rather than staring at a bare "clone failed".
+fn run_git(mut command: Command, description: &str) -> Result<()> {
+    let limits = Limits {
+        timeout: CLONE_TIMEOUT,
+        ..Limits::default()
+    };
+    let output = subprocess::run_captured(&mut command, &limits)
+        .with_context(|| format!("failed to run `{description}`"))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    std::io::Write::write_all(&mut std::io::stdout(), &output.stdout).ok();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    std::io::Write::write_all(&mut std::io::stderr(), stderr.as_bytes()).ok();
+
+    if is_authentication_failure(&stderr) {
+        bail!(
+            "`{description}` failed: authentication was rejected. For SSH URLs, make sure `ssh-agent` has the right key loaded (`ssh-add -l`); for HTTPS, check your credential helper."
+        );
+    }
+
+    bail!("`{description}` failed with {}", output.status);
+}
+
+fn is_authentication_failure(stderr: &str) -> bool {
+    const MARKERS: &[&str] = &[
+        "Permission denied (publickey)",
+        "Authentication failed",
+        "Could not read from remote repository",
+        "fatal: could not read Username",
+    ];
+    MARKERS.iter().any(|marker| stderr.contains(marker))
+}
+
+fn resolve_head_commit(repo: &Path) -> Result<String> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo).arg("rev-parse").arg("HEAD");
+
+    let output = subprocess::run_captured(&mut command, &Limits::default())
+        .context("failed to run `git rev-parse HEAD`")?;
+
+    if !output.status.success() {
+        bail!("`git rev-parse HEAD` failed with {}", output.status);
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
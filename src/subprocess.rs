@@ -0,0 +1,132 @@
+//! Shared subprocess execution for the various external tools keron shells
+//! out to (git, `op`, `chattr`, manifest-declared `cmd()`s, ...). Wraps a
+//! [`Command`] with a wall-clock timeout and, for output-capturing callers,
+//! a cap on how much stdout/stderr is kept in memory — so a stuck package
+//! manager or an unreachable git remote can't hang `keron` forever.
+
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+
+/// Default wall-clock budget for a single subprocess call.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default cap on captured stdout/stderr, per stream.
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 8 * 1024 * 1024;
+
+/// Caps applied to a subprocess run via [`run_captured`].
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub timeout: Duration,
+    pub max_output_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+        }
+    }
+}
+
+pub struct Output {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Runs `command` to completion, capturing stdout/stderr (each truncated at
+/// `limits.max_output_bytes`) and killing it if it outlives `limits.timeout`.
+pub fn run_captured(command: &mut Command, limits: &Limits) -> Result<Output> {
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = command.spawn().context("failed to spawn subprocess")?;
+
+    let mut stdout_pipe = child.stdout.take().expect("stdout was requested as piped");
+    let mut stderr_pipe = child.stderr.take().expect("stderr was requested as piped");
+    let max_bytes = limits.max_output_bytes;
+
+    // Drain both pipes concurrently with the wait loop below, so a chatty
+    // child can't deadlock by filling one pipe's buffer while we're only
+    // watching the other.
+    let stdout_reader = std::thread::spawn(move || read_capped(&mut stdout_pipe, max_bytes));
+    let stderr_reader = std::thread::spawn(move || read_capped(&mut stderr_pipe, max_bytes));
+
+    let status = wait_with_timeout(&mut child, limits.timeout)
+        .context("while waiting for subprocess to exit")?;
+
+    let stdout = stdout_reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("stdout reader thread panicked"))?;
+    let stderr = stderr_reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("stderr reader thread panicked"))?;
+
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// Runs `command` to completion with whatever stdio it was configured with
+/// (typically inherited, for output the user should see live), killing it
+/// if it outlives `timeout`.
+pub fn run_with_timeout(command: &mut Command, timeout: Duration) -> Result<ExitStatus> {
+    let mut child = command.spawn().context("failed to spawn subprocess")?;
+    wait_with_timeout(&mut child, timeout)
+}
+
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().context("failed to poll subprocess")? {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("subprocess timed out after {timeout:?}");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn read_capped(pipe: &mut impl Read, max_bytes: usize) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let _ = pipe.take(max_bytes as u64).read_to_end(&mut buf);
+    buf
+}
+
+/// Delay before the first retry of [`retry_with_backoff`]; doubled after
+/// each further failed attempt.
+pub const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Calls `attempt` up to `retries + 1` times, doubling the delay between
+/// each failure, until it succeeds or the retry budget runs out — shared by
+/// every caller that wants `cmd()`'s "retry a flaky subprocess" behavior
+/// (package installs, ...) without hand-rolling the backoff loop. `attempt`
+/// receives the 1-based attempt number, for logging.
+pub fn retry_with_backoff<T>(retries: u32, mut attempt: impl FnMut(u32) -> Result<T>) -> Result<T> {
+    let mut delay = RETRY_BASE_DELAY;
+    let mut attempt_number = 0;
+    loop {
+        attempt_number += 1;
+        match attempt(attempt_number) {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt_number <= retries => {
+                eprintln!(
+                    "attempt {attempt_number}/{} failed, retrying in {delay:?}: {err}",
+                    retries + 1
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(err) => {
+                return Err(err.context(format!("gave up after {attempt_number} attempt(s)")))
+            }
+        }
+    }
+}
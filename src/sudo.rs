@@ -0,0 +1,62 @@
+//! Detects an invoking non-root user when keron itself is run under `sudo`,
+//! so `~` resolution and file ownership don't silently become root's.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::subprocess::{self, Limits};
+
+/// The user that invoked `sudo`, if keron is currently running under it.
+#[derive(Debug, Clone)]
+pub struct Invoker {
+    pub uid: u32,
+    pub gid: u32,
+    pub home: PathBuf,
+}
+
+/// Reads `SUDO_UID`/`SUDO_GID`/`SUDO_USER` and looks up the invoking user's
+/// home directory. Returns `None` outside of `sudo`, or if the environment
+/// is incomplete.
+pub fn detect() -> Option<Invoker> {
+    let uid: u32 = std::env::var("SUDO_UID").ok()?.parse().ok()?;
+    let gid: u32 = std::env::var("SUDO_GID").ok()?.parse().ok()?;
+    let user = std::env::var("SUDO_USER").ok()?;
+    let home = home_of(&user)?;
+
+    Some(Invoker { uid, gid, home })
+}
+
+fn home_of(user: &str) -> Option<PathBuf> {
+    let mut command = Command::new("getent");
+    command.arg("passwd").arg(user);
+    let output = subprocess::run_captured(&mut command, &Limits::default()).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let line = String::from_utf8(output.stdout).ok()?;
+    let home = line.trim().split(':').nth(5)?;
+    Some(PathBuf::from(home))
+}
+
+/// Changes ownership of `path` to `invoker`, so files and directories
+/// created while running under `sudo` don't end up owned by root. Symlinks
+/// are chowned themselves rather than the path they point to.
+pub fn chown_to_invoker(path: &Path, invoker: &Invoker, recursive: bool) -> Result<()> {
+    let mut command = Command::new("chown");
+    command.arg("-h");
+    if recursive {
+        command.arg("-R");
+    }
+    command
+        .arg(format!("{}:{}", invoker.uid, invoker.gid))
+        .arg(path);
+
+    let status = subprocess::run_with_timeout(&mut command, subprocess::DEFAULT_TIMEOUT)
+        .with_context(|| format!("failed to run `chown` on `{}`", path.display()))?;
+    if !status.success() {
+        bail!("`chown` failed for `{}` with {status}", path.display());
+    }
+    Ok(())
+}
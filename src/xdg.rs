@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+/// The user's cache directory, honouring `XDG_CACHE_HOME` and falling back
+/// to `~/.cache`.
+pub fn cache_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("XDG_CACHE_HOME") {
+        if !dir.is_empty() {
+            return PathBuf::from(dir);
+        }
+    }
+    home_dir().join(".cache")
+}
+
+/// The user's config directory, honouring `XDG_CONFIG_HOME` and falling
+/// back to `~/.config`.
+pub fn config_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("XDG_CONFIG_HOME") {
+        if !dir.is_empty() {
+            return PathBuf::from(dir);
+        }
+    }
+    home_dir().join(".config")
+}
+
+/// The user's data directory, honouring `XDG_DATA_HOME` and falling back to
+/// `~/.local/share`.
+pub fn data_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("XDG_DATA_HOME") {
+        if !dir.is_empty() {
+            return PathBuf::from(dir);
+        }
+    }
+    home_dir().join(".local/share")
+}
+
+/// The user's state directory, honouring `XDG_STATE_HOME` and falling back
+/// to `~/.local/state`.
+pub fn state_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("XDG_STATE_HOME") {
+        if !dir.is_empty() {
+            return PathBuf::from(dir);
+        }
+    }
+    home_dir().join(".local/state")
+}
+
+/// The Windows per-user roaming application data directory (`%APPDATA%`),
+/// so a manifest that targets both platforms doesn't need its own `if
+/// windows` branch just to place a config file. Falls back to
+/// [`config_dir`] on platforms that don't set `APPDATA`, which is the
+/// closest per-platform equivalent.
+pub fn appdata_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("APPDATA") {
+        if !dir.is_empty() {
+            return PathBuf::from(dir);
+        }
+    }
+    config_dir()
+}
+
+/// The user's home directory. When keron is run under `sudo`, this is the
+/// invoking user's home rather than root's.
+pub(crate) fn home_dir() -> PathBuf {
+    if let Some(invoker) = crate::sudo::detect() {
+        return invoker.home;
+    }
+    std::env::var("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("."))
+}
+
+/// Expands a leading `~` or `~/`-prefix to [`home_dir`], leaving every other
+/// path untouched. Doesn't understand `~other_user`, same as most shells'
+/// non-interactive tilde expansion.
+pub(crate) fn expand_tilde(path: &str) -> PathBuf {
+    match path.strip_prefix("~/").or_else(|| path.strip_prefix("~\\")) {
+        Some(rest) => home_dir().join(rest),
+        None if path == "~" => home_dir(),
+        None => PathBuf::from(path),
+    }
+}